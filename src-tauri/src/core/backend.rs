@@ -0,0 +1,149 @@
+use std::env;
+
+use super::command::{self, CommandBackend};
+use super::display::{Fits, Monitor};
+use super::fake::FakeBackend;
+use super::gnome::GnomeBackend;
+#[cfg(unix)]
+use super::hyprpaper::HyprpaperBackend;
+#[cfg(unix)]
+use super::kde::KdeBackend;
+#[cfg(unix)]
+use super::sway::SwayBackend;
+#[cfg(unix)]
+use super::x11::X11Backend;
+
+// Everything Osic needs from the desktop to enumerate monitors and put pictures on them.
+// Colors are 0xRRGGBB.
+pub trait WallpaperBackend: Send {
+    fn name(&self) -> &'static str;
+
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String>;
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String>;
+
+    // Announce a picture that is about to be shown, for backends that have to load it first.
+    fn preload(&mut self, _wallpaper: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String>;
+
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String>;
+
+    fn set_background_color(&mut self, color: u32) -> Result<(), String>;
+
+    fn get_background_color(&self) -> Result<u32, String>;
+}
+
+// OSIC_BACKEND forces a backend by name, otherwise a user defined commands.json wins
+// over the native backend of the platform.
+pub fn default_backend() -> Result<Box<dyn WallpaperBackend>, String> {
+    let forced = env::var("OSIC_BACKEND").unwrap_or_default();
+    let backend: Box<dyn WallpaperBackend> = match forced.as_str() {
+        "" if command::default_config_path().exists() => Box::new(CommandBackend::load()),
+        "" => return native_backend(),
+        "command" => Box::new(CommandBackend::load()),
+        "fake" => Box::new(FakeBackend::new()),
+        "gnome" => Box::new(GnomeBackend::gnome()),
+        "cinnamon" => Box::new(GnomeBackend::cinnamon()),
+        #[cfg(unix)]
+        "hyprpaper" => Box::new(HyprpaperBackend::new()),
+        #[cfg(unix)]
+        "kde" => Box::new(KdeBackend::new()),
+        #[cfg(unix)]
+        "sway" => Box::new(SwayBackend::new()),
+        #[cfg(unix)]
+        "x11" => Box::new(X11Backend::new()),
+        _ => return Err(format!("Unknown OSIC_BACKEND: {}", forced)),
+    };
+    Ok(backend)
+}
+
+fn no_backend(what: &str) -> String {
+    format!(
+        "No wallpaper backend for {}, pick one with OSIC_BACKEND or set up {}",
+        what,
+        command::default_config_path().display()
+    )
+}
+
+#[cfg(windows)]
+fn native_backend() -> Result<Box<dyn WallpaperBackend>, String> {
+    Ok(Box::new(super::win32::Win32API::new()))
+}
+
+// XDG_CURRENT_DESKTOP is a colon separated list such as "ubuntu:GNOME" or "X-Cinnamon".
+#[cfg(unix)]
+fn native_backend() -> Result<Box<dyn WallpaperBackend>, String> {
+    let desktop = env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_lowercase();
+    if desktop.contains("cinnamon") {
+        return Ok(Box::new(GnomeBackend::cinnamon()));
+    }
+    if desktop.contains("kde") {
+        return Ok(Box::new(KdeBackend::new()));
+    }
+    if desktop.contains("gnome") || desktop.contains("unity") || desktop.contains("budgie") {
+        return Ok(Box::new(GnomeBackend::gnome()));
+    }
+    if env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+        return Ok(Box::new(HyprpaperBackend::new()));
+    }
+    if env::var("SWAYSOCK").is_ok() || desktop.contains("sway") || desktop.contains("river") {
+        return Ok(Box::new(SwayBackend::new()));
+    }
+    if env::var("XDG_SESSION_TYPE").unwrap_or_default() == "x11" || env::var("DISPLAY").is_ok() {
+        return Ok(Box::new(X11Backend::new()));
+    }
+
+    Err(no_backend(&format!("the desktop \"{}\"", desktop)))
+}
+
+#[cfg(not(any(windows, unix)))]
+fn native_backend() -> Result<Box<dyn WallpaperBackend>, String> {
+    Err(no_backend("this platform"))
+}
+
+// Stands in when no backend could be picked, every call fails with the reason so the
+// UI can tell the user.
+pub struct NoBackend {
+    reason: String,
+}
+
+impl NoBackend {
+    pub fn new(reason: String) -> Self {
+        NoBackend { reason }
+    }
+}
+
+impl WallpaperBackend for NoBackend {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+        Err(self.reason.clone())
+    }
+
+    fn set_wallpaper(&mut self, _monitor_id: &str, _wallpaper: &str) -> Result<(), String> {
+        Err(self.reason.clone())
+    }
+
+    fn get_wallpaper(&self, _monitor_id: &str) -> Result<String, String> {
+        Err(self.reason.clone())
+    }
+
+    fn set_fit(&mut self, _fit: Fits) -> Result<(), String> {
+        Err(self.reason.clone())
+    }
+
+    fn set_background_color(&mut self, _color: u32) -> Result<(), String> {
+        Err(self.reason.clone())
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        Err(self.reason.clone())
+    }
+}
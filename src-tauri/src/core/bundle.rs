@@ -0,0 +1,574 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::cache::OsicMonitorSettings;
+use super::config::{self, MonitorConfig, OsicConfig};
+use super::display::Monitor;
+use super::edid::{self, MonitorFingerprint};
+
+pub const BUNDLE_VERSION: u32 = 1;
+
+// Paths under an album root are kept as "@album<n>/<path below the root>", so the
+// bundle doesn't care where the albums live on the machine that reads it.
+const ROOT_PREFIX: &str = "@album";
+
+// The whole configuration in one file to carry to another machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OsicBundle {
+    pub version: u32,
+    // where the albums were on the exporting machine
+    pub album_roots: Vec<PathBuf>,
+    pub config: OsicConfig,
+}
+
+// What an import would do, for the mapping step before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleInfo {
+    pub monitors: Vec<BundleMonitor>,
+    pub album_roots: Vec<BundleRoot>,
+    pub profiles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleMonitor {
+    pub device_id: String,
+    pub nickname: Option<String>,
+    pub fingerprint: Option<MonitorFingerprint>,
+    // the connected monitor it would go to without a mapping
+    pub suggested: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleRoot {
+    pub path: PathBuf,
+    // whether the folder is there on this machine as well
+    pub exists: bool,
+}
+
+// Every path a config holds.
+fn for_each_path<F>(config: &mut OsicConfig, f: &mut F)
+where
+    F: FnMut(&mut PathBuf),
+{
+    fn monitor<F: FnMut(&mut PathBuf)>(m: &mut MonitorConfig, f: &mut F) {
+        m.image.iter_mut().for_each(&mut *f);
+        m.album_path.iter_mut().for_each(&mut *f);
+        m.recent_images.iter_mut().for_each(&mut *f);
+        for a in m.time_albums.iter_mut() {
+            f(&mut a.album_path);
+        }
+    }
+
+    for m in config.monitors.iter_mut() {
+        monitor(m, f);
+    }
+    for g in config.groups.iter_mut() {
+        f(&mut g.album_path);
+    }
+    for p in config.profiles.iter_mut() {
+        for m in p.monitors.iter_mut() {
+            monitor(m, f);
+        }
+        for g in p.groups.iter_mut() {
+            f(&mut g.album_path);
+        }
+    }
+}
+
+// Album folders of the config, the outermost one where albums are nested.
+fn album_roots(config: &OsicConfig) -> Vec<PathBuf> {
+    let mut albums = Vec::new();
+    let monitors = config
+        .monitors
+        .iter()
+        .chain(config.profiles.iter().flat_map(|p| p.monitors.iter()));
+    for m in monitors {
+        albums.extend(m.album_path.clone());
+        albums.extend(m.time_albums.iter().map(|a| a.album_path.clone()));
+    }
+    let groups = config
+        .groups
+        .iter()
+        .chain(config.profiles.iter().flat_map(|p| p.groups.iter()));
+    albums.extend(groups.map(|g| g.album_path.clone()));
+
+    albums.sort();
+    albums.dedup();
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for a in albums {
+        if !roots.iter().any(|r| a.starts_with(r)) {
+            roots.push(a);
+        }
+    }
+    roots
+}
+
+fn to_portable(path: &Path, roots: &[PathBuf]) -> Option<PathBuf> {
+    roots.iter().enumerate().find_map(|(i, root)| {
+        let rest = path.strip_prefix(root).ok()?;
+        let mut portable = format!("{}{}", ROOT_PREFIX, i);
+        for c in rest.components() {
+            portable.push('/');
+            portable.push_str(&c.as_os_str().to_string_lossy());
+        }
+        Some(PathBuf::from(portable))
+    })
+}
+
+fn from_portable(path: &Path, roots: &[PathBuf]) -> Option<PathBuf> {
+    let text = path.to_str()?;
+    let rest = text.strip_prefix(ROOT_PREFIX)?;
+    let (index, rest) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let mut resolved = roots.get(index.parse::<usize>().ok()?)?.clone();
+    for part in rest.split('/').filter(|p| !p.is_empty()) {
+        resolved.push(part);
+    }
+    Some(resolved)
+}
+
+pub fn export(config: &OsicConfig) -> OsicBundle {
+    let roots = album_roots(config);
+    let mut config = config.clone();
+    for_each_path(&mut config, &mut |p| {
+        if let Some(portable) = to_portable(p, &roots) {
+            *p = portable;
+        }
+    });
+    OsicBundle {
+        version: BUNDLE_VERSION,
+        album_roots: roots,
+        config,
+    }
+}
+
+pub fn parse(text: &str) -> Result<OsicBundle, String> {
+    let bundle: OsicBundle =
+        serde_json::from_str(text).map_err(|e| format!("Parse bundle Error: {}", e))?;
+    if bundle.version > BUNDLE_VERSION {
+        return Err(format!(
+            "Bundle version {} was written by a newer Osic",
+            bundle.version
+        ));
+    }
+    // the config inside follows the same rules as the config file
+    config::validate(&bundle.config)?;
+    Ok(bundle)
+}
+
+// The connected monitor a bundle monitor belongs to: the same unit or model first,
+// then the same device path.
+fn suggest(m: &MonitorConfig, connected: &[Monitor]) -> Option<String> {
+    let saved = [OsicMonitorSettings::from(m.clone())];
+    connected
+        .iter()
+        .find(|c| edid::match_settings(c, &saved).is_some())
+        .map(|c| c.device_id.clone())
+}
+
+pub fn info(bundle: &OsicBundle, connected: &[Monitor]) -> BundleInfo {
+    BundleInfo {
+        monitors: bundle
+            .config
+            .monitors
+            .iter()
+            .map(|m| BundleMonitor {
+                device_id: m.device_id.clone(),
+                nickname: m.nickname.clone(),
+                fingerprint: m.fingerprint.clone(),
+                suggested: suggest(m, connected),
+            })
+            .collect(),
+        album_roots: bundle
+            .album_roots
+            .iter()
+            .map(|p| BundleRoot {
+                path: p.clone(),
+                exists: p.is_dir(),
+            })
+            .collect(),
+        profiles: bundle
+            .config
+            .profiles
+            .iter()
+            .map(|p| p.name.clone())
+            .collect(),
+    }
+}
+
+// The bundle's config for this machine. `monitors` maps bundle device ids to
+// connected ones, unmapped monitors go where `suggest` puts them and otherwise keep
+// their id for when they get plugged in. `roots` replaces the album roots by index,
+// a missing or empty one keeps the exporting machine's folder.
+pub fn import(
+    bundle: &OsicBundle,
+    connected: &[Monitor],
+    monitors: &HashMap<String, String>,
+    roots: &[PathBuf],
+) -> Result<OsicConfig, String> {
+    let roots = bundle
+        .album_roots
+        .iter()
+        .enumerate()
+        .map(|(i, r)| match roots.get(i) {
+            Some(p) if !p.as_os_str().is_empty() => p.clone(),
+            _ => r.clone(),
+        })
+        .collect::<Vec<PathBuf>>();
+    let mut config = bundle.config.clone();
+    for_each_path(&mut config, &mut |p| {
+        if let Some(resolved) = from_portable(p, &roots) {
+            *p = resolved;
+        }
+    });
+
+    let mut ids: HashMap<String, String> = HashMap::new();
+    for (from, to) in monitors {
+        if !connected.iter().any(|c| &c.device_id == to) {
+            return Err(format!("Unknown monitor: {}", to));
+        }
+        if ids.values().any(|id| id == to) {
+            return Err(format!("Monitor {} is mapped twice", to));
+        }
+        ids.insert(from.clone(), to.clone());
+    }
+    for m in &config.monitors {
+        if ids.contains_key(&m.device_id) {
+            continue;
+        }
+        if let Some(id) = suggest(m, connected) {
+            if !ids.values().any(|v| v == &id) {
+                ids.insert(m.device_id.clone(), id);
+            }
+        }
+    }
+
+    // a mapped monitor takes over the identity of the one it goes to, so it is
+    // recognised as that one from now on. An unmapped one whose id got taken goes.
+    let relabel = |monitors: &mut Vec<MonitorConfig>| {
+        monitors.retain(|m| {
+            ids.contains_key(&m.device_id) || !ids.values().any(|id| id == &m.device_id)
+        });
+        for m in monitors.iter_mut() {
+            if let Some(id) = ids.get(&m.device_id) {
+                let local = connected.iter().find(|c| &c.device_id == id);
+                m.device_id = id.clone();
+                m.fingerprint = local.and_then(|c| c.fingerprint.clone());
+            }
+        }
+    };
+    relabel(&mut config.monitors);
+    for p in config.profiles.iter_mut() {
+        relabel(&mut p.monitors);
+    }
+    let rename = |members: &mut Vec<String>| {
+        for id in members.iter_mut() {
+            if let Some(local) = ids.get(id) {
+                *id = local.clone();
+            }
+        }
+    };
+    config
+        .groups
+        .iter_mut()
+        .for_each(|g| rename(&mut g.members));
+    for p in config.profiles.iter_mut() {
+        p.groups.iter_mut().for_each(|g| rename(&mut g.members));
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::fake::fake_monitor;
+    use crate::core::group::GroupMode;
+    use config::{GroupConfig, ProfileConfig, CONFIG_VERSION};
+
+    fn text(version: u32, config_version: u32) -> String {
+        let mut bundle = export(&OsicConfig::default());
+        bundle.version = version;
+        bundle.config.version = config_version;
+        serde_json::to_string(&bundle).unwrap()
+    }
+
+    #[test]
+    fn parse_checks_both_versions() {
+        assert!(parse(&text(BUNDLE_VERSION, CONFIG_VERSION)).is_ok());
+        assert_eq!(
+            parse(&text(BUNDLE_VERSION + 1, CONFIG_VERSION)).unwrap_err(),
+            format!(
+                "Bundle version {} was written by a newer Osic",
+                BUNDLE_VERSION + 1
+            )
+        );
+        assert_eq!(
+            parse(&text(BUNDLE_VERSION, CONFIG_VERSION + 1)).unwrap_err(),
+            format!(
+                "Config version {} was written by a newer Osic",
+                CONFIG_VERSION + 1
+            )
+        );
+        assert!(parse("{\"version\": 1}").is_err());
+    }
+
+    fn fingerprint(serial: u32) -> MonitorFingerprint {
+        MonitorFingerprint {
+            manufacturer: "DEL".to_string(),
+            product_code: 0xa0b1,
+            serial,
+            model: "U2720Q".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn monitor(device_id: &str, serial: Option<u32>, album: &str) -> MonitorConfig {
+        let mut m: MonitorConfig =
+            serde_json::from_value(serde_json::json!({ "deviceId": device_id })).unwrap();
+        m.album_path = Some(PathBuf::from(album));
+        m.fingerprint = serial.map(fingerprint);
+        m
+    }
+
+    fn group(members: &[&str], album: &str) -> GroupConfig {
+        GroupConfig {
+            name: "desk".to_string(),
+            members: members.iter().map(|m| m.to_string()).collect(),
+            mode: GroupMode::Synchronized,
+            album_path: PathBuf::from(album),
+            slide_interval: Default::default(),
+            slide_time: 0,
+            cursor: 0,
+        }
+    }
+
+    fn connected(device_id: &str, serial: u32) -> Monitor {
+        Monitor {
+            fingerprint: Some(fingerprint(serial)),
+            ..fake_monitor(device_id, device_id, 0, 0, 3840, 2160)
+        }
+    }
+
+    // Ann's machine: two monitors of the same model and a projector without EDID.
+    fn exported() -> OsicConfig {
+        let mut dp2 = monitor("DP-2", Some(22), "/home/ann/Pictures");
+        dp2.image = Some(PathBuf::from("/home/ann/Pictures/City/a.jpg"));
+        dp2.recent_images = vec![
+            PathBuf::from("/home/ann/Pictures/City/a.jpg"),
+            PathBuf::from("/tmp/elsewhere.png"),
+        ];
+        let monitors = vec![
+            monitor("DP-1", Some(11), "/home/ann/Pictures/Beach"),
+            dp2,
+            monitor("HDMI-1", None, "/mnt/share/Art"),
+        ];
+        OsicConfig {
+            groups: vec![group(&["DP-1", "DP-2"], "/home/ann/Pictures/Beach")],
+            profiles: vec![ProfileConfig {
+                name: "work".to_string(),
+                rule: None,
+                monitors: monitors[..2].to_vec(),
+                groups: vec![group(&["DP-2", "DP-1"], "/mnt/share/Art/Old")],
+            }],
+            monitors,
+            ..Default::default()
+        }
+    }
+
+    fn ids(monitors: &[MonitorConfig]) -> Vec<&str> {
+        monitors.iter().map(|m| m.device_id.as_str()).collect()
+    }
+
+    fn path(p: &Option<PathBuf>) -> &str {
+        p.as_ref().unwrap().to_str().unwrap()
+    }
+
+    #[test]
+    fn portable_paths() {
+        let roots = vec![
+            PathBuf::from("/home/ann/Pictures"),
+            PathBuf::from("/mnt/art"),
+        ];
+        let portable = |p: &str| to_portable(Path::new(p), &roots);
+        assert_eq!(
+            portable("/home/ann/Pictures/City/a b.jpg"),
+            Some(PathBuf::from("@album0/City/a b.jpg"))
+        );
+        assert_eq!(portable("/mnt/art"), Some(PathBuf::from("@album1")));
+        assert_eq!(portable("/home/ann/Picturesque/a.jpg"), None);
+        assert_eq!(portable("/tmp/a.jpg"), None);
+
+        let local = vec![PathBuf::from("/data/photos"), PathBuf::from("/srv/art")];
+        let resolve = |p: &str| from_portable(Path::new(p), &local);
+        assert_eq!(
+            resolve("@album0/City/a b.jpg"),
+            Some(PathBuf::from("/data/photos/City/a b.jpg"))
+        );
+        assert_eq!(resolve("@album1"), Some(PathBuf::from("/srv/art")));
+        assert_eq!(resolve("@album2/a.jpg"), None);
+        assert_eq!(resolve("@albumx/a.jpg"), None);
+        assert_eq!(resolve("/tmp/a.jpg"), None);
+    }
+
+    #[test]
+    fn export_keeps_paths_below_album_roots() {
+        let bundle = export(&exported());
+        assert_eq!(
+            bundle.album_roots,
+            vec![
+                PathBuf::from("/home/ann/Pictures"),
+                PathBuf::from("/mnt/share/Art")
+            ]
+        );
+        let m = &bundle.config.monitors;
+        assert_eq!(path(&m[0].album_path), "@album0/Beach");
+        assert_eq!(path(&m[1].album_path), "@album0");
+        assert_eq!(path(&m[1].image), "@album0/City/a.jpg");
+        assert_eq!(
+            m[1].recent_images,
+            vec![
+                PathBuf::from("@album0/City/a.jpg"),
+                PathBuf::from("/tmp/elsewhere.png")
+            ]
+        );
+        assert_eq!(path(&m[2].album_path), "@album1");
+        assert_eq!(
+            bundle.config.groups[0].album_path,
+            PathBuf::from("@album0/Beach")
+        );
+        let work = &bundle.config.profiles[0];
+        assert_eq!(path(&work.monitors[0].album_path), "@album0/Beach");
+        assert_eq!(work.groups[0].album_path, PathBuf::from("@album1/Old"));
+    }
+
+    #[test]
+    fn import_on_another_machine() {
+        let text = serde_json::to_string(&export(&exported())).unwrap();
+        let bundle = parse(&text).unwrap();
+
+        // the second monitor is the same unit under another name, the first one
+        // only the same model
+        let here = vec![
+            connected("DisplayPort-0", 22),
+            connected("DisplayPort-1", 33),
+        ];
+        let info = info(&bundle, &here);
+        let suggested = info
+            .monitors
+            .iter()
+            .map(|m| (m.device_id.as_str(), m.suggested.as_deref()))
+            .collect::<Vec<(&str, Option<&str>)>>();
+        assert_eq!(
+            suggested,
+            vec![
+                ("DP-1", None),
+                ("DP-2", Some("DisplayPort-0")),
+                ("HDMI-1", None)
+            ]
+        );
+        assert_eq!(info.profiles, vec!["work"]);
+        assert!(info.album_roots.iter().all(|r| !r.exists));
+
+        let mapping = HashMap::from([("DP-1".to_string(), "DisplayPort-1".to_string())]);
+        let roots = vec![PathBuf::from("/data/photos"), PathBuf::new()];
+        let config = import(&bundle, &here, &mapping, &roots).unwrap();
+
+        // mapped and suggested monitors take the local identity, the projector
+        // waits under its old name
+        let m = &config.monitors;
+        assert_eq!(ids(m), vec!["DisplayPort-1", "DisplayPort-0", "HDMI-1"]);
+        assert_eq!(m[0].fingerprint, Some(fingerprint(33)));
+        assert_eq!(m[1].fingerprint, Some(fingerprint(22)));
+        assert_eq!(m[2].fingerprint, None);
+
+        // the first root moved, the second one stays where it was
+        assert_eq!(path(&m[0].album_path), "/data/photos/Beach");
+        assert_eq!(path(&m[1].image), "/data/photos/City/a.jpg");
+        assert_eq!(
+            m[1].recent_images,
+            vec![
+                PathBuf::from("/data/photos/City/a.jpg"),
+                PathBuf::from("/tmp/elsewhere.png")
+            ]
+        );
+        assert_eq!(path(&m[2].album_path), "/mnt/share/Art");
+
+        assert_eq!(
+            config.groups[0].members,
+            vec!["DisplayPort-1", "DisplayPort-0"]
+        );
+        assert_eq!(
+            config.groups[0].album_path,
+            PathBuf::from("/data/photos/Beach")
+        );
+        let work = &config.profiles[0];
+        assert_eq!(ids(&work.monitors), vec!["DisplayPort-1", "DisplayPort-0"]);
+        assert_eq!(work.monitors[0].fingerprint, Some(fingerprint(33)));
+        assert_eq!(
+            work.groups[0].members,
+            vec!["DisplayPort-0", "DisplayPort-1"]
+        );
+        assert_eq!(
+            work.groups[0].album_path,
+            PathBuf::from("/mnt/share/Art/Old")
+        );
+    }
+
+    #[test]
+    fn import_rejects_bad_mappings() {
+        let bundle = export(&exported());
+        let here = vec![
+            connected("DisplayPort-0", 22),
+            connected("DisplayPort-1", 33),
+        ];
+        let twice = HashMap::from([
+            ("DP-1".to_string(), "DisplayPort-0".to_string()),
+            ("DP-2".to_string(), "DisplayPort-0".to_string()),
+        ]);
+        assert_eq!(
+            import(&bundle, &here, &twice, &[]).unwrap_err(),
+            "Monitor DisplayPort-0 is mapped twice"
+        );
+        let unknown = HashMap::from([("DP-1".to_string(), "DVI-9".to_string())]);
+        assert_eq!(
+            import(&bundle, &here, &unknown, &[]).unwrap_err(),
+            "Unknown monitor: DVI-9"
+        );
+    }
+
+    #[test]
+    fn mapping_wins_over_the_suggestion() {
+        let bundle = export(&exported());
+        let here = vec![connected("DisplayPort-0", 22), connected("HDMI-1", 44)];
+
+        // the projector takes the monitor DP-2 would have gone to, so DP-2 waits
+        // under its own id
+        let mapping = HashMap::from([
+            ("HDMI-1".to_string(), "DisplayPort-0".to_string()),
+            ("DP-1".to_string(), "HDMI-1".to_string()),
+        ]);
+        let config = import(&bundle, &here, &mapping, &[]).unwrap();
+        assert_eq!(
+            ids(&config.monitors),
+            vec!["HDMI-1", "DP-2", "DisplayPort-0"]
+        );
+        assert_eq!(path(&config.monitors[2].album_path), "/mnt/share/Art");
+        assert_eq!(config.groups[0].members, vec!["HDMI-1", "DP-2"]);
+
+        // DP-1 takes the projector's name, the unmapped projector goes
+        let mapping = HashMap::from([("DP-1".to_string(), "HDMI-1".to_string())]);
+        let config = import(&bundle, &here, &mapping, &[]).unwrap();
+        assert_eq!(ids(&config.monitors), vec!["HDMI-1", "DisplayPort-0"]);
+        assert_eq!(
+            path(&config.monitors[0].album_path),
+            "/home/ann/Pictures/Beach"
+        );
+    }
+}
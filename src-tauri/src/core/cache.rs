@@ -1,7 +1,14 @@
-use crate::core::display::{Fits, Interval, Modes, MonitorWrapper};
+use crate::core::display::{Fits, Modes, Monitor, MonitorWrapper};
+use crate::core::edid::{self, MonitorFingerprint};
+use crate::core::group::MonitorGroup;
+use crate::core::profile::OsicProfile;
+use crate::core::schedule::SlideSchedule;
 use crate::core::selector::OsicSlideSelector;
+use crate::core::solar::{Location, TimeAlbum};
+use crate::core::span::Bezel;
+use crate::core::store;
 use crate::core::utils;
-use image::{DynamicImage, ImageBuffer, ImageError};
+use image::{DynamicImage, ImageBuffer, ImageError, RgbaImage};
 use serde::{Deserialize, Serialize};
 use std::{
     env,
@@ -10,11 +17,44 @@ use std::{
     path::PathBuf,
 };
 
-fn os_temp_folder() -> PathBuf {
+#[cfg(not(test))]
+pub fn os_temp_folder() -> PathBuf {
     let dir = env::temp_dir();
     return dir.join("Osic");
 }
 
+// Tests keep away from the user's settings, in folders of their own run.
+#[cfg(test)]
+pub fn os_temp_folder() -> PathBuf {
+    env::temp_dir().join(format!("osic-test-run-{}", std::process::id()))
+}
+
+#[cfg(test)]
+pub fn os_config_folder() -> PathBuf {
+    os_temp_folder().join("config")
+}
+
+// Per-user config directory: %APPDATA%\Osic, ~/Library/Application Support/Osic,
+// $XDG_CONFIG_HOME/osic or ~/.config/osic.
+#[cfg(not(test))]
+pub fn os_config_folder() -> PathBuf {
+    if cfg!(windows) {
+        if let Some(appdata) = env::var_os("APPDATA") {
+            return PathBuf::from(appdata).join("Osic");
+        }
+    }
+    let home = env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir);
+    if cfg!(target_os = "macos") {
+        return home.join("Library/Application Support/Osic");
+    }
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(p) if !p.is_empty() => PathBuf::from(p).join("osic"),
+        _ => home.join(".config").join("osic"),
+    }
+}
+
 fn app_tmp_image_path() -> PathBuf {
     let mut p = os_temp_folder();
     p.push("img");
@@ -22,6 +62,41 @@ fn app_tmp_image_path() -> PathBuf {
     return p;
 }
 
+fn app_render_path() -> PathBuf {
+    let mut p = os_temp_folder();
+    p.push("render");
+    let _ = fs::create_dir_all(&p);
+    return p;
+}
+
+// Rendered wallpapers are named "<prefix>-<millis>.jpg", older renders with the same
+// prefix are removed so desktops that cache by uri always see a new file.
+pub fn write_rendered_image(prefix: &str, content: &RgbaImage) -> Result<PathBuf, String> {
+    let dir = app_render_path();
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(&format!("{}-", prefix))
+            {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    let img_path = dir.join(format!(
+        "{}-{}.jpg",
+        prefix,
+        utils::get_sys_time_in_millis()
+    ));
+    DynamicImage::ImageRgba8(content.clone())
+        .to_rgb8()
+        .save_with_format(&img_path, image::ImageFormat::Jpeg)
+        .map_err(|e| format!("Write rendered image Error: {}", e))?;
+    Ok(img_path)
+}
+
 pub fn write_image_cache(
     image_path: &PathBuf,
     content: &ImageBuffer<image::Rgba<u8>, Vec<u8>>,
@@ -77,17 +152,26 @@ pub struct OsicMonitorSettings {
     pub image: Option<PathBuf>,
     pub album_path: Option<PathBuf>,
     pub recent_images: Vec<PathBuf>,
-    pub slide_interval: Interval,
+    pub slide_interval: SlideSchedule,
     pub slide_time: u64,
     pub selector: OsicSlideSelector,
+    pub fingerprint: Option<MonitorFingerprint>,
+    pub nickname: Option<String>,
+    pub bezel: Bezel,
+    pub time_albums: Vec<TimeAlbum>,
 }
 
 impl From<MonitorWrapper> for OsicMonitorSettings {
     fn from(item: MonitorWrapper) -> Self {
+        let fingerprint = item.meta().fingerprint.clone();
         let recent_images = item.image_history.into_iter().map(|i| i.path).collect();
 
         OsicMonitorSettings {
             device_id: item.device_id,
+            fingerprint,
+            nickname: item.nickname,
+            bezel: item.bezel,
+            time_albums: item.time_albums,
             mode: item.mode,
             album_path: item.album_path,
             fit: item.fit,
@@ -100,34 +184,50 @@ impl From<MonitorWrapper> for OsicMonitorSettings {
     }
 }
 
-pub fn write_monitor_settings(s: OsicMonitorSettings) -> Result<(), io::Error> {
-    let encode_struct = bincode::serialize(&s).unwrap();
+// Settings that belong to the app rather than to one monitor.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OsicAppSettings {
+    pub location: Option<Location>,
+    pub groups: Vec<MonitorGroup>,
+    pub active_profile: Option<String>,
+}
 
-    let file_name = utils::string_hash(s.device_id).unwrap();
-    let mut file_path = os_temp_folder();
-    file_path.push(file_name);
+pub fn write_app_settings(s: &OsicAppSettings) -> Result<(), io::Error> {
+    store::update(|c| c.set_app_settings(s))
+}
 
-    if file_path.exists() {
-        fs::remove_file(&file_path)?;
-    };
+pub fn load_app_settings() -> OsicAppSettings {
+    store::load().app_settings()
+}
+
+pub fn write_profiles(profiles: &[OsicProfile]) -> Result<(), io::Error> {
+    store::update(|c| c.set_profiles(profiles))
+}
+
+pub fn load_profiles() -> Vec<OsicProfile> {
+    store::load().profiles()
+}
 
-    fs::write(file_path, encode_struct)
+pub fn write_monitor_settings(s: OsicMonitorSettings) -> Result<(), io::Error> {
+    store::update(|c| c.set_monitor_settings(s))
 }
 
 pub fn load_monitor_settings(device_id: String) -> Result<OsicMonitorSettings, ()> {
-    let file_name = utils::string_hash(device_id).unwrap();
-    let mut file_path = os_temp_folder();
-    file_path.push(file_name);
+    load_all_monitor_settings()
+        .into_iter()
+        .find(|s| s.device_id == device_id)
+        .ok_or(())
+}
 
-    if !file_path.exists() {
-        return Err(());
-    };
+pub fn remove_monitor_settings(device_id: String) -> Result<(), io::Error> {
+    store::update(|c| c.monitors.retain(|m| m.device_id != device_id))
+}
 
-    match fs::read(file_path) {
-        Ok(s) => {
-            let decoded_struct = bincode::deserialize(&s).unwrap();
-            return Ok(decoded_struct);
-        }
-        Err(_) => Err(()),
-    }
+pub fn load_all_monitor_settings() -> Vec<OsicMonitorSettings> {
+    store::load().monitor_settings()
+}
+
+pub fn find_monitor_settings(monitor: &Monitor) -> Option<OsicMonitorSettings> {
+    let saved = load_all_monitor_settings();
+    edid::match_settings(monitor, &saved).cloned()
 }
@@ -0,0 +1,550 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use super::backend::WallpaperBackend;
+use super::cache;
+use super::display::{Fits, Monitor};
+use super::edid;
+use super::utils;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StaticMonitor {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub x: i32,
+    #[serde(default)]
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+// commands.json, e.g.
+// { "set": "xwallpaper --output {monitor} {fit} {path}",
+//   "fitArgs": { "Fill": "--zoom", "Fit": "--maximize" },
+//   "enumerate": "xrandr --listmonitors" }
+//
+// Templates are split into arguments like a shell would (quotes and backslashes work)
+// and placeholders are filled in afterwards, so a path with spaces or quotes is always
+// one argument and never reaches a shell. Placeholders: {monitor} {name} {x} {y}
+// {width} {height} {geometry} {fit} {path} {color}.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandConfig {
+    pub set: String,
+    pub fit: Option<String>,
+    pub color: Option<String>,
+    #[serde(default)]
+    pub fit_args: HashMap<String, String>,
+    // every output line holding a WxH+X+Y geometry is a monitor
+    pub enumerate: Option<String>,
+    #[serde(default)]
+    pub monitors: Vec<StaticMonitor>,
+    pub timeout: Option<u64>,
+}
+
+pub fn default_config_path() -> PathBuf {
+    match env::var_os("OSIC_COMMAND_CONFIG") {
+        Some(p) => PathBuf::from(p),
+        None => cache::os_config_folder().join("commands.json"),
+    }
+}
+
+pub struct CommandBackend {
+    config: Result<CommandConfig, String>,
+    fit: Fits,
+    wallpapers: HashMap<String, String>,
+    // the last enumeration, setting a wallpaper doesn't run it again
+    monitors: RefCell<Vec<Monitor>>,
+}
+
+impl CommandBackend {
+    pub fn new(config: CommandConfig) -> Self {
+        Self {
+            config: Ok(config),
+            fit: Fits::Fill,
+            wallpapers: HashMap::new(),
+            monitors: RefCell::new(Vec::new()),
+        }
+    }
+
+    // A broken config doesn't stop Osic, every call reports it instead.
+    pub fn load() -> Self {
+        let path = default_config_path();
+        let config = fs::read_to_string(&path)
+            .map_err(|e| format!("Read {} Error: {}", path.display(), e))
+            .and_then(|s| {
+                serde_json::from_str::<CommandConfig>(&s)
+                    .map_err(|e| format!("Parse {} Error: {}", path.display(), e))
+            });
+        Self {
+            config,
+            fit: Fits::Fill,
+            wallpapers: HashMap::new(),
+            monitors: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn config(&self) -> Result<&CommandConfig, String> {
+        self.config.as_ref().map_err(|e| e.clone())
+    }
+
+    fn timeout(&self) -> Duration {
+        let secs = self
+            .config
+            .as_ref()
+            .ok()
+            .and_then(|c| c.timeout)
+            .unwrap_or(10);
+        Duration::from_secs(secs)
+    }
+
+    fn placeholders(&self, monitor: Option<&Monitor>) -> HashMap<&'static str, String> {
+        let mut values = HashMap::new();
+        let fit = format!("{:?}", self.fit);
+        let fit_arg = self
+            .config
+            .as_ref()
+            .ok()
+            .and_then(|c| c.fit_args.get(&fit).cloned())
+            .unwrap_or_else(|| fit.to_lowercase());
+        values.insert("fit", fit_arg);
+        if let Some(m) = monitor {
+            values.insert("monitor", m.device_id.clone());
+            values.insert("name", m.name.clone());
+            values.insert("x", m.letf.to_string());
+            values.insert("y", m.top.to_string());
+            values.insert("width", m.width.to_string());
+            values.insert("height", m.height.to_string());
+            values.insert(
+                "geometry",
+                format!("{}x{}{:+}{:+}", m.width, m.height, m.letf, m.top),
+            );
+        }
+        values
+    }
+
+    fn known_monitors(&self) -> Result<Vec<Monitor>, String> {
+        let known = self.monitors.borrow().clone();
+        if known.is_empty() {
+            return self.get_monitor_device_path();
+        }
+        Ok(known)
+    }
+
+    // Enumerates again only for a monitor the last enumeration didn't have.
+    fn find_monitor(&self, monitor_id: &str) -> Result<Monitor, String> {
+        let known = self
+            .known_monitors()?
+            .into_iter()
+            .find(|m| m.device_id == monitor_id);
+        match known {
+            Some(m) => Ok(m),
+            None => self
+                .get_monitor_device_path()?
+                .into_iter()
+                .find(|m| m.device_id == monitor_id)
+                .ok_or_else(|| format!("Unknown monitor: {}", monitor_id)),
+        }
+    }
+
+    fn set_one(&self, monitor: &Monitor, wallpaper: &str) -> Result<(), String> {
+        let mut values = self.placeholders(Some(monitor));
+        values.insert("path", wallpaper.to_string());
+        run(&self.config()?.set, &values, self.timeout()).map(|_| ())
+    }
+}
+
+// Split a template into arguments: whitespace separates, '...' is literal,
+// "..." and bare words honour backslash escapes.
+pub fn split_args(template: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(format!("Unclosed ' in: {}", template)),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => current.push(c),
+                            None => return Err(format!("Trailing \\ in: {}", template)),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(format!("Unclosed \" in: {}", template)),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => current.push(c),
+                    None => return Err(format!("Trailing \\ in: {}", template)),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_word {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+pub fn fill_placeholders(
+    template: &str,
+    values: &HashMap<&'static str, String>,
+) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for arg in split_args(template)? {
+        let mut filled = String::new();
+        let mut rest = arg.as_str();
+        while let Some(start) = rest.find('{') {
+            filled.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed {{ in: {}", template))?;
+            let key = &rest[start + 1..start + end];
+            let value = values
+                .get(key)
+                .ok_or_else(|| format!("Placeholder {{{}}} not available in: {}", key, template))?;
+            filled.push_str(value);
+            rest = &rest[start + end + 1..];
+        }
+        filled.push_str(rest);
+        // a placeholder that expands to nothing (an empty fit argument) drops out
+        if !filled.is_empty() || arg.is_empty() {
+            args.push(filled);
+        }
+    }
+    if args.is_empty() {
+        return Err("Empty command".to_string());
+    }
+    Ok(args)
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut s = String::new();
+        if let Some(mut p) = pipe {
+            let _ = p.read_to_string(&mut s);
+        }
+        s
+    })
+}
+
+// Run a template, killing it once `timeout` has passed. Returns its stdout.
+pub fn run(
+    template: &str,
+    values: &HashMap<&'static str, String>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let args = fill_placeholders(template, values)?;
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Run {} Error: {}", args[0], e))?;
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "{} timed out after {}s",
+                    args.join(" "),
+                    timeout.as_secs()
+                ));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(20)),
+            Err(e) => return Err(format!("Wait {} Error: {}", args[0], e)),
+        }
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Err(format!(
+            "{} exited with {}: {}",
+            args.join(" "),
+            status
+                .code()
+                .map_or("signal".to_string(), |c| c.to_string()),
+            stderr.trim()
+        ));
+    }
+    Ok(stdout)
+}
+
+// Any line with a WxH+X+Y (xrandr style WIDTH/mmxHEIGHT/mm+X+Y works too) is a monitor,
+// named by the last other word on the line.
+pub fn parse_monitor_lines(output: &str) -> Vec<Monitor> {
+    let mut monitors = Vec::new();
+    for line in output.lines() {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let geometry = words.iter().find_map(|w| utils::parse_geometry(w));
+        let (width_mm, height_mm) = words
+            .iter()
+            .find_map(|w| utils::parse_geometry_mm(w))
+            .unwrap_or_default();
+        let name = words
+            .iter()
+            .rev()
+            .find(|w| utils::parse_geometry(w).is_none() && !w.ends_with(':'))
+            .map(|w| w.trim_start_matches(['+', '*']));
+        if let (Some((width, height, x, y)), Some(name)) = (geometry, name) {
+            monitors.push(Monitor {
+                width_mm,
+                height_mm,
+                name: name.to_string(),
+                device_id: name.to_string(),
+                letf: x,
+                top: y,
+                right: x + width,
+                bottom: y + height,
+                width,
+                height,
+                ..Default::default()
+            });
+        }
+    }
+    monitors
+}
+
+fn static_monitors(monitors: &[StaticMonitor]) -> Vec<Monitor> {
+    monitors
+        .iter()
+        .map(|m| Monitor {
+            name: if m.name.is_empty() {
+                m.id.clone()
+            } else {
+                m.name.clone()
+            },
+            device_id: m.id.clone(),
+            letf: m.x,
+            top: m.y,
+            right: m.x + m.width,
+            bottom: m.y + m.height,
+            width: m.width,
+            height: m.height,
+            ..Default::default()
+        })
+        .collect()
+}
+
+impl WallpaperBackend for CommandBackend {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+        let config = self.config()?;
+        let monitors = match &config.enumerate {
+            Some(enumerate) => {
+                let output = run(enumerate, &HashMap::new(), self.timeout())?;
+                let mut monitors = parse_monitor_lines(&output);
+                for m in &mut monitors {
+                    m.fingerprint = edid::fingerprint_sysfs(&m.device_id);
+                }
+                monitors
+            }
+            None => static_monitors(&config.monitors),
+        };
+        *self.monitors.borrow_mut() = monitors.clone();
+        Ok(monitors)
+    }
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
+        let monitor = self.find_monitor(monitor_id)?;
+        self.set_one(&monitor, wallpaper)?;
+        self.wallpapers
+            .insert(monitor_id.to_string(), wallpaper.to_string());
+        Ok(())
+    }
+
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String> {
+        Ok(self.wallpapers.get(monitor_id).cloned().unwrap_or_default())
+    }
+
+    // Without a dedicated fit command the wallpapers are set again, {fit} is in `set`.
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String> {
+        self.fit = fit;
+        if let Some(template) = &self.config()?.fit {
+            return run(template, &self.placeholders(None), self.timeout()).map(|_| ());
+        }
+        for monitor in self.known_monitors()? {
+            if let Some(wallpaper) = self.wallpapers.get(&monitor.device_id) {
+                self.set_one(&monitor, wallpaper)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_background_color(&mut self, color: u32) -> Result<(), String> {
+        let template = self
+            .config()?
+            .color
+            .clone()
+            .ok_or_else(|| "No color command configured".to_string())?;
+        let mut values = self.placeholders(None);
+        values.insert("color", format!("#{:06x}", color & 0xffffff));
+        run(&template, &values, self.timeout()).map(|_| ())
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        Err("The command backend can't read the background color".to_string())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::core::testutil::Stubs;
+
+    fn values(list: &[(&'static str, &str)]) -> HashMap<&'static str, String> {
+        list.iter().map(|(k, v)| (*k, v.to_string())).collect()
+    }
+
+    #[test]
+    fn split_like_a_shell() {
+        assert_eq!(
+            split_args(r#"set  'a b' "c \"d\" \\e" f\ g '' h"i"j"#).unwrap(),
+            vec!["set", "a b", "c \"d\" \\e", "f g", "", "hij"]
+        );
+        // single quotes take backslashes literally
+        assert_eq!(split_args(r"'a\b'").unwrap(), vec![r"a\b"]);
+        assert!(split_args("").unwrap().is_empty());
+        assert!(split_args("set 'a").is_err());
+        assert!(split_args("set \"a").is_err());
+        assert!(split_args("set a\\").is_err());
+    }
+
+    #[test]
+    fn placeholders_stay_one_argument() {
+        let v = values(&[
+            ("path", "/pics/it's a \"b\" c.png"),
+            ("monitor", "DP-1"),
+            ("fit", ""),
+        ]);
+        assert_eq!(
+            fill_placeholders("set --output={monitor} {fit} {path} ''", &v).unwrap(),
+            vec!["set", "--output=DP-1", "/pics/it's a \"b\" c.png", ""]
+        );
+        // quotes in the template around a placeholder change nothing
+        assert_eq!(
+            fill_placeholders("set \"{path}\"", &v).unwrap(),
+            vec!["set", "/pics/it's a \"b\" c.png"]
+        );
+        assert!(fill_placeholders("set {color}", &v).is_err());
+        assert!(fill_placeholders("set {path", &v).is_err());
+        assert!(fill_placeholders("{fit}", &v).is_err());
+    }
+
+    #[test]
+    fn failing_command_reports_its_exit() {
+        let none = HashMap::new();
+        let err = run("false", &none, Duration::from_secs(5)).unwrap_err();
+        assert_eq!(err, "false exited with 1: ");
+        let err = run(
+            "sh -c 'echo oops >&2; exit 3'",
+            &none,
+            Duration::from_secs(5),
+        )
+        .unwrap_err();
+        assert!(err.ends_with("exited with 3: oops"), "{}", err);
+        assert!(run("osic-no-such-tool", &none, Duration::from_secs(5)).is_err());
+        assert_eq!(
+            run("echo hello", &none, Duration::from_secs(5)),
+            Ok("hello\n".to_string())
+        );
+    }
+
+    #[test]
+    fn slow_command_is_killed() {
+        let started = Instant::now();
+        let err = run("sleep 5", &HashMap::new(), Duration::from_millis(200)).unwrap_err();
+        assert!(err.starts_with("sleep 5 timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn wallpapers_use_the_enumerated_monitors() {
+        let stubs = Stubs::new("command");
+        stubs.tool(
+            "list-outputs",
+            "echo list >> \"$LOG\"\n\
+             echo 'Monitors: 2'\n\
+             echo ' 0: +*DP-1 2560/597x1440/336+0+0  DP-1'\n\
+             echo ' 1: +HDMI-1 1920/527x1080/296+2560+0  HDMI-1'",
+        );
+        stubs.tool("set-wallpaper", "echo \"set $*\" >> \"$LOG\"");
+        let mut backend = CommandBackend::new(CommandConfig {
+            set: "set-wallpaper {monitor} {geometry} {path}".to_string(),
+            enumerate: Some("list-outputs".to_string()),
+            ..Default::default()
+        });
+
+        let monitors = backend.get_monitor_device_path().unwrap();
+        assert_eq!(monitors.len(), 2);
+        assert_eq!((monitors[0].width_mm, monitors[1].letf), (597, 2560));
+        backend.set_wallpaper("DP-1", "/pics/a.png").unwrap();
+        backend.set_wallpaper("HDMI-1", "/pics/b.png").unwrap();
+        backend.set_fit(Fits::Fit).unwrap();
+        assert_eq!(
+            stubs.log(),
+            "list\n\
+             set DP-1 2560x1440+0+0 /pics/a.png\n\
+             set HDMI-1 1920x1080+2560+0 /pics/b.png\n\
+             set DP-1 2560x1440+0+0 /pics/a.png\n\
+             set HDMI-1 1920x1080+2560+0 /pics/b.png\n"
+        );
+
+        // a monitor the last list didn't have is looked for once more
+        assert_eq!(
+            backend.set_wallpaper("DP-2", "/pics/c.png"),
+            Err("Unknown monitor: DP-2".to_string())
+        );
+        assert!(stubs.log().ends_with("/pics/b.png\nlist\n"));
+    }
+}
@@ -0,0 +1,331 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use super::cache::{self, OsicAppSettings, OsicMonitorSettings};
+use super::display::{Fits, Modes};
+use super::edid::MonitorFingerprint;
+use super::group::{GroupMode, MonitorGroup};
+use super::profile::{OsicProfile, ProfileRule};
+use super::schedule::SlideSchedule;
+use super::selector::OsicSlideSelector;
+use super::solar::{Location, TimeAlbum};
+use super::span::Bezel;
+
+pub const CONFIG_VERSION: u32 = 1;
+
+// All settings in one file meant to be read and edited by hand. Album listings
+// aren't kept, they are read from the album folders again.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OsicConfig {
+    pub version: u32,
+    #[serde(default)]
+    pub location: Option<Location>,
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    #[serde(default)]
+    pub monitors: Vec<MonitorConfig>,
+    #[serde(default)]
+    pub groups: Vec<GroupConfig>,
+    #[serde(default)]
+    pub profiles: Vec<ProfileConfig>,
+    // pictures by `picture_id`, ratings go from 1 to 5
+    #[serde(default)]
+    pub favorites: BTreeSet<String>,
+    #[serde(default)]
+    pub ratings: BTreeMap<String, u8>,
+    #[serde(default)]
+    pub blacklist: BTreeSet<String>,
+}
+
+impl Default for OsicConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            location: None,
+            active_profile: None,
+            monitors: Vec::new(),
+            groups: Vec::new(),
+            profiles: Vec::new(),
+            favorites: BTreeSet::new(),
+            ratings: BTreeMap::new(),
+            blacklist: BTreeSet::new(),
+        }
+    }
+}
+
+fn default_mode() -> Modes {
+    Modes::Picture
+}
+
+fn default_fit() -> Fits {
+    Fits::Fill
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorConfig {
+    pub device_id: String,
+    #[serde(default)]
+    pub nickname: Option<String>,
+    #[serde(default = "default_mode")]
+    pub mode: Modes,
+    #[serde(default = "default_fit")]
+    pub fit: Fits,
+    #[serde(default)]
+    pub image: Option<PathBuf>,
+    #[serde(default)]
+    pub album_path: Option<PathBuf>,
+    #[serde(default)]
+    pub slide_interval: SlideSchedule,
+    #[serde(default)]
+    pub slide_time: u64,
+    #[serde(default)]
+    pub shuffle: bool,
+    #[serde(default)]
+    pub ratio: bool,
+    #[serde(default)]
+    pub ratio_range: f32,
+    #[serde(default)]
+    pub bezel: Bezel,
+    #[serde(default)]
+    pub time_albums: Vec<TimeAlbum>,
+    #[serde(default)]
+    pub recent_images: Vec<PathBuf>,
+    #[serde(default)]
+    pub fingerprint: Option<MonitorFingerprint>,
+}
+
+impl From<OsicMonitorSettings> for MonitorConfig {
+    fn from(s: OsicMonitorSettings) -> Self {
+        Self {
+            device_id: s.device_id,
+            nickname: s.nickname,
+            mode: s.mode,
+            fit: s.fit,
+            image: s.image,
+            album_path: s.album_path,
+            slide_interval: s.slide_interval,
+            slide_time: s.slide_time,
+            shuffle: s.selector.shuffle,
+            ratio: s.selector.ratio,
+            ratio_range: s.selector.ratio_range,
+            bezel: s.bezel,
+            time_albums: s.time_albums,
+            recent_images: s.recent_images,
+            fingerprint: s.fingerprint,
+        }
+    }
+}
+
+impl From<MonitorConfig> for OsicMonitorSettings {
+    fn from(c: MonitorConfig) -> Self {
+        // the pictures are listed when the settings are applied to a monitor
+        let mut selector = OsicSlideSelector::default();
+        selector.path = c.album_path.clone().unwrap_or_default();
+        selector.shuffle = c.shuffle;
+        selector.ratio = c.ratio;
+        selector.ratio_range = c.ratio_range;
+        Self {
+            device_id: c.device_id,
+            mode: c.mode,
+            fit: c.fit,
+            image: c.image,
+            album_path: c.album_path,
+            recent_images: c.recent_images,
+            slide_interval: c.slide_interval,
+            slide_time: c.slide_time,
+            selector,
+            fingerprint: c.fingerprint,
+            nickname: c.nickname,
+            bezel: c.bezel,
+            time_albums: c.time_albums,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupConfig {
+    pub name: String,
+    pub members: Vec<String>,
+    pub mode: GroupMode,
+    pub album_path: PathBuf,
+    #[serde(default)]
+    pub slide_interval: SlideSchedule,
+    #[serde(default)]
+    pub slide_time: u64,
+    #[serde(default)]
+    pub cursor: usize,
+}
+
+impl From<MonitorGroup> for GroupConfig {
+    fn from(g: MonitorGroup) -> Self {
+        Self {
+            name: g.name,
+            members: g.members,
+            mode: g.mode,
+            album_path: g.album_path,
+            slide_interval: g.slide_interval,
+            slide_time: g.slide_time,
+            cursor: g.cursor,
+        }
+    }
+}
+
+impl From<GroupConfig> for MonitorGroup {
+    fn from(c: GroupConfig) -> Self {
+        // listed like a monitor's album, once the group is in use
+        let mut selector = OsicSlideSelector::default();
+        selector.path = c.album_path.clone();
+        Self {
+            name: c.name,
+            members: c.members,
+            mode: c.mode,
+            album_path: c.album_path,
+            slide_interval: c.slide_interval,
+            slide_time: c.slide_time,
+            cursor: c.cursor,
+            selector,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileConfig {
+    pub name: String,
+    #[serde(default)]
+    pub rule: Option<ProfileRule>,
+    #[serde(default)]
+    pub monitors: Vec<MonitorConfig>,
+    #[serde(default)]
+    pub groups: Vec<GroupConfig>,
+}
+
+impl From<OsicProfile> for ProfileConfig {
+    fn from(p: OsicProfile) -> Self {
+        Self {
+            name: p.name,
+            rule: p.rule,
+            monitors: p.monitors.into_iter().map(MonitorConfig::from).collect(),
+            groups: p.groups.into_iter().map(GroupConfig::from).collect(),
+        }
+    }
+}
+
+impl From<ProfileConfig> for OsicProfile {
+    fn from(c: ProfileConfig) -> Self {
+        Self {
+            name: c.name,
+            rule: c.rule,
+            monitors: c
+                .monitors
+                .into_iter()
+                .map(OsicMonitorSettings::from)
+                .collect(),
+            groups: c.groups.into_iter().map(MonitorGroup::from).collect(),
+        }
+    }
+}
+
+impl OsicConfig {
+    pub fn marks(&self, id: &str) -> PictureMarks {
+        PictureMarks {
+            favorite: self.favorites.contains(id),
+            rating: self.ratings.get(id).copied(),
+            blacklisted: self.blacklist.contains(id),
+        }
+    }
+
+    pub fn app_settings(&self) -> OsicAppSettings {
+        OsicAppSettings {
+            location: self.location,
+            groups: self
+                .groups
+                .iter()
+                .cloned()
+                .map(MonitorGroup::from)
+                .collect(),
+            active_profile: self.active_profile.clone(),
+        }
+    }
+
+    pub fn set_app_settings(&mut self, s: &OsicAppSettings) {
+        self.location = s.location;
+        self.groups = s.groups.iter().cloned().map(GroupConfig::from).collect();
+        self.active_profile = s.active_profile.clone();
+    }
+
+    pub fn monitor_settings(&self) -> Vec<OsicMonitorSettings> {
+        self.monitors
+            .iter()
+            .cloned()
+            .map(OsicMonitorSettings::from)
+            .collect()
+    }
+
+    pub fn set_monitor_settings(&mut self, s: OsicMonitorSettings) {
+        let c = MonitorConfig::from(s);
+        match self
+            .monitors
+            .iter_mut()
+            .find(|m| m.device_id == c.device_id)
+        {
+            Some(m) => *m = c,
+            None => self.monitors.push(c),
+        }
+    }
+
+    pub fn profiles(&self) -> Vec<OsicProfile> {
+        self.profiles
+            .iter()
+            .cloned()
+            .map(OsicProfile::from)
+            .collect()
+    }
+
+    pub fn set_profiles(&mut self, profiles: &[OsicProfile]) {
+        self.profiles = profiles.iter().cloned().map(ProfileConfig::from).collect();
+    }
+}
+
+// What the app knows of a picture, see `OsicConfig::marks`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PictureMarks {
+    pub favorite: bool,
+    pub rating: Option<u8>,
+    pub blacklisted: bool,
+}
+
+// A picture as every machine sees it: the name of its folder and its file name. An
+// album shared between machines keeps both wherever it is mounted.
+pub fn picture_id(image: &Path) -> Option<String> {
+    let file = image.file_name()?.to_str()?;
+    let folder = image.parent()?.file_name()?.to_str()?;
+    Some(format!("{}/{}", folder, file))
+}
+
+pub fn config_path() -> PathBuf {
+    cache::os_config_folder().join("config.json")
+}
+
+pub fn parse(text: &str) -> Result<OsicConfig, String> {
+    let config: OsicConfig =
+        serde_json::from_str(text).map_err(|e| format!("Parse config Error: {}", e))?;
+    validate(&config)?;
+    Ok(config)
+}
+
+// What a config has to pass besides being well formed.
+pub fn validate(config: &OsicConfig) -> Result<(), String> {
+    if config.version > CONFIG_VERSION {
+        return Err(format!(
+            "Config version {} was written by a newer Osic",
+            config.version
+        ));
+    }
+    Ok(())
+}
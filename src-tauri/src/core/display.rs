@@ -1,15 +1,38 @@
+use image::ImageFormat;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
-use std::{collections::VecDeque, path::PathBuf};
-use tauri::{command, InvokePayload, PageLoadPayload, State, Window};
+use std::thread;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+use tauri::{command, AppHandle, InvokePayload, Manager, PageLoadPayload, State, Window};
 
-use super::{cache::OsicRecentImage, selector::OsicSlideSelector};
+use super::bundle::{self, BundleInfo, OsicBundle};
+use super::cache::{self, OsicAppSettings, OsicMonitorSettings, OsicRecentImage};
+use super::config::{self, GroupConfig, MonitorConfig, OsicConfig, PictureMarks};
+use super::edid::{self, MonitorFingerprint};
+use super::group::{GroupMode, MonitorGroup};
+use super::profile::{self, OsicProfile, ProfileRule, ProfileSummary};
+use super::render;
+use super::schedule::{self, SlideSchedule};
+use super::scheduler::{self, SystemClock};
+use super::selector::OsicSlideSelector;
+use super::solar::{self, Location, TimeAlbum};
+use super::span::{self, Bezel};
+use super::store;
+use super::sync::{self, SyncInfo, SyncReport};
+use super::utils;
+use super::watcher;
+use crate::tray;
 
-use super::win32::Win32API;
+use super::backend::{default_backend, NoBackend, WallpaperBackend};
 const MODES: &'static [&'static str] = &["Picture", "SlidShow"];
 const FITS: &'static [&'static str] = &["Fill", "Fit", "Stretch", "Tile", "Center", "Span"];
-const INTERVAL: &'static [&'static str] =
-    &["1 minute", "10 minutes", "30 minutes", "1 hour", "6 hour"];
+const HISTORY_SIZE: usize = 20;
+// widest accepted difference between picture and monitor aspect ratio
+const MAX_RATIO_RANGE: f32 = 2.0;
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Modes {
@@ -51,48 +74,6 @@ impl Modes {
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
-pub enum Interval {
-    OneMinute,
-    TenMinutes,
-    ThirtyMinutes,
-    OneHour,
-    SixHours,
-}
-
-impl Interval {
-    fn find(i: &str) -> Interval {
-        match i {
-            "1 minute" => Interval::OneMinute,
-            "10 minutes" => Interval::TenMinutes,
-            "30 minutes" => Interval::ThirtyMinutes,
-            "1 hour" => Interval::OneHour,
-            "6 hour" => Interval::SixHours,
-            _ => Interval::TenMinutes,
-        }
-    }
-
-    fn seconds(i: &Interval) -> u64 {
-        match i {
-            Interval::OneMinute => 60,
-            Interval::TenMinutes => 600,
-            Interval::ThirtyMinutes => 60 * 30,
-            Interval::OneHour => 60 * 60,
-            Interval::SixHours => 60 * 60 * 6,
-        }
-    }
-
-    fn as_str(&self) -> &'static str {
-        match self {
-            Interval::OneMinute => "1 minute",
-            Interval::TenMinutes => "10 minutes",
-            Interval::ThirtyMinutes => "30 minutes",
-            Interval::OneHour => "1 hour",
-            Interval::SixHours => "6 hours",
-        }
-    }
-}
-
-#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Monitor {
     pub name: String,
@@ -103,6 +84,70 @@ pub struct Monitor {
     pub bottom: i32,
     pub width: i32,
     pub height: i32,
+    pub make: String,
+    pub model: String,
+    pub serial: String,
+    pub scale: f32,
+    // wayland output transform: normal, 90, 180, 270, flipped, flipped-90...
+    pub transform: String,
+    // clockwise degrees: 0, 90, 180 or 270
+    pub rotation: u32,
+    // Hz, 0 when unknown
+    pub refresh_rate: f32,
+    pub width_mm: u32,
+    pub height_mm: u32,
+    pub fingerprint: Option<MonitorFingerprint>,
+}
+
+impl Monitor {
+    pub fn ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    pub fn is_portrait(&self) -> bool {
+        self.height > self.width
+    }
+
+    // Not every backend reports the physical size, the EDID does.
+    fn fill_physical_size(&mut self) {
+        if self.width_mm > 0 && self.height_mm > 0 {
+            return;
+        }
+        if let Some(f) = &self.fingerprint {
+            let (w, h) = (f.width_mm, f.height_mm);
+            // the EDID describes the panel unrotated
+            (self.width_mm, self.height_mm) = if self.rotation % 180 == 90 {
+                (h, w)
+            } else {
+                (w, h)
+            };
+        }
+    }
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            device_id: String::new(),
+            letf: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+            width: 0,
+            height: 0,
+            make: String::new(),
+            model: String::new(),
+            serial: String::new(),
+            scale: 1.0,
+            transform: "normal".to_string(),
+            rotation: 0,
+            refresh_rate: 0.0,
+            width_mm: 0,
+            height_mm: 0,
+            fingerprint: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -110,31 +155,132 @@ pub struct Monitor {
 pub struct MonitorWrapper {
     meta: Monitor,
     pub device_id: String,
+    // shown instead of the monitor name
+    pub nickname: Option<String>,
+    // used by the Span fit to keep the picture continuous behind the frames
+    pub bezel: Bezel,
+    // albums that take turns at sunrise, sunset and twilight
+    pub time_albums: Vec<TimeAlbum>,
     pub album_path: Option<PathBuf>,
     pub image: Option<PathBuf>,
     pub image_history: VecDeque<OsicRecentImage>,
     pub fit: Fits,
-    pub slide_interval: Interval,
+    pub slide_interval: SlideSchedule,
     pub slide_time: u64,
     pub mode: Modes,
     pub selector: OsicSlideSelector,
 }
 impl MonitorWrapper {
     fn new(monitor: Monitor) -> Self {
-        let ratio_value: f32 = monitor.width as f32 / monitor.height as f32;
+        let ratio_value: f32 = monitor.ratio();
         Self {
             device_id: monitor.device_id.clone(),
             meta: monitor,
+            nickname: None,
+            bezel: Bezel::default(),
+            time_albums: Vec::new(),
             mode: Modes::Picture,
             image: None,
             image_history: VecDeque::default(),
             fit: Fits::Fill,
             album_path: None,
-            slide_interval: Interval::TenMinutes,
+            slide_interval: SlideSchedule::default(),
             slide_time: 0,
             selector: OsicSlideSelector::new(ratio_value),
         }
     }
+
+    pub fn meta(&self) -> &Monitor {
+        &self.meta
+    }
+
+    // A monitor seen before gets its saved settings back, even when it shows up under
+    // a new device path. Those settings then move over to the new path.
+    fn restore(monitor: Monitor) -> Self {
+        let mut w = MonitorWrapper::new(monitor);
+        if let Some(s) = cache::find_monitor_settings(&w.meta) {
+            let moved = s.device_id != w.device_id;
+            if moved {
+                println!("Monitor {} was {}", w.device_id, s.device_id);
+                let _ = cache::remove_monitor_settings(s.device_id.clone());
+            }
+            w.apply_settings(s);
+            if moved {
+                let _ = cache::write_monitor_settings(OsicMonitorSettings::from(w.clone()));
+            }
+        }
+        w
+    }
+
+    fn apply_settings(&mut self, s: OsicMonitorSettings) {
+        let ratio_value = self.selector.ratio_value;
+        self.nickname = s.nickname;
+        self.bezel = s.bezel;
+        self.time_albums = s.time_albums;
+        self.mode = s.mode;
+        self.fit = s.fit;
+        self.image = s.image;
+        self.album_path = s.album_path;
+        self.image_history = s
+            .recent_images
+            .into_iter()
+            .map(|path| OsicRecentImage { path })
+            .collect();
+        self.slide_interval = s.slide_interval;
+        self.slide_time = s.slide_time;
+        self.selector = s.selector;
+        self.selector.set_ratio_value(ratio_value);
+        if !self.selector.path.as_os_str().is_empty() {
+            self.selector.reload(self.image.as_ref());
+        }
+    }
+
+    // Make `image` the current picture and remember it in the history.
+    fn show_image(&mut self, image: PathBuf) {
+        self.image_history.retain(|i| i.path != image);
+        self.image_history.push_front(OsicRecentImage {
+            path: image.clone(),
+        });
+        self.image_history.truncate(HISTORY_SIZE);
+        self.image = Some(image);
+    }
+
+    // In slideshow mode take the next picture from the album. Returns whether there
+    // is a new picture to show. `now` is in unix seconds.
+    fn next_slide(&mut self, now: u64) -> bool {
+        if self.mode != Modes::SlidShow {
+            return self.image.is_some();
+        }
+        match self.selector.one() {
+            Some(p) => {
+                self.show_image(p.path);
+                self.slide_time = now;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // When the slideshow moves on next, in unix seconds. None when not in slideshow mode.
+    pub fn slide_deadline(&self) -> Option<u64> {
+        if self.mode != Modes::SlidShow {
+            return None;
+        }
+        self.slide_interval.next_after(self.slide_time)
+    }
+
+    // Monitor geometry changed in place (resolution, position, rotation), keep the
+    // settings. Turning between portrait and landscape changes which pictures fit.
+    fn update_meta(&mut self, monitor: Monitor) {
+        if monitor.is_portrait() != self.meta.is_portrait() {
+            println!(
+                "Monitor {} rotated to {} degrees",
+                monitor.device_id, monitor.rotation
+            );
+        }
+        self.selector.set_ratio_value(monitor.ratio());
+        self.meta = monitor;
+    }
 }
 pub struct DisplayState(Arc<Mutex<DisplayHandle>>);
 pub type DisplayArg<'a> = State<'a, DisplayState>;
@@ -143,44 +289,1612 @@ impl DisplayState {
     pub fn new() -> Self {
         Self(Arc::new(Mutex::new(DisplayHandle::new())))
     }
+
+    pub fn with_backend(backend: Box<dyn WallpaperBackend>) -> Self {
+        Self(Arc::new(Mutex::new(DisplayHandle::with_backend(backend))))
+    }
+
+    // Follow monitors being plugged in and out for the lifetime of the app.
+    pub fn watch(&self, app: AppHandle) {
+        watcher::spawn(app.clone(), self.0.clone());
+        watcher::spawn_config(app, self.0.clone());
+    }
+
+    // Run the slideshows for the lifetime of the app.
+    pub fn schedule(&self, app: AppHandle) {
+        scheduler::spawn(app, self.0.clone(), SystemClock);
+    }
+
+    // Follow the shared sync folder for the lifetime of the app.
+    pub fn sync(&self, app: AppHandle) {
+        sync::spawn(app, self.0.clone());
+    }
+
+    // Settings are written a moment after they change, don't lose them on the way out.
+    pub fn flush(&self) {
+        if let Err(e) = store::flush() {
+            println!("Write config Error: {}", e);
+        }
+    }
+
+    // Show the saved pictures again without holding up the window.
+    pub fn restore_wallpapers(&self) {
+        let handle = self.0.clone();
+        thread::spawn(move || handle.lock().unwrap().restore_wallpapers());
+    }
+
+    pub fn profiles(&self) -> Vec<ProfileSummary> {
+        self.0.lock().unwrap().profiles()
+    }
+
+    // For the tray, which has no command arguments to go through.
+    pub fn apply_profile(&self, app: &AppHandle, name: &str) -> Result<(), String> {
+        let displays = self.0.lock().unwrap().apply_profile(name)?;
+        let _ = app.emit_all(watcher::MONITORS_CHANGED, displays);
+        tray::update_menu(app, &self.profiles());
+        Ok(())
+    }
+}
+
+fn enumerate(backend: &dyn WallpaperBackend) -> Result<Vec<Monitor>, String> {
+    let mut monitors = backend.get_monitor_device_path()?;
+    for m in &mut monitors {
+        m.fill_physical_size();
+    }
+    Ok(monitors)
 }
 
 pub struct DisplayHandle {
     monitors: Vec<MonitorWrapper>,
-    win32: Win32API,
+    backend: Box<dyn WallpaperBackend>,
+    error: Option<String>,
+    location: Option<Location>,
+    groups: Vec<MonitorGroup>,
+    active_profile: Option<String>,
+    ahead: HashMap<String, Ahead>,
+    // whether the desktop's own fit was set to leave rendered pictures alone
+    fit_set: bool,
+}
+
+// A slideshow's next picture, rendered while the current one is up so the backend can
+// load it early. `image` is cleared once the monitor shows something, the file stays
+// until the slot comes round again.
+struct Ahead {
+    image: Option<PathBuf>,
+    path: PathBuf,
+    slot: bool,
+}
+
+// What the scheduler moves on: a monitor on its own or a whole group.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SlideTarget {
+    Monitor(String),
+    Group(String),
+}
+
+// When a slideshow last moved on, as saved in its settings, and when it moves on
+// next. Unix seconds.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SlideTimes {
+    pub target: SlideTarget,
+    pub slide_time: u64,
+    pub deadline: u64,
 }
 
 impl DisplayHandle {
     fn new() -> Self {
-        let win32 = Win32API::new();
+        match default_backend() {
+            Ok(backend) => DisplayHandle::with_backend(backend),
+            Err(e) => {
+                println!("Wallpaper backend Error: {}", e);
+                DisplayHandle::with_backend(Box::new(NoBackend::new(e)))
+            }
+        }
+    }
 
-        match win32.get_monitor_device_path() {
+    pub fn with_backend(backend: Box<dyn WallpaperBackend>) -> Self {
+        println!("Wallpaper backend: {}", backend.name());
+        let mut settings = cache::load_app_settings();
+        for g in settings.groups.iter_mut() {
+            g.selector.reload(None);
+        }
+
+        let mut handle = match enumerate(backend.as_ref()) {
             Ok(monitors) => {
                 let ws = monitors
                     .into_iter()
-                    .map(|x| MonitorWrapper::new(x))
+                    .map(|x| MonitorWrapper::restore(x))
                     .collect::<Vec<MonitorWrapper>>();
 
                 Self {
                     monitors: ws,
-                    win32,
+                    backend,
+                    error: None,
+                    location: settings.location,
+                    groups: settings.groups,
+                    active_profile: settings.active_profile,
+                    ahead: HashMap::new(),
+                    fit_set: false,
                 }
             }
-            Err(_) => Self {
-                monitors: Vec::new(),
-                win32,
-            },
+            Err(e) => {
+                println!("Enumerate monitors Error: {}", e);
+                Self {
+                    monitors: Vec::new(),
+                    backend,
+                    error: Some(e),
+                    location: settings.location,
+                    groups: settings.groups,
+                    active_profile: settings.active_profile,
+                    ahead: HashMap::new(),
+                    fit_set: false,
+                }
+            }
+        };
+        if let Err(e) = handle.neutral_fit() {
+            println!("Set fit Error: {}", e);
+        }
+        handle
+    }
+
+    // Pictures are rendered at the monitor's resolution, so the desktop's fit only has
+    // to show them as they are. Set once, or again on the next apply when it failed.
+    fn neutral_fit(&mut self) -> Result<(), String> {
+        if !self.fit_set {
+            self.backend.set_fit(Fits::Fill)?;
+            self.fit_set = true;
         }
+        Ok(())
     }
 
     pub fn displays(&self) -> Vec<MonitorWrapper> {
         self.monitors.clone()
     }
+
+    fn monitor_mut(&mut self, device_id: &str) -> Result<&mut MonitorWrapper, String> {
+        self.monitors
+            .iter_mut()
+            .find(|w| w.device_id == device_id)
+            .ok_or_else(|| format!("Unknown monitor: {}", device_id))
+    }
+
+    // Change one monitor, save it and, when `f` says the picture is affected, show the
+    // result right away.
+    fn update<F>(&mut self, device_id: &str, f: F) -> Result<MonitorWrapper, String>
+    where
+        F: FnOnce(&mut MonitorWrapper) -> Result<bool, String>,
+    {
+        let blacklist = store::load().blacklist;
+        let w = self.monitor_mut(device_id)?;
+        // a slideshow moving on in `f` passes over blacklisted pictures
+        w.selector.set_blacklist(&blacklist);
+        let needs_apply = f(w)?;
+        cache::write_monitor_settings(OsicMonitorSettings::from(w.clone()))
+            .map_err(|e| format!("Write monitor settings Error: {}", e))?;
+        if needs_apply {
+            return self.apply(device_id);
+        }
+        self.monitor_mut(device_id).map(|w| w.clone())
+    }
+
+    pub fn set_nickname(
+        &mut self,
+        device_id: &str,
+        nickname: Option<String>,
+    ) -> Result<MonitorWrapper, String> {
+        self.update(device_id, |w| {
+            w.nickname = nickname
+                .map(|n| n.trim().to_string())
+                .filter(|n| !n.is_empty());
+            Ok(false)
+        })
+    }
+
+    pub fn set_bezel(&mut self, device_id: &str, bezel: Bezel) -> Result<MonitorWrapper, String> {
+        if [bezel.left, bezel.top, bezel.right, bezel.bottom]
+            .iter()
+            .any(|b| !b.is_finite() || *b < 0.0)
+        {
+            return Err(format!("Invalid bezel: {:?}", bezel));
+        }
+        self.update(device_id, |w| {
+            w.bezel = bezel;
+            Ok(w.fit == Fits::Span)
+        })
+    }
+
+    pub fn set_mode(&mut self, device_id: &str, mode: Modes) -> Result<MonitorWrapper, String> {
+        self.update(device_id, |w| {
+            w.mode = mode;
+            Ok(w.next_slide(utils::get_sys_time_in_secs()))
+        })
+    }
+
+    pub fn set_fit(&mut self, device_id: &str, fit: Fits) -> Result<MonitorWrapper, String> {
+        self.update(device_id, |w| {
+            w.fit = fit;
+            Ok(true)
+        })
+    }
+
+    pub fn set_album_path(
+        &mut self,
+        device_id: &str,
+        album_path: PathBuf,
+    ) -> Result<MonitorWrapper, String> {
+        if !album_path.is_dir() {
+            return Err(format!("Not a folder: {}", album_path.display()));
+        }
+        self.update(device_id, |w| {
+            w.album_path = Some(album_path.clone());
+            w.selector.set_album_path(album_path);
+            Ok(w.next_slide(utils::get_sys_time_in_secs()))
+        })
+    }
+
+    pub fn set_image(&mut self, device_id: &str, image: PathBuf) -> Result<MonitorWrapper, String> {
+        if !image.is_file() || ImageFormat::from_path(&image).is_err() {
+            return Err(format!("Not an image: {}", image.display()));
+        }
+        self.update(device_id, |w| {
+            w.show_image(image);
+            Ok(true)
+        })
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+
+    fn write_app_settings(&self) -> Result<(), String> {
+        cache::write_app_settings(&OsicAppSettings {
+            location: self.location,
+            groups: self.groups.clone(),
+            active_profile: self.active_profile.clone(),
+        })
+        .map_err(|e| format!("Write app settings Error: {}", e))
+    }
+
+    pub fn set_location(&mut self, location: Option<Location>) -> Result<(), String> {
+        self.location = location;
+        self.write_app_settings()
+    }
+
+    pub fn profiles(&self) -> Vec<ProfileSummary> {
+        cache::load_profiles()
+            .into_iter()
+            .map(|p| ProfileSummary {
+                active: self.active_profile.as_ref() == Some(&p.name),
+                monitors: p.monitors.len(),
+                rule: p.rule,
+                name: p.name,
+            })
+            .collect()
+    }
+
+    // Save what every monitor shows now. Monitors that are unplugged keep what the
+    // profile had for them.
+    pub fn save_profile(&mut self, name: &str) -> Result<Vec<ProfileSummary>, String> {
+        let name = profile::validate_name(name)?;
+        let mut profiles = cache::load_profiles();
+        let mut monitors = self
+            .monitors
+            .iter()
+            .map(|w| OsicMonitorSettings::from(w.clone()))
+            .collect::<Vec<OsicMonitorSettings>>();
+        if let Some(old) = profiles.iter().find(|p| p.name == name) {
+            for s in &old.monitors {
+                let connected = self
+                    .monitors
+                    .iter()
+                    .any(|w| edid::match_settings(&w.meta, std::slice::from_ref(s)).is_some());
+                if !connected {
+                    monitors.push(s.clone());
+                }
+            }
+        }
+
+        let rule = profiles
+            .iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.rule);
+        profiles.retain(|p| p.name != name);
+        profiles.push(OsicProfile {
+            name: name.clone(),
+            monitors,
+            groups: self.groups.clone(),
+            rule,
+        });
+        cache::write_profiles(&profiles).map_err(|e| format!("Write profiles Error: {}", e))?;
+        self.active_profile = Some(name);
+        self.write_app_settings()?;
+        Ok(self.profiles())
+    }
+
+    pub fn remove_profile(&mut self, name: &str) -> Result<Vec<ProfileSummary>, String> {
+        let mut profiles = cache::load_profiles();
+        let before = profiles.len();
+        profiles.retain(|p| p.name != name);
+        if profiles.len() == before {
+            return Err(format!("Unknown profile: {}", name));
+        }
+        cache::write_profiles(&profiles).map_err(|e| format!("Write profiles Error: {}", e))?;
+        if self.active_profile.as_deref() == Some(name) {
+            self.active_profile = None;
+            self.write_app_settings()?;
+        }
+        Ok(self.profiles())
+    }
+
+    pub fn set_profile_rule(
+        &mut self,
+        name: &str,
+        rule: Option<ProfileRule>,
+    ) -> Result<Vec<ProfileSummary>, String> {
+        if rule == Some(ProfileRule::Count(0)) {
+            return Err("A profile needs at least one monitor".to_string());
+        }
+        let mut profiles = cache::load_profiles();
+        match profiles.iter_mut().find(|p| p.name == name) {
+            Some(p) => p.rule = rule,
+            None => return Err(format!("Unknown profile: {}", name)),
+        }
+        cache::write_profiles(&profiles).map_err(|e| format!("Write profiles Error: {}", e))?;
+        Ok(self.profiles())
+    }
+
+    // The connected monitors as far as profile rules can tell them apart.
+    pub fn topology(&self) -> Vec<(String, Option<MonitorFingerprint>)> {
+        let mut topology = self
+            .monitors
+            .iter()
+            .map(|w| (w.device_id.clone(), w.meta.fingerprint.clone()))
+            .collect::<Vec<(String, Option<MonitorFingerprint>)>>();
+        topology.sort_by(|a, b| a.0.cmp(&b.0));
+        topology
+    }
+
+    // Switch to the profile whose rule fits the connected monitors. Returns the
+    // profile when it wasn't active yet.
+    pub fn apply_profile_for_topology(&mut self) -> Result<Option<String>, String> {
+        let profiles = cache::load_profiles();
+        let metas = self.metas();
+        let name = match profile::pick(&profiles, &metas, self.active_profile.as_deref()) {
+            Some(p) => p.name.clone(),
+            None => return Ok(None),
+        };
+        if self.active_profile.as_ref() == Some(&name) {
+            return Ok(None);
+        }
+        println!("Monitors changed, switching to profile {}", name);
+        self.apply_profile(&name)?;
+        Ok(Some(name))
+    }
+
+    // Switch every monitor to the profile at once. When the desktop refuses one of
+    // the pictures the previous settings come back, nothing is left half switched.
+    pub fn apply_profile(&mut self, name: &str) -> Result<Vec<MonitorWrapper>, String> {
+        let profile = cache::load_profiles()
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("Unknown profile: {}", name))?;
+        let now = utils::get_sys_time_in_secs();
+
+        let monitors = self
+            .monitors
+            .iter()
+            .map(|w| {
+                let mut next = w.clone();
+                if let Some(s) = edid::match_settings(&w.meta, &profile.monitors) {
+                    next.apply_settings(s.clone());
+                    // these belong to the hardware, not to the profile
+                    next.nickname = w.nickname.clone();
+                    next.bezel = w.bezel.clone();
+                    next.slide_time = now;
+                }
+                next
+            })
+            .collect::<Vec<MonitorWrapper>>();
+
+        let mut groups = profile.groups;
+        for g in groups.iter_mut() {
+            g.selector.reload(None);
+        }
+        let previous = (
+            std::mem::replace(&mut self.monitors, monitors),
+            std::mem::replace(&mut self.groups, groups),
+            self.active_profile.replace(profile.name),
+        );
+        if let Err(e) = self.apply_all() {
+            println!("Apply profile {} Error: {}", name, e);
+            (self.monitors, self.groups, self.active_profile) = previous;
+            let _ = self.apply_all();
+            return Err(e);
+        }
+
+        for w in &self.monitors {
+            let _ = cache::write_monitor_settings(OsicMonitorSettings::from(w.clone()));
+        }
+        self.write_app_settings()?;
+        Ok(self.displays())
+    }
+
+    // Monitors to apply to show every picture, a spanned picture only once.
+    fn apply_targets(&self) -> Vec<String> {
+        let mut spanned = false;
+        let mut ids = Vec::new();
+        for w in &self.monitors {
+            let span = w.fit == Fits::Span;
+            if span && spanned {
+                continue;
+            }
+            spanned |= span;
+            ids.push(w.device_id.clone());
+        }
+        ids
+    }
+
+    fn apply_all(&mut self) -> Result<(), String> {
+        for device_id in self.apply_targets() {
+            self.apply(&device_id)?;
+        }
+        Ok(())
+    }
+
+    // Put the saved pictures back after a start. A monitor that fails doesn't keep
+    // the others from getting theirs.
+    pub fn restore_wallpapers(&mut self) {
+        for device_id in self.apply_targets() {
+            if let Err(e) = self.apply(&device_id) {
+                println!("Restore wallpaper {} Error: {}", device_id, e);
+            }
+        }
+    }
+
+    pub fn groups(&self) -> Vec<MonitorGroup> {
+        self.groups.clone()
+    }
+
+    fn group_of(&self, device_id: &str) -> Option<&MonitorGroup> {
+        self.groups
+            .iter()
+            .find(|g| g.members.iter().any(|m| m == device_id))
+    }
+
+    // Create the group or replace the one with the same name, its members show their
+    // first pictures right away.
+    pub fn save_group(&mut self, group: MonitorGroup) -> Result<Vec<MonitorWrapper>, String> {
+        if group.name.trim().is_empty() {
+            return Err("A group needs a name".to_string());
+        }
+        if group.members.is_empty() {
+            return Err("A group needs members".to_string());
+        }
+        if !group.album_path.is_dir() {
+            return Err(format!("Not a folder: {}", group.album_path.display()));
+        }
+        for m in &group.members {
+            if !self.monitors.iter().any(|w| &w.device_id == m) {
+                return Err(format!("Unknown monitor: {}", m));
+            }
+            if let Some(other) = self.group_of(m).filter(|g| g.name != group.name) {
+                return Err(format!("Monitor {} is already in group {}", m, other.name));
+            }
+        }
+
+        let name = group.name.clone();
+        self.groups.retain(|g| g.name != name);
+        self.groups.push(group);
+        let changed = self.advance_group(&name, utils::get_sys_time_in_secs(), true)?;
+        self.write_app_settings()?;
+        Ok(changed)
+    }
+
+    pub fn remove_group(&mut self, name: &str) -> Result<(), String> {
+        let before = self.groups.len();
+        self.groups.retain(|g| g.name != name);
+        if self.groups.len() == before {
+            return Err(format!("Unknown group: {}", name));
+        }
+        self.write_app_settings()
+    }
+
+    // Hand the group's next pictures to its members, never one that another member
+    // shows.
+    pub fn advance_group(
+        &mut self,
+        name: &str,
+        now: u64,
+        all: bool,
+    ) -> Result<Vec<MonitorWrapper>, String> {
+        let mut group = self
+            .groups
+            .iter()
+            .find(|g| g.name == name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown group: {}", name))?;
+
+        // connected members, left to right
+        let mut members = self
+            .monitors
+            .iter()
+            .filter(|w| group.members.contains(&w.device_id))
+            .map(|w| (w.meta.letf, w.meta.top, w.device_id.clone()))
+            .collect::<Vec<(i32, i32, String)>>();
+        members.sort();
+        let members = members
+            .into_iter()
+            .map(|(_, _, id)| id)
+            .collect::<Vec<String>>();
+
+        group.selector.set_blacklist(&store::load().blacklist);
+        let turn = group.turn(&members, all);
+        let shown = self
+            .monitors
+            .iter()
+            .filter(|w| members.contains(&w.device_id))
+            .filter_map(|w| w.image.clone().map(|i| (w.device_id.clone(), i)))
+            .collect::<Vec<(String, PathBuf)>>();
+        let mut changed = Vec::new();
+        for (device_id, image) in group.deal(&turn, shown) {
+            match self.update(&device_id, |w| {
+                w.show_image(image);
+                Ok(true)
+            }) {
+                Ok(w) => changed.push(w),
+                Err(e) => println!("Group {} {} Error: {}", name, device_id, e),
+            }
+        }
+
+        group.slide_time = now;
+        if let Some(g) = self.groups.iter_mut().find(|g| g.name == name) {
+            *g = group;
+        }
+        self.write_app_settings()?;
+        Ok(changed)
+    }
+
+    // Every slideshow, grouped monitors follow their group.
+    pub fn slide_times(&self) -> Vec<SlideTimes> {
+        let mut times = self
+            .monitors
+            .iter()
+            .filter(|w| self.group_of(&w.device_id).is_none())
+            .filter_map(|w| {
+                w.slide_deadline().map(|deadline| SlideTimes {
+                    target: SlideTarget::Monitor(w.device_id.clone()),
+                    slide_time: w.slide_time,
+                    deadline,
+                })
+            })
+            .collect::<Vec<SlideTimes>>();
+        for g in &self.groups {
+            if let Some(deadline) = g.deadline() {
+                times.push(SlideTimes {
+                    target: SlideTarget::Group(g.name.clone()),
+                    slide_time: g.slide_time,
+                    deadline,
+                });
+            }
+        }
+        times
+    }
+
+    pub fn set_time_albums(
+        &mut self,
+        device_id: &str,
+        time_albums: Vec<TimeAlbum>,
+    ) -> Result<MonitorWrapper, String> {
+        if let Some(a) = time_albums.iter().find(|a| !a.album_path.is_dir()) {
+            return Err(format!("Not a folder: {}", a.album_path.display()));
+        }
+        self.update(device_id, |w| {
+            w.time_albums = time_albums;
+            Ok(false)
+        })?;
+        let now = utils::get_sys_time_in_secs();
+        match self.switch_time_album(device_id, now)? {
+            Some(w) => Ok(w),
+            None => self.monitor_mut(device_id).map(|w| w.clone()),
+        }
+    }
+
+    // Move the monitor to the album of the current time of day. Returns the monitor
+    // when it switched.
+    pub fn switch_time_album(
+        &mut self,
+        device_id: &str,
+        now: u64,
+    ) -> Result<Option<MonitorWrapper>, String> {
+        let location = match &self.location {
+            Some(l) => *l,
+            None => return Ok(None),
+        };
+        if self.group_of(device_id).is_some() {
+            return Ok(None);
+        }
+        let w = self.monitor_mut(device_id)?;
+        let album_path = match solar::current_album(&w.time_albums, &location, now) {
+            Some((p, _)) if w.album_path.as_ref() != Some(&p) => p,
+            _ => return Ok(None),
+        };
+        println!("Monitor {} switches to {}", device_id, album_path.display());
+        self.update(device_id, |w| {
+            w.album_path = Some(album_path.clone());
+            w.selector.set_album_path(album_path);
+            Ok(w.next_slide(now))
+        })
+        .map(Some)
+    }
+
+    // The next sunrise, sunset or twilight any monitor switches albums at.
+    pub fn next_time_album_switch(&self, now: u64) -> Option<u64> {
+        let location = self.location.as_ref()?;
+        self.monitors
+            .iter()
+            .filter_map(|w| solar::current_album(&w.time_albums, location, now))
+            .filter_map(|(_, next)| next)
+            .min()
+    }
+
+    // Next slideshow picture, the deadline restarts at `now` even when the album has
+    // nothing to show so an empty album isn't retried all the time.
+    pub fn advance_slide(&mut self, device_id: &str, now: u64) -> Result<MonitorWrapper, String> {
+        let w = self.update(device_id, |w| {
+            let changed = w.next_slide(now);
+            w.slide_time = now;
+            Ok(changed)
+        })?;
+        if let Err(e) = self.render_ahead(device_id) {
+            println!("Preload next slide Error: {}", e);
+        }
+        Ok(w)
+    }
+
+    // Render the picture the slideshow shows next and hand it to the backend now, so
+    // the next slide only has to be switched to.
+    fn render_ahead(&mut self, device_id: &str) -> Result<(), String> {
+        let w = self.monitor_mut(device_id)?;
+        if w.mode != Modes::SlidShow || w.fit == Fits::Span {
+            return Ok(());
+        }
+        let next = match w.selector.peek() {
+            Some(p) => p.path,
+            None => return Ok(()),
+        };
+        let w = w.clone();
+        let slot = self.ahead.get(device_id).is_some_and(|a| !a.slot);
+        let hash = utils::string_hash(w.device_id.clone()).map_err(|e| e.to_string())?;
+        let path = self.render_fit(&w, &next, &format!("next-{}-{}", hash, slot as u8))?;
+        self.backend.preload(&path.to_string_lossy())?;
+        self.ahead.insert(
+            device_id.to_string(),
+            Ahead {
+                image: Some(next),
+                path,
+                slot,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn set_slide_interval(
+        &mut self,
+        device_id: &str,
+        slide_interval: &str,
+        jitter: Option<&str>,
+    ) -> Result<MonitorWrapper, String> {
+        let jitter = match jitter.map(|j| j.trim()).filter(|j| !j.is_empty()) {
+            Some(j) => schedule::parse_duration(j)?,
+            None => 0,
+        };
+        let slide_interval = SlideSchedule::parse(slide_interval, jitter)?;
+        self.update(device_id, |w| {
+            w.slide_interval = slide_interval;
+            Ok(false)
+        })
+    }
+
+    // Only the given selector settings change.
+    pub fn set_selector(
+        &mut self,
+        device_id: &str,
+        shuffle: Option<bool>,
+        ratio: Option<bool>,
+        ratio_range: Option<f32>,
+    ) -> Result<MonitorWrapper, String> {
+        if let Some(range) = ratio_range {
+            if !range.is_finite() || range < 0.0 || range > MAX_RATIO_RANGE {
+                return Err(format!(
+                    "Ratio range must be between 0 and {}: {}",
+                    MAX_RATIO_RANGE, range
+                ));
+            }
+        }
+        self.update(device_id, |w| {
+            if let Some(shuffle) = shuffle {
+                w.selector.set_shuffle(shuffle);
+            }
+            if let Some(ratio) = ratio {
+                w.selector.set_ratio(ratio);
+            }
+            if let Some(range) = ratio_range {
+                w.selector.set_ratio_range(range);
+            }
+            Ok(false)
+        })
+    }
+
+    // Desktops only have one fit for all monitors (IDesktopWallpaper::SetPosition is
+    // global), so the monitor's own fit is rendered here at its exact resolution and
+    // shown with a fit that leaves such an image untouched.
+    pub fn apply(&mut self, device_id: &str) -> Result<MonitorWrapper, String> {
+        let w = self.monitor_mut(device_id)?.clone();
+        let image = match &w.image {
+            Some(image) => image.clone(),
+            None => return Ok(w),
+        };
+        if w.fit == Fits::Span {
+            self.apply_span(image)?;
+            return self.monitor_mut(device_id).map(|w| w.clone());
+        }
+
+        // rendered ahead by the slideshow
+        let ready = self
+            .ahead
+            .get_mut(device_id)
+            .and_then(|a| match a.image.take() {
+                Some(next) if next == image && a.path.exists() => Some(a.path.clone()),
+                _ => None,
+            });
+        let path = match ready {
+            Some(path) => path,
+            None => {
+                let hash = utils::string_hash(w.device_id.clone()).map_err(|e| e.to_string())?;
+                self.render_fit(&w, &image, &format!("fit-{}", hash))?
+            }
+        };
+
+        self.neutral_fit()?;
+        self.backend
+            .set_wallpaper(&w.device_id, &path.to_string_lossy())?;
+        Ok(w)
+    }
+
+    fn render_fit(
+        &self,
+        w: &MonitorWrapper,
+        image: &PathBuf,
+        prefix: &str,
+    ) -> Result<PathBuf, String> {
+        let img = image::open(image)
+            .map_err(|e| format!("Open image {} Error: {}", image.display(), e))?;
+        let background = render::rgba(self.backend.get_background_color().unwrap_or(0));
+        let part = render::render_fit(
+            &img,
+            w.meta.width.max(1) as u32,
+            w.meta.height.max(1) as u32,
+            &w.fit,
+            background,
+        );
+        cache::write_rendered_image(prefix, &part)
+    }
+
+    // One picture across every monitor, cut in software so screens of different size
+    // and the bezels between them are taken into account. Each monitor gets its own crop.
+    pub fn apply_span(&mut self, image: PathBuf) -> Result<Vec<MonitorWrapper>, String> {
+        let img = image::open(&image)
+            .map_err(|e| format!("Open image {} Error: {}", image.display(), e))?;
+        let metas = self.metas();
+        let bezels = self
+            .monitors
+            .iter()
+            .map(|w| (w.device_id.clone(), w.bezel.clone()))
+            .collect::<HashMap<String, Bezel>>();
+
+        let mut crops = Vec::new();
+        for (device_id, part) in span::span_crops(&img, &metas, &bezels)? {
+            let hash = utils::string_hash(device_id.clone()).map_err(|e| e.to_string())?;
+            let prefix = format!("span-{}", hash);
+            crops.push((device_id, cache::write_rendered_image(&prefix, &part)?));
+        }
+
+        // the crops match the resolution exactly
+        self.neutral_fit()?;
+        for (device_id, path) in &crops {
+            self.backend
+                .set_wallpaper(device_id, &path.to_string_lossy())?;
+        }
+
+        for w in &mut self.monitors {
+            w.fit = Fits::Span;
+            w.image = Some(image.clone());
+            let _ = cache::write_monitor_settings(OsicMonitorSettings::from(w.clone()));
+        }
+        Ok(self.displays())
+    }
+
+    // The config file was edited by hand or replaced. Monitors whose section changed
+    // take the new settings and are shown again, the others carry on undisturbed.
+    // Groups, location and the active profile follow the file. Returns the monitors
+    // that were shown again.
+    pub fn reload_config(&mut self, config: OsicConfig) -> Vec<MonitorWrapper> {
+        let saved = config.monitor_settings();
+        let ids = self
+            .monitors
+            .iter()
+            .map(|w| w.device_id.clone())
+            .collect::<Vec<String>>();
+        let mut changed = Vec::new();
+        for device_id in ids {
+            let w = match self.monitor_mut(&device_id) {
+                Ok(w) => w,
+                Err(_) => continue,
+            };
+            // a monitor without a section is back to defaults
+            let s = match edid::match_settings(&w.meta, &saved) {
+                Some(s) => s.clone(),
+                None => OsicMonitorSettings::from(MonitorWrapper::new(w.meta.clone())),
+            };
+            let current = MonitorConfig::from(OsicMonitorSettings::from(w.clone()));
+            if MonitorConfig::from(s.clone()) == current {
+                continue;
+            }
+            println!("Config changed for monitor {}", device_id);
+            w.apply_settings(s);
+            match self.apply(&device_id) {
+                Ok(w) => changed.push(w),
+                Err(e) => println!("Apply config {} Error: {}", device_id, e),
+            }
+        }
+
+        let groups = self
+            .groups
+            .iter()
+            .cloned()
+            .map(GroupConfig::from)
+            .collect::<Vec<GroupConfig>>();
+        let settings = config.app_settings();
+        if groups != config.groups {
+            self.groups = settings.groups;
+            for g in self.groups.iter_mut() {
+                g.selector.reload(None);
+            }
+        }
+        self.location = settings.location;
+        self.active_profile = settings.active_profile;
+        changed
+    }
+
+    pub fn export_settings(&self, path: &str) -> Result<(), String> {
+        let bundle = bundle::export(&store::load());
+        let text = serde_json::to_string_pretty(&bundle).unwrap();
+        fs::write(path, text).map_err(|e| format!("Write bundle Error: {}", e))
+    }
+
+    fn read_bundle(path: &str) -> Result<OsicBundle, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Read bundle Error: {}", e))?;
+        bundle::parse(&text)
+    }
+
+    pub fn inspect_bundle(&self, path: &str) -> Result<BundleInfo, String> {
+        Ok(bundle::info(
+            &DisplayHandle::read_bundle(path)?,
+            &self.metas(),
+        ))
+    }
+
+    // Replace the whole configuration by a bundle's and show it right away.
+    pub fn import_settings(
+        &mut self,
+        path: &str,
+        monitors: &HashMap<String, String>,
+        album_roots: &[PathBuf],
+    ) -> Result<Vec<MonitorWrapper>, String> {
+        let bundle = DisplayHandle::read_bundle(path)?;
+        let config = bundle::import(&bundle, &self.metas(), monitors, album_roots)?;
+        store::update(|c| *c = config.clone()).map_err(|e| format!("Write config Error: {}", e))?;
+        self.reload_config(config);
+        Ok(self.displays())
+    }
+
+    // Favorite, rating and blacklist of a picture, kept by `config::picture_id` so
+    // they sync to machines that have the album elsewhere.
+    pub fn picture_marks(&self, image: &Path) -> Result<PictureMarks, String> {
+        let id = config::picture_id(image)
+            .ok_or_else(|| format!("Not a picture: {}", image.display()))?;
+        Ok(store::load().marks(&id))
+    }
+
+    pub fn set_picture_marks(
+        &mut self,
+        image: &Path,
+        marks: PictureMarks,
+    ) -> Result<PictureMarks, String> {
+        if let Some(rating) = marks.rating {
+            if !(1..=5).contains(&rating) {
+                return Err(format!("Rating must be between 1 and 5: {}", rating));
+            }
+        }
+        let id = config::picture_id(image)
+            .ok_or_else(|| format!("Not a picture: {}", image.display()))?;
+        store::update(|c| {
+            if marks.favorite {
+                c.favorites.insert(id.clone());
+            } else {
+                c.favorites.remove(&id);
+            }
+            match marks.rating {
+                Some(rating) => c.ratings.insert(id.clone(), rating),
+                None => c.ratings.remove(&id),
+            };
+            if marks.blacklisted {
+                c.blacklist.insert(id.clone());
+            } else {
+                c.blacklist.remove(&id);
+            }
+        })
+        .map_err(|e| format!("Write config Error: {}", e))?;
+        Ok(marks)
+    }
+
+    pub fn sync_info(&self) -> SyncInfo {
+        let state = sync::load_state();
+        SyncInfo {
+            machine: state.machine,
+            folder: state.folder,
+            last_sync: state.last_sync,
+        }
+    }
+
+    pub fn set_sync_folder(&mut self, folder: Option<PathBuf>) -> Result<SyncInfo, String> {
+        if let Some(f) = &folder {
+            if !f.is_dir() {
+                return Err(format!("Sync folder not found: {}", f.display()));
+            }
+        }
+        let mut state = sync::load_state();
+        state.folder = folder;
+        sync::write_state(&state)?;
+        Ok(self.sync_info())
+    }
+
+    // One round with the shared sync folder. Profiles and picture marks other
+    // machines changed are taken over, the monitors keep their wallpaper until a
+    // profile is applied.
+    pub fn sync_shared(&mut self) -> Result<Option<SyncReport>, String> {
+        let mut config = store::load();
+        let connected = self
+            .monitors
+            .iter()
+            .map(|w| MonitorConfig::from(OsicMonitorSettings::from(w.clone())))
+            .collect::<Vec<MonitorConfig>>();
+        let now = utils::get_sys_time_in_millis() as u64;
+        let report = match sync::sync(&mut config, &connected, now)? {
+            Some(report) => report,
+            None => return Ok(None),
+        };
+        if !report.changed.is_empty() {
+            store::update(|c| {
+                c.profiles = config.profiles.clone();
+                c.active_profile = config.active_profile.clone();
+                c.favorites = config.favorites.clone();
+                c.ratings = config.ratings.clone();
+                c.blacklist = config.blacklist.clone();
+            })
+            .map_err(|e| format!("Write config Error: {}", e))?;
+            self.active_profile = config.active_profile;
+        }
+        Ok(Some(report))
+    }
+
+    fn metas(&self) -> Vec<Monitor> {
+        self.monitors.iter().map(|w| w.meta.clone()).collect()
+    }
+
+    // Enumerate again and bring the wrappers in line, matched by device id.
+    // Returns whether anything changed.
+    pub fn refresh_monitors(&mut self) -> Result<bool, String> {
+        let monitors = match enumerate(self.backend.as_ref()) {
+            Ok(m) => m,
+            Err(e) => {
+                self.error = Some(e.clone());
+                return Err(e);
+            }
+        };
+        let mut changed = self.error.take().is_some();
+
+        let (mut kept, removed): (Vec<MonitorWrapper>, Vec<MonitorWrapper>) = self
+            .monitors
+            .drain(..)
+            .partition(|w| monitors.iter().any(|m| m.device_id == w.device_id));
+        for w in removed {
+            println!("Monitor removed: {}", w.device_id);
+            let _ = cache::write_monitor_settings(OsicMonitorSettings::from(w));
+            changed = true;
+        }
+
+        for monitor in monitors {
+            match kept.iter().position(|w| w.device_id == monitor.device_id) {
+                Some(i) => {
+                    let mut w = kept.remove(i);
+                    if w.meta != monitor {
+                        w.update_meta(monitor);
+                        let _ = cache::write_monitor_settings(OsicMonitorSettings::from(w.clone()));
+                        changed = true;
+                    }
+                    self.monitors.push(w);
+                }
+                None => {
+                    println!("Monitor added: {}", monitor.device_id);
+                    self.monitors.push(MonitorWrapper::restore(monitor));
+                    changed = true;
+                }
+            }
+        }
+        Ok(changed)
+    }
 }
 
 #[command]
 pub fn display_info(display: DisplayArg<'_>) -> Result<Vec<MonitorWrapper>, String> {
     let handle = display.0.lock().unwrap();
     // let _ = window.emit("display_info", handle.displays());
+    if let Some(e) = &handle.error {
+        return Err(e.clone());
+    }
     Ok(handle.displays())
 }
+
+#[command]
+pub fn set_monitor_nickname(
+    display: DisplayArg<'_>,
+    device_id: String,
+    nickname: Option<String>,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_nickname(&device_id, nickname)
+}
+
+#[command]
+pub fn set_monitor_bezel(
+    display: DisplayArg<'_>,
+    device_id: String,
+    bezel: Bezel,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_bezel(&device_id, bezel)
+}
+
+#[command]
+pub fn set_span_wallpaper(
+    display: DisplayArg<'_>,
+    image: PathBuf,
+) -> Result<Vec<MonitorWrapper>, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.apply_span(image)
+}
+
+#[command]
+pub fn apply_wallpaper(
+    display: DisplayArg<'_>,
+    device_id: String,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.apply(&device_id)
+}
+
+#[command]
+pub fn set_monitor_mode(
+    display: DisplayArg<'_>,
+    device_id: String,
+    mode: Modes,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_mode(&device_id, mode)
+}
+
+#[command]
+pub fn set_monitor_fit(
+    display: DisplayArg<'_>,
+    device_id: String,
+    fit: Fits,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_fit(&device_id, fit)
+}
+
+#[command]
+pub fn set_monitor_album_path(
+    display: DisplayArg<'_>,
+    device_id: String,
+    album_path: PathBuf,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_album_path(&device_id, album_path)
+}
+
+#[command]
+pub fn set_monitor_image(
+    display: DisplayArg<'_>,
+    device_id: String,
+    image: PathBuf,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_image(&device_id, image)
+}
+
+#[command]
+pub fn set_monitor_slide_interval(
+    display: DisplayArg<'_>,
+    device_id: String,
+    slide_interval: String,
+    jitter: Option<String>,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_slide_interval(&device_id, &slide_interval, jitter.as_deref())
+}
+
+#[command]
+pub fn set_monitor_selector(
+    display: DisplayArg<'_>,
+    device_id: String,
+    shuffle: Option<bool>,
+    ratio: Option<bool>,
+    ratio_range: Option<f32>,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_selector(&device_id, shuffle, ratio, ratio_range)
+}
+
+#[command]
+pub fn set_monitor_time_albums(
+    display: DisplayArg<'_>,
+    device_id: String,
+    time_albums: Vec<TimeAlbum>,
+) -> Result<MonitorWrapper, String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.set_time_albums(&device_id, time_albums)
+}
+
+#[command]
+pub fn location_info(display: DisplayArg<'_>) -> Option<Location> {
+    display.0.lock().unwrap().location()
+}
+
+// Both or neither, without a location there are no solar events.
+#[command]
+pub fn set_location(
+    display: DisplayArg<'_>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+) -> Result<Option<Location>, String> {
+    let location = match (latitude, longitude) {
+        (Some(lat), Some(lon)) => Some(Location::new(lat, lon)?),
+        (None, None) => None,
+        _ => return Err("Latitude and longitude go together".to_string()),
+    };
+    let mut handle = display.0.lock().unwrap();
+    handle.set_location(location)?;
+    Ok(location)
+}
+
+#[command]
+pub fn group_info(display: DisplayArg<'_>) -> Vec<MonitorGroup> {
+    display.0.lock().unwrap().groups()
+}
+
+#[command]
+pub fn save_monitor_group(
+    display: DisplayArg<'_>,
+    name: String,
+    members: Vec<String>,
+    mode: GroupMode,
+    album_path: PathBuf,
+    slide_interval: String,
+    jitter: Option<String>,
+) -> Result<Vec<MonitorWrapper>, String> {
+    let jitter = match jitter
+        .as_deref()
+        .map(|j| j.trim())
+        .filter(|j| !j.is_empty())
+    {
+        Some(j) => schedule::parse_duration(j)?,
+        None => 0,
+    };
+    let slide_interval = SlideSchedule::parse(&slide_interval, jitter)?;
+    let group = MonitorGroup::new(
+        name.trim().to_string(),
+        members,
+        mode,
+        album_path,
+        slide_interval,
+    );
+    let mut handle = display.0.lock().unwrap();
+    handle.save_group(group)
+}
+
+#[command]
+pub fn remove_monitor_group(display: DisplayArg<'_>, name: String) -> Result<(), String> {
+    let mut handle = display.0.lock().unwrap();
+    handle.remove_group(&name)
+}
+
+#[command]
+pub fn profile_info(display: DisplayArg<'_>) -> Vec<ProfileSummary> {
+    display.profiles()
+}
+
+#[command]
+pub fn save_profile(
+    app: AppHandle,
+    display: DisplayArg<'_>,
+    name: String,
+) -> Result<Vec<ProfileSummary>, String> {
+    let profiles = display.0.lock().unwrap().save_profile(&name)?;
+    tray::update_menu(&app, &profiles);
+    Ok(profiles)
+}
+
+#[command]
+pub fn remove_profile(
+    app: AppHandle,
+    display: DisplayArg<'_>,
+    name: String,
+) -> Result<Vec<ProfileSummary>, String> {
+    let profiles = display.0.lock().unwrap().remove_profile(&name)?;
+    tray::update_menu(&app, &profiles);
+    Ok(profiles)
+}
+
+#[command]
+pub fn apply_profile(
+    app: AppHandle,
+    display: DisplayArg<'_>,
+    name: String,
+) -> Result<Vec<MonitorWrapper>, String> {
+    let displays = display.0.lock().unwrap().apply_profile(&name)?;
+    tray::update_menu(&app, &display.profiles());
+    Ok(displays)
+}
+
+#[command]
+pub fn set_profile_rule(
+    app: AppHandle,
+    display: DisplayArg<'_>,
+    name: String,
+    rule: Option<ProfileRule>,
+) -> Result<Vec<ProfileSummary>, String> {
+    let profiles = display.0.lock().unwrap().set_profile_rule(&name, rule)?;
+    tray::update_menu(&app, &profiles);
+    Ok(profiles)
+}
+
+#[command]
+pub fn export_settings(display: DisplayArg<'_>, path: String) -> Result<(), String> {
+    display.0.lock().unwrap().export_settings(&path)
+}
+
+#[command]
+pub fn inspect_settings_bundle(
+    display: DisplayArg<'_>,
+    path: String,
+) -> Result<BundleInfo, String> {
+    display.0.lock().unwrap().inspect_bundle(&path)
+}
+
+#[command]
+pub fn import_settings(
+    app: AppHandle,
+    display: DisplayArg<'_>,
+    path: String,
+    monitors: HashMap<String, String>,
+    album_roots: Vec<PathBuf>,
+) -> Result<Vec<MonitorWrapper>, String> {
+    let displays = display
+        .0
+        .lock()
+        .unwrap()
+        .import_settings(&path, &monitors, &album_roots)?;
+    tray::update_menu(&app, &display.profiles());
+    Ok(displays)
+}
+
+#[command]
+pub fn picture_marks(display: DisplayArg<'_>, image: PathBuf) -> Result<PictureMarks, String> {
+    display.0.lock().unwrap().picture_marks(&image)
+}
+
+#[command]
+pub fn set_picture_marks(
+    display: DisplayArg<'_>,
+    image: PathBuf,
+    marks: PictureMarks,
+) -> Result<PictureMarks, String> {
+    display.0.lock().unwrap().set_picture_marks(&image, marks)
+}
+
+#[command]
+pub fn sync_info(display: DisplayArg<'_>) -> SyncInfo {
+    display.0.lock().unwrap().sync_info()
+}
+
+#[command]
+pub fn set_sync_folder(
+    display: DisplayArg<'_>,
+    folder: Option<PathBuf>,
+) -> Result<SyncInfo, String> {
+    display.0.lock().unwrap().set_sync_folder(folder)
+}
+
+#[command]
+pub fn sync_now(app: AppHandle, display: DisplayArg<'_>) -> Result<SyncReport, String> {
+    let report = display
+        .0
+        .lock()
+        .unwrap()
+        .sync_shared()?
+        .ok_or_else(|| "No sync folder set".to_string())?;
+    if !report.changed.is_empty() {
+        tray::update_menu(&app, &display.profiles());
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::fake::{fake_monitor, FakeBackend};
+    use crate::core::testutil::TempDir;
+    use image::{Rgb, RgbImage};
+
+    // The settings of the whole test run are shared, so every test names its own
+    // monitors. They are 64x48 and side by side.
+    fn handle(ids: &[&str]) -> (DisplayHandle, FakeBackend) {
+        let monitors = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| fake_monitor(id, id, i as i32 * 64, 0, 64, 48))
+            .collect();
+        let backend = FakeBackend::with_monitors(monitors);
+        (
+            DisplayHandle::with_backend(Box::new(backend.clone())),
+            backend,
+        )
+    }
+
+    fn picture(dir: &TempDir, name: &str, color: [u8; 3]) -> PathBuf {
+        let path = dir.path().join(name);
+        RgbImage::from_pixel(16, 16, Rgb(color))
+            .save(&path)
+            .unwrap();
+        path
+    }
+
+    // What the desktop shows on the monitor.
+    fn shown(backend: &FakeBackend, device_id: &str) -> RgbImage {
+        let path = backend.state().lock().unwrap().wallpapers[device_id].clone();
+        image::open(path).unwrap().to_rgb8()
+    }
+
+    // Rendered wallpapers are jpeg, colors come back close.
+    fn is_color(image: &RgbImage, x: u32, y: u32, color: [u8; 3]) -> bool {
+        let Rgb(pixel) = *image.get_pixel(x, y);
+        pixel.iter().zip(color).all(|(a, b)| a.abs_diff(b) < 16)
+    }
+
+    #[test]
+    fn set_image_renders_the_picture_for_the_monitor() {
+        let dir = TempDir::new("display-image");
+        let red = picture(&dir, "red.png", [255, 0, 0]);
+        let (mut handle, backend) = handle(&["IMAGE-0"]);
+
+        assert!(handle
+            .set_image("IMAGE-0", dir.path().join("gone.png"))
+            .is_err());
+        assert!(handle.set_image("IMAGE-9", red.clone()).is_err());
+
+        let w = handle.set_image("IMAGE-0", red.clone()).unwrap();
+        assert_eq!(w.image, Some(red.clone()));
+        assert_eq!(w.image_history[0].path, red);
+        let image = shown(&backend, "IMAGE-0");
+        assert_eq!(image.dimensions(), (64, 48));
+        assert!(is_color(&image, 0, 0, [255, 0, 0]));
+        // the desktop's own fit stays neutral, the picture comes rendered
+        assert_eq!(backend.state().lock().unwrap().fit, Some(Fits::Fill));
+        let saved = cache::load_monitor_settings("IMAGE-0".to_string()).unwrap();
+        assert_eq!(saved.image, Some(red));
+    }
+
+    #[test]
+    fn set_fit_renders_again_with_the_new_fit() {
+        let dir = TempDir::new("display-fit");
+        let red = picture(&dir, "red.png", [255, 0, 0]);
+        let (mut handle, backend) = handle(&["FIT-0"]);
+        handle.set_image("FIT-0", red).unwrap();
+
+        let w = handle.set_fit("FIT-0", Fits::Center).unwrap();
+        assert_eq!(w.fit, Fits::Center);
+        let image = shown(&backend, "FIT-0");
+        assert!(is_color(&image, 32, 24, [255, 0, 0]));
+        assert!(is_color(&image, 0, 0, [0, 0, 0]));
+
+        handle.set_fit("FIT-0", Fits::Stretch).unwrap();
+        let image = shown(&backend, "FIT-0");
+        assert!(is_color(&image, 0, 0, [255, 0, 0]));
+        assert!(is_color(&image, 63, 47, [255, 0, 0]));
+        assert_eq!(backend.state().lock().unwrap().fit, Some(Fits::Fill));
+        assert_eq!(
+            cache::load_monitor_settings("FIT-0".to_string())
+                .unwrap()
+                .fit,
+            Fits::Stretch
+        );
+    }
+
+    #[test]
+    fn advance_slide_shows_the_next_picture() {
+        let dir = TempDir::new("display-slide");
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+        for (i, color) in colors.iter().enumerate() {
+            picture(&dir, &format!("{}.png", i), *color);
+        }
+        let color_of = |path: &PathBuf| -> [u8; 3] {
+            let i: usize = path.file_stem().unwrap().to_str().unwrap().parse().unwrap();
+            colors[i]
+        };
+        let (mut handle, backend) = handle(&["SLIDE-0"]);
+        handle
+            .set_album_path("SLIDE-0", dir.path().to_path_buf())
+            .unwrap();
+        let first = handle.set_mode("SLIDE-0", Modes::SlidShow).unwrap();
+        let first = first.image.expect("a first slide");
+
+        let w = handle.advance_slide("SLIDE-0", 1000).unwrap();
+        let next = w.image.clone().unwrap();
+        assert_ne!(next, first);
+        assert_eq!(w.slide_time, 1000);
+        assert_eq!(w.image_history[1].path, first);
+        assert!(is_color(&shown(&backend, "SLIDE-0"), 0, 0, color_of(&next)));
+
+        let w = handle.advance_slide("SLIDE-0", 2000).unwrap();
+        assert_ne!(w.image.as_ref(), Some(&next));
+        assert!(is_color(
+            &shown(&backend, "SLIDE-0"),
+            0,
+            0,
+            color_of(w.image.as_ref().unwrap())
+        ));
+    }
+
+    #[test]
+    fn advance_group_deals_pictures_to_its_members() {
+        let dir = TempDir::new("display-group");
+        for (i, color) in [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]]
+            .iter()
+            .enumerate()
+        {
+            picture(&dir, &format!("{}.png", i), *color);
+        }
+        let (mut handle, backend) = handle(&["GROUP-0", "GROUP-1"]);
+        let group = MonitorGroup::new(
+            "Group test".to_string(),
+            vec!["GROUP-1".to_string(), "GROUP-0".to_string()],
+            GroupMode::Distributed,
+            dir.path().to_path_buf(),
+            SlideSchedule::default(),
+        );
+        let images = |handle: &DisplayHandle| -> Vec<Option<PathBuf>> {
+            handle.displays().into_iter().map(|w| w.image).collect()
+        };
+
+        // a new group fills every member with a picture of its own
+        let changed = handle.save_group(group).unwrap();
+        assert_eq!(changed.len(), 2);
+        let before = images(&handle);
+        assert!(before[0].is_some() && before[1].is_some());
+        assert_ne!(before[0], before[1]);
+        assert!(backend.state().lock().unwrap().wallpapers.len() == 2);
+
+        // then one member per step, left to right
+        let changed = handle.advance_group("Group test", 500, false).unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].device_id, "GROUP-0");
+        let after = images(&handle);
+        assert_ne!(after[0], before[0]);
+        assert_ne!(after[0], after[1]);
+        assert_eq!(after[1], before[1]);
+
+        let changed = handle.advance_group("Group test", 600, false).unwrap();
+        assert_eq!(changed[0].device_id, "GROUP-1");
+        assert_eq!(handle.groups()[0].slide_time, 600);
+        assert!(handle.advance_group("No such group", 700, true).is_err());
+    }
+
+    #[test]
+    fn slideshows_and_groups_pass_over_blacklisted_pictures() {
+        let dir = TempDir::new("display-blacklist");
+        let pictures = (0..4)
+            .map(|i| picture(&dir, &format!("{}.png", i), [0, 0, 0]))
+            .collect::<Vec<PathBuf>>();
+        let (mut handle, _) = handle(&["BLACK-0", "BLACK-1", "BLACK-2"]);
+        let blacklisted = PictureMarks {
+            favorite: false,
+            rating: None,
+            blacklisted: true,
+        };
+        for p in &pictures[..2] {
+            handle.set_picture_marks(p, blacklisted.clone()).unwrap();
+        }
+        let allowed = &pictures[2..];
+
+        handle
+            .set_album_path("BLACK-0", dir.path().to_path_buf())
+            .unwrap();
+        handle.set_mode("BLACK-0", Modes::SlidShow).unwrap();
+        for now in 0..10 {
+            let w = handle.advance_slide("BLACK-0", now).unwrap();
+            assert!(allowed.contains(w.image.as_ref().unwrap()));
+        }
+
+        let group = MonitorGroup::new(
+            "Blacklist test".to_string(),
+            vec!["BLACK-1".to_string(), "BLACK-2".to_string()],
+            GroupMode::Synchronized,
+            dir.path().to_path_buf(),
+            SlideSchedule::default(),
+        );
+        handle.save_group(group).unwrap();
+        for now in 0..10 {
+            handle.advance_group("Blacklist test", now, true).unwrap();
+            for w in handle.displays() {
+                assert!(allowed.contains(w.image.as_ref().unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn refresh_follows_monitors_coming_and_going() {
+        let (mut handle, backend) = handle(&["REFRESH-0"]);
+        let state = backend.state();
+        let ids = |handle: &DisplayHandle| -> Vec<String> {
+            handle.displays().into_iter().map(|w| w.device_id).collect()
+        };
+        assert_eq!(handle.refresh_monitors(), Ok(false));
+
+        let second = fake_monitor("REFRESH-1", "REFRESH-1", 64, 0, 64, 48);
+        state.lock().unwrap().monitors.push(second);
+        assert_eq!(handle.refresh_monitors(), Ok(true));
+        assert_eq!(ids(&handle), ["REFRESH-0", "REFRESH-1"]);
+
+        // turned in place, the settings stay
+        handle.set_fit("REFRESH-1", Fits::Center).unwrap();
+        state.lock().unwrap().monitors[1] = fake_monitor("REFRESH-1", "REFRESH-1", 64, 0, 48, 64);
+        assert_eq!(handle.refresh_monitors(), Ok(true));
+        let turned = &handle.displays()[1];
+        assert!(turned.meta().is_portrait());
+        assert_eq!(turned.fit, Fits::Center);
+
+        // unplugged and back, with its settings
+        handle
+            .set_nickname("REFRESH-0", Some("Left".to_string()))
+            .unwrap();
+        let first = state.lock().unwrap().monitors.remove(0);
+        assert_eq!(handle.refresh_monitors(), Ok(true));
+        assert_eq!(ids(&handle), ["REFRESH-1"]);
+        state.lock().unwrap().monitors.insert(0, first);
+        assert_eq!(handle.refresh_monitors(), Ok(true));
+        assert_eq!(ids(&handle), ["REFRESH-0", "REFRESH-1"]);
+        assert_eq!(handle.displays()[0].nickname.as_deref(), Some("Left"));
+    }
+
+    #[test]
+    fn missing_backend_is_reported() {
+        let reason = "No wallpaper backend for the desktop \"\"".to_string();
+        let mut handle = DisplayHandle::with_backend(Box::new(NoBackend::new(reason.clone())));
+        // what display_info hands to the UI
+        assert_eq!(handle.error, Some(reason.clone()));
+        assert!(handle.displays().is_empty());
+        assert_eq!(handle.refresh_monitors(), Err(reason));
+    }
+}
@@ -0,0 +1,289 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use super::cache::OsicMonitorSettings;
+use super::display::Monitor;
+
+// What identifies a physical monitor no matter which port or dock it is plugged into.
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorFingerprint {
+    pub manufacturer: String,
+    pub product_code: u16,
+    pub serial: u32,
+    pub serial_text: String,
+    pub model: String,
+    pub width_mm: u32,
+    pub height_mm: u32,
+}
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+// Text of a display descriptor, terminated by a line feed and padded with spaces.
+fn descriptor_text(d: &[u8]) -> String {
+    let text = &d[5..18];
+    let end = text.iter().position(|&c| c == 0x0a).unwrap_or(text.len());
+    String::from_utf8_lossy(&text[..end]).trim().to_string()
+}
+
+impl MonitorFingerprint {
+    // EDID 1.x base block
+    pub fn parse(edid: &[u8]) -> Option<Self> {
+        if edid.len() < 128 || edid[0..8] != EDID_HEADER {
+            return None;
+        }
+
+        // three 5 bit letters, 'A' is 1
+        let id = u16::from_be_bytes([edid[8], edid[9]]);
+        let manufacturer = [(id >> 10) & 0x1f, (id >> 5) & 0x1f, id & 0x1f]
+            .iter()
+            .map(|&c| (c as u8 + b'@') as char)
+            .collect::<String>();
+
+        let mut fingerprint = Self {
+            manufacturer,
+            product_code: u16::from_le_bytes([edid[10], edid[11]]),
+            serial: u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]),
+            width_mm: edid[21] as u32 * 10,
+            height_mm: edid[22] as u32 * 10,
+            ..Default::default()
+        };
+
+        for d in edid[54..126].chunks(18) {
+            if d[0] != 0 || d[1] != 0 {
+                continue;
+            }
+            match d[3] {
+                0xff => fingerprint.serial_text = descriptor_text(d),
+                0xfc => fingerprint.model = descriptor_text(d),
+                _ => {}
+            }
+        }
+        Some(fingerprint)
+    }
+
+    // Wayland compositors hand out the decoded EDID strings instead of the raw block.
+    pub fn from_description(make: &str, model: &str, serial: &str) -> Option<Self> {
+        if make.is_empty() && model.is_empty() {
+            return None;
+        }
+        Some(Self {
+            manufacturer: make.to_string(),
+            model: model.to_string(),
+            serial_text: serial.to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn has_serial(&self) -> bool {
+        self.serial != 0 || !self.serial_text.is_empty()
+    }
+
+    // Same kind of monitor, sizes are compared when both sides know them.
+    pub fn same_model(&self, other: &MonitorFingerprint) -> bool {
+        let size_known = self.width_mm > 0 && other.width_mm > 0;
+        self.manufacturer == other.manufacturer
+            && self.product_code == other.product_code
+            && self.model == other.model
+            && (!size_known
+                || (self.width_mm == other.width_mm && self.height_mm == other.height_mm))
+    }
+
+    // The very same unit, only possible when the monitor reports a serial number.
+    pub fn same_unit(&self, other: &MonitorFingerprint) -> bool {
+        self.same_model(other)
+            && self.has_serial()
+            && self.serial == other.serial
+            && self.serial_text == other.serial_text
+    }
+}
+
+// Raw EDID of a DRM connector such as "DP-1" or "HDMI-A-1", from /sys/class/drm/card0-DP-1/edid.
+pub fn read_sysfs(connector: &str) -> Option<Vec<u8>> {
+    let suffix = format!("-{}", connector);
+    fs::read_dir("/sys/class/drm")
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().ends_with(&suffix))
+        .find_map(|e| {
+            fs::read(e.path().join("edid"))
+                .ok()
+                .filter(|d| !d.is_empty())
+        })
+}
+
+pub fn fingerprint_sysfs(connector: &str) -> Option<MonitorFingerprint> {
+    read_sysfs(connector).and_then(|edid| MonitorFingerprint::parse(&edid))
+}
+
+// Pick the saved settings that belong to a monitor:
+// 1. same unit by fingerprint serial, wherever it is plugged in
+// 2. same device path, unless a different monitor now sits on that port
+// 3. same model, when exactly one saved monitor of that model could be meant
+pub fn match_settings<'a>(
+    monitor: &Monitor,
+    saved: &'a [OsicMonitorSettings],
+) -> Option<&'a OsicMonitorSettings> {
+    let fingerprint = monitor.fingerprint.as_ref();
+
+    if let Some(f) = fingerprint {
+        let unit = saved
+            .iter()
+            .find(|s| s.fingerprint.as_ref().is_some_and(|sf| f.same_unit(sf)));
+        if unit.is_some() {
+            return unit;
+        }
+    }
+
+    let by_path = saved.iter().find(|s| {
+        s.device_id == monitor.device_id
+            && match (fingerprint, &s.fingerprint) {
+                (Some(f), Some(sf)) => f.same_model(sf),
+                _ => true,
+            }
+    });
+    if by_path.is_some() {
+        return by_path;
+    }
+
+    let f = fingerprint?;
+    let models = saved
+        .iter()
+        .filter(|s| {
+            s.fingerprint
+                .as_ref()
+                .is_some_and(|sf| f.same_model(sf) && !(f.has_serial() && sf.has_serial()))
+        })
+        .collect::<Vec<&OsicMonitorSettings>>();
+    if models.len() == 1 {
+        return Some(models[0]);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::MonitorConfig;
+    use crate::core::fake::fake_monitor;
+
+    // A full EDID 1.4 base block: 3840x2160 timing, serial, name and range descriptors.
+    const U2720Q: &[u8] = include_bytes!("testdata/dell-u2720q.edid");
+
+    fn fingerprint(model: &str, serial: u32) -> MonitorFingerprint {
+        MonitorFingerprint {
+            manufacturer: "DEL".to_string(),
+            product_code: 0xa0c4,
+            serial,
+            model: model.to_string(),
+            width_mm: 600,
+            height_mm: 340,
+            ..Default::default()
+        }
+    }
+
+    fn saved(device_id: &str, fingerprint: Option<MonitorFingerprint>) -> OsicMonitorSettings {
+        let mut m: MonitorConfig =
+            serde_json::from_value(serde_json::json!({ "deviceId": device_id })).unwrap();
+        m.fingerprint = fingerprint;
+        OsicMonitorSettings::from(m)
+    }
+
+    fn plugged(device_id: &str, fingerprint: Option<MonitorFingerprint>) -> Monitor {
+        Monitor {
+            fingerprint,
+            ..fake_monitor(device_id, device_id, 0, 0, 3840, 2160)
+        }
+    }
+
+    fn matched<'a>(monitor: &Monitor, saved: &'a [OsicMonitorSettings]) -> Option<&'a str> {
+        match_settings(monitor, saved).map(|s| s.device_id.as_str())
+    }
+
+    #[test]
+    fn parse_a_full_edid() {
+        assert_eq!(U2720Q.len(), 128);
+        assert_eq!(U2720Q.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)), 0);
+        assert_eq!(
+            MonitorFingerprint::parse(U2720Q),
+            Some(MonitorFingerprint {
+                manufacturer: "DEL".to_string(),
+                product_code: 0xa0c4,
+                serial: 0x4c4b4b30,
+                serial_text: "9QKPN13".to_string(),
+                model: "DELL U2720Q".to_string(),
+                width_mm: 600,
+                height_mm: 340,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_rejects_what_is_not_an_edid() {
+        assert_eq!(MonitorFingerprint::parse(&U2720Q[..127]), None);
+        let mut broken = U2720Q.to_vec();
+        broken[1] = 0;
+        assert_eq!(MonitorFingerprint::parse(&broken), None);
+        // extension blocks behind the base block don't matter
+        let mut extended = U2720Q.to_vec();
+        extended.extend_from_slice(&[0x02; 128]);
+        assert_eq!(
+            MonitorFingerprint::parse(&extended),
+            MonitorFingerprint::parse(U2720Q)
+        );
+    }
+
+    #[test]
+    fn same_unit_wherever_it_is_plugged_in() {
+        // two of the same model, only the serial tells them apart
+        let saved = [
+            saved("DP-1", Some(fingerprint("DELL U2720Q", 1))),
+            saved("DP-2", Some(fingerprint("DELL U2720Q", 2))),
+        ];
+        let swapped = plugged("DP-1", Some(fingerprint("DELL U2720Q", 2)));
+        assert_eq!(matched(&swapped, &saved), Some("DP-2"));
+        let swapped = plugged("DP-2", Some(fingerprint("DELL U2720Q", 1)));
+        assert_eq!(matched(&swapped, &saved), Some("DP-1"));
+        let docked = plugged("DP-5", Some(fingerprint("DELL U2720Q", 2)));
+        assert_eq!(matched(&docked, &saved), Some("DP-2"));
+    }
+
+    #[test]
+    fn same_device_path() {
+        let saved = [
+            saved("DP-1", Some(fingerprint("DELL U2720Q", 1))),
+            saved("HDMI-1", None),
+        ];
+        // without an EDID the port decides
+        assert_eq!(matched(&plugged("HDMI-1", None), &saved), Some("HDMI-1"));
+        assert_eq!(matched(&plugged("DP-1", None), &saved), Some("DP-1"));
+        // another unit of the same model on the same port
+        let other = plugged("DP-1", Some(fingerprint("DELL U2720Q", 7)));
+        assert_eq!(matched(&other, &saved), Some("DP-1"));
+        // a different model on that port is a different monitor
+        let different = plugged("DP-1", Some(fingerprint("DELL P2419H", 1)));
+        assert_eq!(matched(&different, &saved), None);
+    }
+
+    #[test]
+    fn same_model_when_only_one_fits() {
+        // saved without a serial, any unit of the model on any port is that one
+        let saved_one = [
+            saved("DP-1", Some(fingerprint("DELL U2720Q", 0))),
+            saved("DP-2", Some(fingerprint("DELL P2419H", 0))),
+        ];
+        let moved = plugged("DP-3", Some(fingerprint("DELL U2720Q", 9)));
+        assert_eq!(matched(&moved, &saved_one), Some("DP-1"));
+
+        // two saved of the model, which one is meant can't be told
+        let saved_two = [
+            saved("DP-1", Some(fingerprint("DELL U2720Q", 0))),
+            saved("DP-2", Some(fingerprint("DELL U2720Q", 0))),
+        ];
+        assert_eq!(matched(&moved, &saved_two), None);
+
+        // both report serials and they differ: another unit, not this one
+        let saved_unit = [saved("DP-1", Some(fingerprint("DELL U2720Q", 1)))];
+        assert_eq!(matched(&moved, &saved_unit), None);
+    }
+}
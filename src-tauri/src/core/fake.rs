@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::backend::WallpaperBackend;
+use super::display::{Fits, Monitor};
+
+#[derive(Debug, Default)]
+pub struct FakeState {
+    pub monitors: Vec<Monitor>,
+    pub wallpapers: HashMap<String, String>,
+    pub fit: Option<Fits>,
+    pub background_color: u32,
+}
+
+// In-memory backend, clones share the same state so the desktop can be inspected
+// (or changed) from outside after the handle took ownership of the backend.
+#[derive(Clone, Default)]
+pub struct FakeBackend {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        FakeBackend::with_monitors(vec![
+            fake_monitor("FAKE-0", "Fake Landscape", 0, 0, 1920, 1080),
+            fake_monitor("FAKE-1", "Fake Portrait", 1920, 0, 1080, 1920),
+        ])
+    }
+
+    pub fn with_monitors(monitors: Vec<Monitor>) -> Self {
+        let backend = FakeBackend::default();
+        backend.state.lock().unwrap().monitors = monitors;
+        backend
+    }
+
+    pub fn state(&self) -> Arc<Mutex<FakeState>> {
+        self.state.clone()
+    }
+}
+
+pub fn fake_monitor(id: &str, name: &str, x: i32, y: i32, width: i32, height: i32) -> Monitor {
+    Monitor {
+        name: name.to_string(),
+        device_id: id.to_string(),
+        letf: x,
+        top: y,
+        right: x + width,
+        bottom: y + height,
+        width,
+        height,
+        ..Default::default()
+    }
+}
+
+impl WallpaperBackend for FakeBackend {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+        Ok(self.state.lock().unwrap().monitors.clone())
+    }
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if !state.monitors.iter().any(|m| m.device_id == monitor_id) {
+            return Err(format!("Unknown monitor: {}", monitor_id));
+        }
+        state
+            .wallpapers
+            .insert(monitor_id.to_string(), wallpaper.to_string());
+        Ok(())
+    }
+
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .wallpapers
+            .get(monitor_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String> {
+        self.state.lock().unwrap().fit = Some(fit);
+        Ok(())
+    }
+
+    fn set_background_color(&mut self, color: u32) -> Result<(), String> {
+        self.state.lock().unwrap().background_color = color;
+        Ok(())
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        Ok(self.state.lock().unwrap().background_color)
+    }
+}
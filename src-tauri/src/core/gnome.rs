@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::backend::WallpaperBackend;
+use super::cache;
+use super::command;
+use super::display::{Fits, Monitor};
+use super::edid;
+use super::render;
+use super::utils;
+
+// GNOME (and its Cinnamon fork) keeps one wallpaper for the whole desktop in gsettings.
+// Per-monitor wallpapers are combined into one image and shown with "spanned".
+pub struct GnomeBackend {
+    schema: &'static str,
+    dark_uri: bool,
+    fit: Fits,
+    wallpapers: HashMap<String, String>,
+}
+
+impl GnomeBackend {
+    pub fn gnome() -> Self {
+        Self {
+            schema: "org.gnome.desktop.background",
+            dark_uri: true,
+            fit: Fits::Fill,
+            wallpapers: HashMap::new(),
+        }
+    }
+
+    pub fn cinnamon() -> Self {
+        Self {
+            schema: "org.cinnamon.desktop.background",
+            dark_uri: false,
+            fit: Fits::Fill,
+            wallpapers: HashMap::new(),
+        }
+    }
+
+    fn gsettings(&self, args: &[&str]) -> Result<String, String> {
+        let output = Command::new("gsettings")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Run gsettings Error: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "gsettings {} Error: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn set_key(&self, key: &str, value: &str) -> Result<(), String> {
+        self.gsettings(&["set", self.schema, key, &gvariant_string(value)])
+            .map(|_| ())
+    }
+
+    fn get_key(&self, key: &str) -> Result<String, String> {
+        self.gsettings(&["get", self.schema, key])
+            .map(|v| v.trim_matches('\'').to_string())
+    }
+
+    fn set_picture(&self, path: &Path, options: &str) -> Result<(), String> {
+        let uri = utils::file_uri(path);
+        self.set_key("picture-uri", &uri)?;
+        if self.dark_uri {
+            self.set_key("picture-uri-dark", &uri)?;
+        }
+        self.set_key("picture-options", options)
+    }
+
+    // A single picture shared by every monitor can be handed over as is,
+    // anything else is rendered into one spanned image.
+    fn apply(&self) -> Result<(), String> {
+        let monitors = self.get_monitor_device_path()?;
+        let pictures = monitors
+            .iter()
+            .filter_map(|m| self.wallpapers.get(&m.device_id))
+            .collect::<Vec<&String>>();
+
+        if !pictures.is_empty()
+            && pictures.len() == monitors.len()
+            && pictures.iter().all(|p| *p == pictures[0])
+        {
+            return self.set_picture(Path::new(pictures[0]), fit_option(&self.fit));
+        }
+
+        let layers = monitors
+            .iter()
+            .filter_map(|m| {
+                self.wallpapers
+                    .get(&m.device_id)
+                    .map(|w| (m.device_id.clone(), PathBuf::from(w), self.fit.clone()))
+            })
+            .collect::<Vec<(String, PathBuf, Fits)>>();
+        let background = render::rgba(self.get_background_color().unwrap_or(0));
+        let span = render::compose_desktop(&monitors, &layers, background)?;
+        let path = cache::write_rendered_image("gnome-span", &span)?;
+        self.set_picture(&path, fit_option(&Fits::Span))
+    }
+}
+
+fn fit_option(fit: &Fits) -> &'static str {
+    match fit {
+        Fits::Fill => "zoom",
+        Fits::Fit => "scaled",
+        Fits::Stretch => "stretched",
+        Fits::Tile => "wallpaper",
+        Fits::Center => "centered",
+        Fits::Span => "spanned",
+    }
+}
+
+// gsettings parses its value as GVariant text, so strings are single quoted.
+fn gvariant_string(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+impl WallpaperBackend for GnomeBackend {
+    fn name(&self) -> &'static str {
+        if self.dark_uri {
+            "gnome"
+        } else {
+            "cinnamon"
+        }
+    }
+
+    // GNOME has no monitor listing in gsettings, xrandr works on Xorg and XWayland alike.
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+        let output = Command::new("xrandr")
+            .arg("--listmonitors")
+            .output()
+            .map_err(|e| format!("Run xrandr Error: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "xrandr Error: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let mut monitors = command::parse_monitor_lines(&String::from_utf8_lossy(&output.stdout));
+        for m in &mut monitors {
+            m.fingerprint = edid::fingerprint_sysfs(&m.device_id);
+        }
+        Ok(monitors)
+    }
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
+        self.wallpapers
+            .insert(monitor_id.to_string(), wallpaper.to_string());
+        self.apply()
+    }
+
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String> {
+        if let Some(w) = self.wallpapers.get(monitor_id) {
+            return Ok(w.clone());
+        }
+        self.get_key("picture-uri")
+            .map(|uri| utils::uri_to_path(&uri))
+    }
+
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String> {
+        self.fit = fit;
+        if self.wallpapers.is_empty() {
+            return self.set_key("picture-options", fit_option(&self.fit));
+        }
+        self.apply()
+    }
+
+    fn set_background_color(&mut self, color: u32) -> Result<(), String> {
+        self.set_key("color-shading-type", "solid")?;
+        self.set_key("primary-color", &format!("#{:06x}", color & 0xffffff))
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        let color = self.get_key("primary-color")?;
+        u32::from_str_radix(color.trim_start_matches('#'), 16)
+            .map_err(|e| format!("Parse primary-color {} Error: {}", color, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::Stubs;
+
+    const LISTMONITORS: &str = "printf 'Monitors: 2\\n 0: +*DP-1 200/597x100/336+0+0  DP-1\\n 1: +HDMI-1 100/527x200/296+200+0  HDMI-1\\n'";
+
+    fn stubs() -> Stubs {
+        let stubs = Stubs::new("gnome");
+        stubs.tool("xrandr", LISTMONITORS);
+        stubs.tool(
+            "gsettings",
+            "echo \"$@\" >> \"$LOG\"\ncase \"$3\" in primary-color) echo \"'#102030'\";; esac",
+        );
+        stubs
+    }
+
+    #[test]
+    fn lists_monitors_with_their_size() {
+        let _stubs = stubs();
+        let monitors = GnomeBackend::gnome().get_monitor_device_path().unwrap();
+        assert_eq!(monitors.len(), 2);
+        assert_eq!(monitors[1].device_id, "HDMI-1");
+        assert_eq!((monitors[1].letf, monitors[1].width), (200, 100));
+        assert_eq!((monitors[0].width_mm, monitors[0].height_mm), (597, 336));
+    }
+
+    fn picture(stubs: &Stubs, name: &str) -> String {
+        let path = stubs.dir.join(name);
+        image::RgbaImage::new(4, 4).save(&path).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn one_picture_everywhere_is_set_as_is() {
+        let stubs = stubs();
+        let picture = picture(&stubs, "a b.png");
+        let mut backend = GnomeBackend::gnome();
+        backend.set_wallpaper("DP-1", &picture).unwrap();
+        // one monitor without a picture yet, the desktop is rendered
+        assert!(stubs
+            .log()
+            .ends_with("set org.gnome.desktop.background picture-options 'spanned'\n"));
+
+        backend.set_wallpaper("HDMI-1", &picture).unwrap();
+        let uri = utils::file_uri(Path::new(&picture));
+        assert!(uri.ends_with("/a%20b.png"));
+        let log = stubs.log();
+        let last = log.lines().rev().take(3).collect::<Vec<&str>>();
+        assert_eq!(
+            last,
+            vec![
+                "set org.gnome.desktop.background picture-options 'zoom'".to_string(),
+                format!(
+                    "set org.gnome.desktop.background picture-uri-dark '{}'",
+                    uri
+                ),
+                format!("set org.gnome.desktop.background picture-uri '{}'", uri),
+            ]
+        );
+    }
+
+    #[test]
+    fn cinnamon_has_no_dark_uri() {
+        let stubs = stubs();
+        let picture = picture(&stubs, "a.png");
+        let mut backend = GnomeBackend::cinnamon();
+        backend.set_wallpaper("DP-1", &picture).unwrap();
+        backend.set_wallpaper("HDMI-1", &picture).unwrap();
+        let log = stubs.log();
+        assert!(!log.contains("picture-uri-dark"));
+        assert!(log.contains(&format!(
+            "set org.cinnamon.desktop.background picture-uri '{}'",
+            utils::file_uri(Path::new(&picture))
+        )));
+    }
+
+    #[test]
+    fn background_color() {
+        let stubs = stubs();
+        let mut backend = GnomeBackend::gnome();
+        backend.set_background_color(0xff0a0b0c).unwrap();
+        assert_eq!(
+            stubs.log(),
+            "set org.gnome.desktop.background color-shading-type 'solid'\n\
+             set org.gnome.desktop.background primary-color '#0a0b0c'\n"
+        );
+        assert_eq!(backend.get_background_color(), Ok(0x102030));
+    }
+
+    #[test]
+    fn gsettings_failure_is_reported() {
+        let stubs = Stubs::new("gnome-fail");
+        stubs.tool("gsettings", "echo 'No such schema' >&2\nexit 1");
+        let err = GnomeBackend::gnome().set_background_color(0).unwrap_err();
+        assert!(err.contains("No such schema"), "{}", err);
+    }
+}
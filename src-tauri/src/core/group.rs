@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use super::schedule::SlideSchedule;
+use super::selector::OsicSlideSelector;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum GroupMode {
+    // every member changes at the same instant
+    Synchronized,
+    // one member changes per step, left to right
+    Distributed,
+}
+
+// Monitors sharing one album and one clock. While a monitor is in a group its own
+// slideshow settings rest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorGroup {
+    pub name: String,
+    pub members: Vec<String>,
+    pub mode: GroupMode,
+    pub album_path: PathBuf,
+    pub slide_interval: SlideSchedule,
+    pub slide_time: u64,
+    // the member a distributed group changes next
+    pub cursor: usize,
+    pub selector: OsicSlideSelector,
+}
+
+impl MonitorGroup {
+    pub fn new(
+        name: String,
+        members: Vec<String>,
+        mode: GroupMode,
+        album_path: PathBuf,
+        slide_interval: SlideSchedule,
+    ) -> Self {
+        let mut selector = OsicSlideSelector::default();
+        selector.set_album_path(album_path.clone());
+        Self {
+            name,
+            members,
+            mode,
+            album_path,
+            slide_interval,
+            slide_time: 0,
+            cursor: 0,
+            selector,
+        }
+    }
+
+    pub fn deadline(&self) -> Option<u64> {
+        self.slide_interval.next_after(self.slide_time)
+    }
+
+    // Next picture that no other member shows. None when the album runs out of
+    // pictures, the member then keeps what it has.
+    pub fn pick(&mut self, exclude: &[PathBuf]) -> Option<PathBuf> {
+        self.selector.one_except(exclude).map(|p| p.path)
+    }
+
+    // The members that change this step, out of the connected ones left to right.
+    // `all` changes every member, otherwise a distributed group changes one.
+    pub fn turn(&mut self, members: &[String], all: bool) -> Vec<String> {
+        if all || self.mode == GroupMode::Synchronized {
+            members.to_vec()
+        } else if members.is_empty() {
+            Vec::new()
+        } else {
+            let i = self.cursor % members.len();
+            self.cursor = (i + 1) % members.len();
+            vec![members[i].clone()]
+        }
+    }
+
+    // New pictures for the members in `turn`, given what the members show now.
+    // Rather a new picture, but a repeat on the same monitor is fine.
+    pub fn deal(
+        &mut self,
+        turn: &[String],
+        mut shown: Vec<(String, PathBuf)>,
+    ) -> Vec<(String, PathBuf)> {
+        let mut dealt = Vec::new();
+        for device_id in turn {
+            let all_shown = shown
+                .iter()
+                .map(|(_, i)| i.clone())
+                .collect::<Vec<PathBuf>>();
+            let others = shown
+                .iter()
+                .filter(|(id, _)| id != device_id)
+                .map(|(_, i)| i.clone())
+                .collect::<Vec<PathBuf>>();
+            let image = match self.pick(&all_shown).or_else(|| self.pick(&others)) {
+                Some(image) => image,
+                None => continue,
+            };
+            shown.retain(|(id, _)| id != device_id);
+            shown.push((device_id.clone(), image.clone()));
+            dealt.push((device_id.clone(), image));
+        }
+        dealt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::TempDir;
+
+    fn album(dir: &TempDir, pictures: usize) {
+        for i in 0..pictures {
+            image::RgbImage::new(4, 3)
+                .save(dir.path().join(format!("{}.png", i)))
+                .unwrap();
+        }
+    }
+
+    fn group(dir: &TempDir, members: &[&str], mode: GroupMode) -> MonitorGroup {
+        MonitorGroup::new(
+            "desk".to_string(),
+            members.iter().map(|m| m.to_string()).collect(),
+            mode,
+            dir.path().to_path_buf(),
+            SlideSchedule::default(),
+        )
+    }
+
+    fn ids(members: &[&str]) -> Vec<String> {
+        members.iter().map(|m| m.to_string()).collect()
+    }
+
+    // What the members show after `dealt` replaced their pictures.
+    fn apply(shown: &mut Vec<(String, PathBuf)>, dealt: Vec<(String, PathBuf)>) {
+        for (id, image) in dealt {
+            shown.retain(|(m, _)| m != &id);
+            shown.push((id, image));
+        }
+    }
+
+    fn distinct(shown: &[(String, PathBuf)]) -> bool {
+        let mut images = shown.iter().map(|(_, i)| i).collect::<Vec<&PathBuf>>();
+        images.sort();
+        images.dedup();
+        images.len() == shown.len()
+    }
+
+    #[test]
+    fn members_never_show_the_same_picture() {
+        let dir = TempDir::new("group");
+        album(&dir, 5);
+        let members = ids(&["A", "B", "C"]);
+        let mut g = group(&dir, &["A", "B", "C"], GroupMode::Synchronized);
+        g.selector.set_shuffle(true);
+
+        let mut shown = Vec::new();
+        for _ in 0..20 {
+            let before = shown.clone();
+            let turn = g.turn(&members, false);
+            let dealt = g.deal(&turn, shown.clone());
+            assert_eq!(dealt.len(), 3);
+            apply(&mut shown, dealt);
+            assert!(distinct(&shown), "{:?}", shown);
+            // five pictures are enough for every member to get a new one
+            for (id, image) in &before {
+                assert!(!shown.contains(&(id.clone(), image.clone())), "{:?}", shown);
+            }
+        }
+    }
+
+    #[test]
+    fn distributed_group_changes_one_member_left_to_right() {
+        let dir = TempDir::new("group");
+        album(&dir, 5);
+        let members = ids(&["A", "B", "C"]);
+        let mut g = group(&dir, &["A", "B", "C"], GroupMode::Distributed);
+
+        let order = (0..4)
+            .map(|_| g.turn(&members, false))
+            .collect::<Vec<Vec<String>>>();
+        assert_eq!(
+            order,
+            vec![ids(&["A"]), ids(&["B"]), ids(&["C"]), ids(&["A"])]
+        );
+
+        // changing all of them doesn't move the cursor
+        assert_eq!(g.turn(&members, true), members);
+        assert_eq!(g.turn(&members, false), ids(&["B"]));
+
+        // a member went away, the cursor wraps
+        g.cursor = 2;
+        assert_eq!(g.turn(&ids(&["A", "B"]), false), ids(&["A"]));
+        assert!(g.turn(&[], false).is_empty());
+    }
+
+    #[test]
+    fn distributed_member_gets_a_picture_no_one_shows() {
+        let dir = TempDir::new("group");
+        album(&dir, 4);
+        let members = ids(&["A", "B", "C"]);
+        let mut g = group(&dir, &["A", "B", "C"], GroupMode::Distributed);
+
+        let turn = g.turn(&members, true);
+        let mut shown = Vec::new();
+        apply(&mut shown, g.deal(&turn, Vec::new()));
+        for _ in 0..12 {
+            let turn = g.turn(&members, false);
+            let dealt = g.deal(&turn, shown.clone());
+            assert_eq!(dealt.len(), 1);
+            assert_eq!(dealt[0].0, turn[0]);
+            let old = shown
+                .iter()
+                .find(|(id, _)| id == &turn[0])
+                .unwrap()
+                .1
+                .clone();
+            assert_ne!(dealt[0].1, old);
+            apply(&mut shown, dealt);
+            assert!(distinct(&shown), "{:?}", shown);
+        }
+    }
+
+    #[test]
+    fn small_album_leaves_members_without_a_new_picture() {
+        let dir = TempDir::new("group");
+        album(&dir, 2);
+        let members = ids(&["A", "B", "C"]);
+        let mut g = group(&dir, &["A", "B", "C"], GroupMode::Synchronized);
+
+        // two pictures for three members, the last one keeps what it has
+        let turn = g.turn(&members, false);
+        let dealt = g.deal(&turn, Vec::new());
+        assert_eq!(
+            dealt
+                .iter()
+                .map(|(id, _)| id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["A", "B"]
+        );
+        let mut shown = Vec::new();
+        apply(&mut shown, dealt);
+        assert!(distinct(&shown));
+
+        // nothing new is left, each member may only repeat its own picture
+        let dealt = g.deal(&ids(&["A"]), shown.clone());
+        assert_eq!(dealt, vec![shown[0].clone()]);
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::backend::WallpaperBackend;
+use super::display::{Fits, Monitor};
+use super::edid::{self, MonitorFingerprint};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HyprMonitor {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    make: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    serial: String,
+    width: i32,
+    height: i32,
+    x: i32,
+    y: i32,
+    scale: Option<f32>,
+    #[serde(default)]
+    refresh_rate: f32,
+    #[serde(default)]
+    transform: i32,
+}
+
+// wl_output transform enum
+fn transform_name(t: i32) -> &'static str {
+    match t {
+        1 => "90",
+        2 => "180",
+        3 => "270",
+        4 => "flipped",
+        5 => "flipped-90",
+        6 => "flipped-180",
+        7 => "flipped-270",
+        _ => "normal",
+    }
+}
+
+// hyprctl reports the mode in physical pixels, the rect is stored in layout coordinates.
+pub fn parse_hypr_monitors(json: &str) -> Result<Vec<Monitor>, String> {
+    let monitors: Vec<HyprMonitor> =
+        serde_json::from_str(json).map_err(|e| format!("Parse hyprctl monitors Error: {}", e))?;
+    Ok(monitors
+        .into_iter()
+        .map(|m| {
+            let scale = m.scale.unwrap_or(1.0);
+            let (mut width, mut height) = (
+                (m.width as f32 / scale).round() as i32,
+                (m.height as f32 / scale).round() as i32,
+            );
+            if m.transform % 2 == 1 {
+                std::mem::swap(&mut width, &mut height);
+            }
+            Monitor {
+                fingerprint: MonitorFingerprint::from_description(&m.make, &m.model, &m.serial),
+                name: if m.description.is_empty() {
+                    m.name.clone()
+                } else {
+                    m.description
+                },
+                device_id: m.name,
+                letf: m.x,
+                top: m.y,
+                right: m.x + width,
+                bottom: m.y + height,
+                width,
+                height,
+                make: m.make,
+                model: m.model,
+                serial: m.serial,
+                scale,
+                transform: transform_name(m.transform).to_string(),
+                rotation: (m.transform % 4) as u32 * 90,
+                refresh_rate: m.refresh_rate,
+                ..Default::default()
+            }
+        })
+        .collect())
+}
+
+fn default_socket() -> Option<PathBuf> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+    let mut candidates = Vec::new();
+    if let Ok(runtime) = env::var("XDG_RUNTIME_DIR") {
+        candidates.push(PathBuf::from(runtime).join("hypr"));
+    }
+    candidates.push(PathBuf::from("/tmp/hypr"));
+    candidates
+        .into_iter()
+        .map(|p| p.join(&signature).join(".hyprpaper.sock"))
+        .find(|p| p.exists())
+}
+
+// hyprpaper only shows images that were preloaded, and keeps every preloaded image in
+// memory until it is unloaded. The backend preloads a picture right before it is shown
+// and unloads whatever no monitor shows any more, so a slideshow stays at one picture
+// per monitor (plus the ones announced through `preload`).
+pub struct HyprpaperBackend {
+    socket: Option<PathBuf>,
+    fit: Fits,
+    loaded: HashSet<String>,
+    pending: HashSet<String>,
+    active: HashMap<String, String>,
+}
+
+impl HyprpaperBackend {
+    pub fn new() -> Self {
+        HyprpaperBackend::with_socket(default_socket())
+    }
+
+    pub fn with_socket(socket: Option<PathBuf>) -> Self {
+        Self {
+            socket,
+            fit: Fits::Fill,
+            loaded: HashSet::new(),
+            pending: HashSet::new(),
+            active: HashMap::new(),
+        }
+    }
+
+    fn request(&self, command: &str) -> Result<String, String> {
+        let socket = self
+            .socket
+            .as_ref()
+            .ok_or_else(|| "hyprpaper socket not found".to_string())?;
+        let mut stream = UnixStream::connect(socket)
+            .map_err(|e| format!("Connect {} Error: {}", socket.display(), e))?;
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        stream
+            .write_all(command.as_bytes())
+            .map_err(|e| format!("hyprpaper {} Error: {}", command, e))?;
+
+        let mut reply = String::new();
+        stream
+            .read_to_string(&mut reply)
+            .map_err(|e| format!("hyprpaper {} Error: {}", command, e))?;
+        let reply = reply.trim().to_string();
+        if reply != "ok" {
+            return Err(format!("hyprpaper {} Error: {}", command, reply));
+        }
+        Ok(reply)
+    }
+
+    fn load(&mut self, wallpaper: &str) -> Result<(), String> {
+        if self.loaded.contains(wallpaper) {
+            return Ok(());
+        }
+        self.request(&format!("preload {}", wallpaper))?;
+        self.loaded.insert(wallpaper.to_string());
+        Ok(())
+    }
+
+    fn show(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
+        self.load(wallpaper)?;
+        let prefix = match self.fit {
+            Fits::Fit => "contain:",
+            Fits::Tile => "tile:",
+            _ => "",
+        };
+        self.request(&format!("wallpaper {},{}{}", monitor_id, prefix, wallpaper))?;
+        self.pending.remove(wallpaper);
+        self.active
+            .insert(monitor_id.to_string(), wallpaper.to_string());
+        Ok(())
+    }
+
+    fn unload_unused(&mut self) -> Result<(), String> {
+        // a preloaded picture whose file is gone won't be shown any more
+        self.pending.retain(|w| Path::new(w).exists());
+        let unused = self
+            .loaded
+            .iter()
+            .filter(|w| !self.pending.contains(*w) && !self.active.values().any(|a| a == *w))
+            .cloned()
+            .collect::<Vec<String>>();
+        for wallpaper in unused {
+            self.request(&format!("unload {}", wallpaper))?;
+            self.loaded.remove(&wallpaper);
+        }
+        Ok(())
+    }
+}
+
+impl WallpaperBackend for HyprpaperBackend {
+    fn name(&self) -> &'static str {
+        "hyprpaper"
+    }
+
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+        let output = Command::new("hyprctl")
+            .args(["monitors", "-j"])
+            .output()
+            .map_err(|e| format!("Run hyprctl Error: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "hyprctl Error: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let mut monitors = parse_hypr_monitors(&String::from_utf8_lossy(&output.stdout))?;
+        for m in &mut monitors {
+            if let Some(f) = edid::fingerprint_sysfs(&m.device_id) {
+                m.fingerprint = Some(f);
+            }
+        }
+        Ok(monitors)
+    }
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
+        self.show(monitor_id, wallpaper)?;
+        self.unload_unused()
+    }
+
+    fn preload(&mut self, wallpaper: &str) -> Result<(), String> {
+        self.load(wallpaper)?;
+        self.pending.insert(wallpaper.to_string());
+        Ok(())
+    }
+
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String> {
+        Ok(self.active.get(monitor_id).cloned().unwrap_or_default())
+    }
+
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String> {
+        self.fit = fit;
+        let active = self
+            .active
+            .iter()
+            .map(|(m, w)| (m.clone(), w.clone()))
+            .collect::<Vec<(String, String)>>();
+        for (monitor_id, wallpaper) in active {
+            self.show(&monitor_id, &wallpaper)?;
+        }
+        Ok(())
+    }
+
+    fn set_background_color(&mut self, _color: u32) -> Result<(), String> {
+        Err("hyprpaper has no background color".to_string())
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        Err("hyprpaper has no background color".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    // Stands in for hyprpaper on its socket: keeps every request and answers "ok".
+    fn hyprpaper() -> (HyprpaperBackend, Arc<Mutex<Vec<String>>>, PathBuf) {
+        let dir = env::temp_dir().join(format!("osic-test-hyprpaper-{:x}", fastrand::u64(..)));
+        fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join(".hyprpaper.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let log = sent.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => break,
+                };
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).unwrap_or(0);
+                log.lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(&buf[..n]).to_string());
+                let _ = stream.write_all(b"ok");
+            }
+        });
+        (HyprpaperBackend::with_socket(Some(socket)), sent, dir)
+    }
+
+    fn picture(dir: &PathBuf, name: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, b"").unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn picture_is_preloaded_then_shown_with_the_fit() {
+        let (mut backend, sent, dir) = hyprpaper();
+        let a = picture(&dir, "a.jpg");
+        backend.set_wallpaper("DP-1", &a).unwrap();
+        backend.set_fit(Fits::Fit).unwrap();
+        backend.set_fit(Fits::Tile).unwrap();
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                format!("preload {}", a),
+                format!("wallpaper DP-1,{}", a),
+                format!("wallpaper DP-1,contain:{}", a),
+                format!("wallpaper DP-1,tile:{}", a),
+            ]
+        );
+        assert_eq!(backend.get_wallpaper("DP-1"), Ok(a));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn replaced_picture_is_unloaded() {
+        let (mut backend, sent, dir) = hyprpaper();
+        let (a, b) = (picture(&dir, "a.jpg"), picture(&dir, "b.jpg"));
+        backend.set_wallpaper("DP-1", &a).unwrap();
+        backend.set_wallpaper("HDMI-A-1", &a).unwrap();
+        backend.set_wallpaper("DP-1", &b).unwrap();
+        backend.set_wallpaper("HDMI-A-1", &b).unwrap();
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                format!("preload {}", a),
+                format!("wallpaper DP-1,{}", a),
+                format!("wallpaper HDMI-A-1,{}", a),
+                format!("preload {}", b),
+                format!("wallpaper DP-1,{}", b),
+                format!("wallpaper HDMI-A-1,{}", b),
+                format!("unload {}", a),
+            ]
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn announced_picture_stays_loaded_until_shown() {
+        let (mut backend, sent, dir) = hyprpaper();
+        let (a, next, gone) = (
+            picture(&dir, "a.jpg"),
+            picture(&dir, "next.jpg"),
+            picture(&dir, "gone.jpg"),
+        );
+        backend.set_wallpaper("DP-1", &a).unwrap();
+        backend.preload(&next).unwrap();
+        backend.preload(&gone).unwrap();
+        fs::remove_file(&gone).unwrap();
+        backend.set_wallpaper("DP-1", &next).unwrap();
+
+        let mut sent = sent.lock().unwrap().clone();
+        // unloaded in no particular order
+        sent[5..].sort();
+        let mut unloaded = vec![format!("unload {}", a), format!("unload {}", gone)];
+        unloaded.sort();
+        assert_eq!(
+            sent[..5],
+            [
+                format!("preload {}", a),
+                format!("wallpaper DP-1,{}", a),
+                format!("preload {}", next),
+                format!("preload {}", gone),
+                format!("wallpaper DP-1,{}", next),
+            ]
+        );
+        assert_eq!(sent[5..], unloaded[..]);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn missing_socket_is_an_error() {
+        let mut backend = HyprpaperBackend::with_socket(None);
+        assert!(backend.set_wallpaper("DP-1", "/pics/a.jpg").is_err());
+        assert!(backend.preload("/pics/a.jpg").is_err());
+    }
+}
@@ -0,0 +1,448 @@
+use std::path::Path;
+
+use zbus::blocking::Connection;
+
+use super::backend::WallpaperBackend;
+use super::display::{Fits, Monitor};
+use super::utils;
+
+const PLASMA_SERVICE: &str = "org.kde.plasmashell";
+const PLASMA_PATH: &str = "/PlasmaShell";
+const PLASMA_INTERFACE: &str = "org.kde.PlasmaShell";
+
+// The one D-Bus call the backend makes, apart so tests can stand in for plasmashell.
+pub trait PlasmaShell: Send {
+    fn evaluate_script(&self, script: &str) -> Result<String, String>;
+}
+
+pub struct SessionPlasma;
+
+impl PlasmaShell for SessionPlasma {
+    fn evaluate_script(&self, script: &str) -> Result<String, String> {
+        let connection =
+            Connection::session().map_err(|e| format!("Connect session bus Error: {}", e))?;
+        let reply = connection
+            .call_method(
+                Some(PLASMA_SERVICE),
+                PLASMA_PATH,
+                Some(PLASMA_INTERFACE),
+                "evaluateScript",
+                &(script,),
+            )
+            .map_err(|e| format!("evaluateScript Error: {}", e))?;
+        reply
+            .body()
+            .deserialize::<String>()
+            .map_err(|e| format!("evaluateScript reply Error: {}", e))
+    }
+}
+
+// Plasma keeps one desktop containment per screen, each with its own wallpaper config.
+// Monitors are identified as "<screen>:<width>x<height>+<x>+<y>", the geometry wins when
+// screens have been renumbered, the index is the fallback when a screen was resized.
+pub struct KdeBackend {
+    fit: Fits,
+    shell: Box<dyn PlasmaShell>,
+}
+
+impl KdeBackend {
+    pub fn new() -> Self {
+        KdeBackend::with_shell(Box::new(SessionPlasma))
+    }
+
+    pub fn with_shell(shell: Box<dyn PlasmaShell>) -> Self {
+        Self {
+            fit: Fits::Fill,
+            shell,
+        }
+    }
+
+    // Runs `body` with `d` bound to the containment of the monitor.
+    fn with_desktop(&self, monitor_id: &str, body: &str) -> Result<String, String> {
+        self.shell
+            .evaluate_script(&desktop_script(monitor_id, body)?)
+    }
+}
+
+const MONITORS_SCRIPT: &str = r#"var ds = desktops();
+for (var i = 0; i < ds.length; i++) {
+    if (ds[i].screen < 0) continue;
+    var g = screenGeometry(ds[i].screen);
+    print([ds[i].screen, g.x, g.y, g.width, g.height].join(","));
+}"#;
+
+const COLOR_SCRIPT: &str = r#"var d = desktops()[0];
+d.currentConfigGroup = ["Wallpaper", "org.kde.image", "General"];
+print(d.readConfig("Color"));"#;
+
+fn desktop_script(monitor_id: &str, body: &str) -> Result<String, String> {
+    let (screen, width, height, x, y) =
+        parse_device_id(monitor_id).ok_or_else(|| format!("Unknown monitor: {}", monitor_id))?;
+    Ok(format!(
+        r#"var ds = desktops();
+var d = null;
+for (var i = 0; i < ds.length; i++) {{
+    var g = screenGeometry(ds[i].screen);
+    if (ds[i].screen >= 0 && g.x == {x} && g.y == {y} && g.width == {width} && g.height == {height}) {{ d = ds[i]; break; }}
+}}
+for (var i = 0; d === null && i < ds.length; i++) {{
+    if (ds[i].screen == {screen}) {{ d = ds[i]; }}
+}}
+if (d === null) {{ throw "No desktop on screen {screen}"; }}
+d.wallpaperPlugin = "org.kde.image";
+d.currentConfigGroup = ["Wallpaper", "org.kde.image", "General"];
+{body}"#
+    ))
+}
+
+// `write` runs with `ds[i]` bound to each desktop in turn.
+fn all_desktops_script(write: &str) -> String {
+    format!(
+        r#"var ds = desktops();
+for (var i = 0; i < ds.length; i++) {{
+    ds[i].wallpaperPlugin = "org.kde.image";
+    ds[i].currentConfigGroup = ["Wallpaper", "org.kde.image", "General"];
+    {}
+}}"#,
+        write
+    )
+}
+
+// Lines of "screen,x,y,width,height" as printed by `MONITORS_SCRIPT`.
+fn parse_monitors(output: &str) -> Vec<Monitor> {
+    let mut monitors = Vec::new();
+    for line in output.lines() {
+        let v = line
+            .split(',')
+            .filter_map(|n| n.trim().parse::<i32>().ok())
+            .collect::<Vec<i32>>();
+        if v.len() != 5 {
+            continue;
+        }
+        let (screen, x, y, width, height) = (v[0], v[1], v[2], v[3], v[4]);
+        monitors.push(Monitor {
+            name: format!("Screen {}", screen),
+            device_id: format!("{}:{}x{}{:+}{:+}", screen, width, height, x, y),
+            letf: x,
+            top: y,
+            right: x + width,
+            bottom: y + height,
+            width,
+            height,
+            ..Default::default()
+        });
+    }
+    monitors.sort_by_key(|m| parse_device_id(&m.device_id).map(|i| i.0));
+    monitors
+}
+
+pub fn parse_device_id(id: &str) -> Option<(i32, i32, i32, i32, i32)> {
+    let (screen, geometry) = id.split_once(':')?;
+    let (width, height, x, y) = utils::parse_geometry(geometry)?;
+    Some((screen.parse().ok()?, width, height, x, y))
+}
+
+// Values of QtQuick Image.FillMode
+fn fill_mode(fit: &Fits) -> i32 {
+    match fit {
+        Fits::Stretch => 0,
+        Fits::Fit => 1,
+        Fits::Fill | Fits::Span => 2,
+        Fits::Tile => 3,
+        Fits::Center => 6,
+    }
+}
+
+fn js_string(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
+
+impl WallpaperBackend for KdeBackend {
+    fn name(&self) -> &'static str {
+        "kde"
+    }
+
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+        Ok(parse_monitors(
+            &self.shell.evaluate_script(MONITORS_SCRIPT)?,
+        ))
+    }
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
+        let body = format!(
+            "d.writeConfig(\"Image\", {});\nd.writeConfig(\"FillMode\", {});",
+            js_string(&utils::file_uri(Path::new(wallpaper))),
+            fill_mode(&self.fit)
+        );
+        self.with_desktop(monitor_id, &body).map(|_| ())
+    }
+
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String> {
+        let uri = self.with_desktop(monitor_id, "print(d.readConfig(\"Image\"));")?;
+        Ok(utils::uri_to_path(uri.trim()))
+    }
+
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String> {
+        self.fit = fit;
+        let write = format!("ds[i].writeConfig(\"FillMode\", {});", fill_mode(&self.fit));
+        self.shell
+            .evaluate_script(&all_desktops_script(&write))
+            .map(|_| ())
+    }
+
+    fn set_background_color(&mut self, color: u32) -> Result<(), String> {
+        let write = format!(
+            "ds[i].writeConfig(\"Color\", \"#{:06x}\");",
+            color & 0xffffff
+        );
+        self.shell
+            .evaluate_script(&all_desktops_script(&write))
+            .map(|_| ())
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        let output = self.shell.evaluate_script(COLOR_SCRIPT)?;
+        let color = output.trim().trim_start_matches('#');
+        u32::from_str_radix(color, 16).map_err(|e| format!("Parse Color {} Error: {}", color, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::TempDir;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::{Arc, Mutex};
+
+    // Stands in for plasmashell on the bus: keeps every script and answers with
+    // `reply`.
+    #[derive(Clone, Default)]
+    struct Plasma {
+        sent: Arc<Mutex<Vec<String>>>,
+        reply: String,
+    }
+
+    impl PlasmaShell for Plasma {
+        fn evaluate_script(&self, script: &str) -> Result<String, String> {
+            self.sent.lock().unwrap().push(script.to_string());
+            Ok(self.reply.clone())
+        }
+    }
+
+    fn plasma(reply: &str) -> (KdeBackend, Arc<Mutex<Vec<String>>>) {
+        let plasma = Plasma {
+            reply: reply.to_string(),
+            ..Default::default()
+        };
+        let sent = plasma.sent.clone();
+        (KdeBackend::with_shell(Box::new(plasma)), sent)
+    }
+
+    #[test]
+    fn monitors_from_the_script_output() {
+        let (backend, sent) = plasma("1,1920,0,1080,1920\n0,0,0,1920,1080\njunk\n");
+        let monitors = backend.get_monitor_device_path().unwrap();
+        assert_eq!(sent.lock().unwrap()[0], MONITORS_SCRIPT);
+        let ids = monitors
+            .iter()
+            .map(|m| m.device_id.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(ids, vec!["0:1920x1080+0+0", "1:1080x1920+1920+0"]);
+        assert_eq!((monitors[1].letf, monitors[1].bottom), (1920, 1920));
+    }
+
+    #[test]
+    fn wallpaper_goes_to_the_desktop_of_the_monitor() {
+        let (mut backend, sent) = plasma("");
+        backend.set_fit(Fits::Center).unwrap();
+        backend
+            .set_wallpaper("1:1080x1920+1920+0", "/pics/a \"b\".png")
+            .unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert!(sent[0].contains("ds[i].writeConfig(\"FillMode\", 6);"));
+        let script = &sent[1];
+        assert!(script.contains("g.x == 1920 && g.y == 0 && g.width == 1080 && g.height == 1920"));
+        assert!(script.contains("if (ds[i].screen == 1)"));
+        assert!(script.ends_with(
+            "d.writeConfig(\"Image\", \"file:///pics/a%20%22b%22.png\");\n\
+             d.writeConfig(\"FillMode\", 6);"
+        ));
+    }
+
+    #[test]
+    fn unknown_monitor_sends_nothing() {
+        let (mut backend, sent) = plasma("");
+        assert!(backend.set_wallpaper("DP-1", "/pics/a.png").is_err());
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_back_wallpaper_and_color() {
+        let (backend, sent) = plasma("file:///pics/a%20b.png\n");
+        assert_eq!(
+            backend.get_wallpaper("0:1920x1080+0+0"),
+            Ok("/pics/a b.png".to_string())
+        );
+        assert!(sent.lock().unwrap()[0].ends_with("print(d.readConfig(\"Image\"));"));
+
+        let (mut backend, sent) = plasma("#102030\n");
+        assert_eq!(backend.get_background_color(), Ok(0x102030));
+        backend.set_background_color(0xff0a0b0c).unwrap();
+        assert!(sent.lock().unwrap()[1].contains("ds[i].writeConfig(\"Color\", \"#0a0b0c\");"));
+    }
+
+    #[test]
+    fn every_fit_writes_its_fill_mode() {
+        let fits = [
+            (Fits::Stretch, 0),
+            (Fits::Fit, 1),
+            (Fits::Fill, 2),
+            (Fits::Span, 2),
+            (Fits::Tile, 3),
+            (Fits::Center, 6),
+        ];
+        for (fit, mode) in fits {
+            let (mut backend, sent) = plasma("");
+            backend.set_fit(fit.clone()).unwrap();
+            backend
+                .set_wallpaper("0:1920x1080+0+0", "/pics/a.png")
+                .unwrap();
+
+            let sent = sent.lock().unwrap();
+            assert_eq!(
+                sent[0],
+                all_desktops_script(&format!("ds[i].writeConfig(\"FillMode\", {});", mode)),
+                "{:?}",
+                fit
+            );
+            assert_eq!(
+                sent[1],
+                desktop_script(
+                    "0:1920x1080+0+0",
+                    &format!(
+                        "d.writeConfig(\"Image\", \"file:///pics/a.png\");\n\
+                         d.writeConfig(\"FillMode\", {});",
+                        mode
+                    )
+                )
+                .unwrap(),
+                "{:?}",
+                fit
+            );
+        }
+    }
+
+    #[test]
+    fn color_script_is_written_to_every_desktop() {
+        let (mut backend, sent) = plasma("");
+        backend.set_background_color(0x00ff80).unwrap();
+        backend.set_background_color(0x000001).unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(
+            sent[0],
+            "var ds = desktops();\n\
+             for (var i = 0; i < ds.length; i++) {\n    \
+             ds[i].wallpaperPlugin = \"org.kde.image\";\n    \
+             ds[i].currentConfigGroup = [\"Wallpaper\", \"org.kde.image\", \"General\"];\n    \
+             ds[i].writeConfig(\"Color\", \"#00ff80\");\n\
+             }"
+        );
+        // always six digits
+        assert!(sent[1].contains("ds[i].writeConfig(\"Color\", \"#000001\");"));
+    }
+
+    // plasmashell's scripting interface on a private session bus.
+    struct Shell {
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[zbus::interface(name = "org.kde.PlasmaShell")]
+    impl Shell {
+        #[zbus(name = "evaluateScript")]
+        fn evaluate_script(&self, script: String) -> String {
+            let reply = if script == MONITORS_SCRIPT {
+                "0,0,0,1920,1080\n"
+            } else {
+                ""
+            };
+            self.sent.lock().unwrap().push(script);
+            reply.to_string()
+        }
+    }
+
+    // The daemon goes before the directory of its socket.
+    struct Bus {
+        daemon: Child,
+        _dir: TempDir,
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    // None when dbus-daemon isn't installed.
+    fn session_bus() -> Option<(Bus, String)> {
+        let dir = TempDir::new("kde");
+        let child = Command::new("dbus-daemon")
+            .arg("--session")
+            .arg("--nofork")
+            .arg("--print-address")
+            .arg(format!(
+                "--address=unix:path={}",
+                dir.path().join("bus").display()
+            ))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut bus = Bus {
+            daemon: child.ok()?,
+            _dir: dir,
+        };
+        let mut address = String::new();
+        BufReader::new(bus.daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some((bus, address.trim().to_string()))
+    }
+
+    #[test]
+    fn scripts_go_over_the_session_bus() {
+        let (_bus, address) = match session_bus() {
+            Some(b) => b,
+            None => {
+                println!("dbus-daemon not found, skipping");
+                return;
+            }
+        };
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let _plasma = zbus::blocking::connection::Builder::address(address.as_str())
+            .unwrap()
+            .name(PLASMA_SERVICE)
+            .unwrap()
+            .serve_at(PLASMA_PATH, Shell { sent: sent.clone() })
+            .unwrap()
+            .build()
+            .unwrap();
+        // only this test talks to the session bus
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &address);
+
+        let mut backend = KdeBackend::new();
+        let monitors = backend.get_monitor_device_path().unwrap();
+        assert_eq!(monitors[0].device_id, "0:1920x1080+0+0");
+        backend.set_fit(Fits::Tile).unwrap();
+        backend
+            .set_wallpaper(&monitors[0].device_id, "/pics/a.png")
+            .unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0], MONITORS_SCRIPT);
+        assert!(sent[1].contains("ds[i].writeConfig(\"FillMode\", 3);"));
+        assert!(sent[2].ends_with("d.writeConfig(\"FillMode\", 3);"));
+    }
+}
@@ -0,0 +1,886 @@
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::fs;
+use std::path::PathBuf;
+
+use super::cache::{self, OsicAppSettings, OsicMonitorSettings};
+use super::config::{GroupConfig, MonitorConfig, OsicConfig};
+use super::display::{Fits, Modes};
+use super::edid::MonitorFingerprint;
+use super::group::{GroupMode, MonitorGroup};
+use super::profile::{OsicProfile, ProfileRule};
+use super::schedule::SlideSchedule;
+use super::solar::{Location, SolarEvent, TimeAlbum};
+use super::span::Bezel;
+
+// Settings of older versions, kept in the temp folder as bincode. Every file is a
+// magic for its kind, the schema version as little endian u16 and the payload.
+// Files written before the header existed start right with the payload; which
+// version they are is told by the layout that reads them to the last byte. Each
+// old layout has one step up to the next. Nothing writes bincode anymore, so every
+// type below is frozen as it was released and the live types can change freely.
+const MONITOR_MAGIC: &[u8; 4] = b"OSMS";
+const APP_MAGIC: &[u8; 4] = b"OSAS";
+const PROFILES_MAGIC: &[u8; 4] = b"OSPR";
+
+pub const MONITOR_SETTINGS_VERSION: u16 = 5;
+pub const APP_SETTINGS_VERSION: u16 = 3;
+pub const PROFILES_VERSION: u16 = 2;
+
+// Same byte layout as `bincode::serialize`, but a payload only counts when it is
+// read to the last byte.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+}
+
+// Schema version and payload. Files without a header are version 0.
+fn open<'a>(magic: &[u8; 4], bytes: &'a [u8]) -> (u16, &'a [u8]) {
+    if bytes.len() >= 6 && &bytes[..4] == magic {
+        (u16::from_le_bytes([bytes[4], bytes[5]]), &bytes[6..])
+    } else {
+        (0, bytes)
+    }
+}
+
+fn read<T: DeserializeOwned>(payload: &[u8]) -> Option<T> {
+    options().deserialize(payload).ok()
+}
+
+fn newer(what: &str, version: u16) {
+    println!(
+        "{} version {} was written by a newer Osic, using defaults",
+        what, version
+    );
+}
+
+// Types inside the settings. None of them changed while settings were bincode, all
+// versions share them. Enums are read by variant index, so the order is the layout.
+
+#[derive(Deserialize)]
+enum ModeV1 {
+    Picture,
+    SlidShow,
+}
+
+impl From<ModeV1> for Modes {
+    fn from(m: ModeV1) -> Self {
+        match m {
+            ModeV1::Picture => Modes::Picture,
+            ModeV1::SlidShow => Modes::SlidShow,
+        }
+    }
+}
+
+// the index was written, not the discriminant the live `Fits` gives each variant
+#[derive(Deserialize)]
+enum FitV1 {
+    Fill,
+    Fit,
+    Stretch,
+    Tile,
+    Center,
+    Span,
+}
+
+impl From<FitV1> for Fits {
+    fn from(f: FitV1) -> Self {
+        match f {
+            FitV1::Fill => Fits::Fill,
+            FitV1::Fit => Fits::Fit,
+            FitV1::Stretch => Fits::Stretch,
+            FitV1::Tile => Fits::Tile,
+            FitV1::Center => Fits::Center,
+            FitV1::Span => Fits::Span,
+        }
+    }
+}
+
+// The listed album went along with the settings. Only the choices are kept, the
+// pictures are listed again when the settings are applied.
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct ImageV1 {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct SelectorV1 {
+    path: PathBuf,
+    shuffle: bool,
+    ratio_value: f32,
+    ratio: bool,
+    ratio_range: f32,
+    ratio_range_ui: f32,
+    pictures: Option<Vec<ImageV1>>,
+    ratio_pool: Vec<usize>,
+    wallpaper_index: usize,
+}
+
+#[derive(Deserialize)]
+struct FingerprintV1 {
+    manufacturer: String,
+    product_code: u16,
+    serial: u32,
+    serial_text: String,
+    model: String,
+    width_mm: u32,
+    height_mm: u32,
+}
+
+impl From<FingerprintV1> for MonitorFingerprint {
+    fn from(f: FingerprintV1) -> Self {
+        MonitorFingerprint {
+            manufacturer: f.manufacturer,
+            product_code: f.product_code,
+            serial: f.serial,
+            serial_text: f.serial_text,
+            model: f.model,
+            width_mm: f.width_mm,
+            height_mm: f.height_mm,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BezelV1 {
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+}
+
+impl From<BezelV1> for Bezel {
+    fn from(b: BezelV1) -> Self {
+        Bezel {
+            left: b.left,
+            top: b.top,
+            right: b.right,
+            bottom: b.bottom,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum ScheduleV1 {
+    Every { seconds: u64, jitter: u64 },
+    Cron { expression: String, jitter: u64 },
+}
+
+impl From<ScheduleV1> for SlideSchedule {
+    fn from(s: ScheduleV1) -> Self {
+        match s {
+            ScheduleV1::Every { seconds, jitter } => SlideSchedule::Every { seconds, jitter },
+            ScheduleV1::Cron { expression, jitter } => SlideSchedule::Cron { expression, jitter },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum SolarEventV1 {
+    Dawn,
+    Sunrise,
+    Sunset,
+    Dusk,
+}
+
+#[derive(Deserialize)]
+struct TimeAlbumV1 {
+    event: SolarEventV1,
+    album_path: PathBuf,
+}
+
+impl From<TimeAlbumV1> for TimeAlbum {
+    fn from(a: TimeAlbumV1) -> Self {
+        let event = match a.event {
+            SolarEventV1::Dawn => SolarEvent::Dawn,
+            SolarEventV1::Sunrise => SolarEvent::Sunrise,
+            SolarEventV1::Sunset => SolarEvent::Sunset,
+            SolarEventV1::Dusk => SolarEvent::Dusk,
+        };
+        TimeAlbum {
+            event,
+            album_path: a.album_path,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LocationV1 {
+    latitude: f64,
+    longitude: f64,
+}
+
+impl From<LocationV1> for Location {
+    fn from(l: LocationV1) -> Self {
+        Location {
+            latitude: l.latitude,
+            longitude: l.longitude,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum GroupModeV1 {
+    Synchronized,
+    Distributed,
+}
+
+#[derive(Deserialize)]
+struct GroupV1 {
+    name: String,
+    members: Vec<String>,
+    mode: GroupModeV1,
+    album_path: PathBuf,
+    slide_interval: ScheduleV1,
+    slide_time: u64,
+    cursor: usize,
+    // listed again once the group is in use
+    #[allow(dead_code)]
+    selector: SelectorV1,
+}
+
+impl From<GroupV1> for MonitorGroup {
+    fn from(g: GroupV1) -> Self {
+        let mode = match g.mode {
+            GroupModeV1::Synchronized => GroupMode::Synchronized,
+            GroupModeV1::Distributed => GroupMode::Distributed,
+        };
+        MonitorGroup::from(GroupConfig {
+            name: g.name,
+            members: g.members,
+            mode,
+            album_path: g.album_path,
+            slide_interval: g.slide_interval.into(),
+            slide_time: g.slide_time,
+            cursor: g.cursor,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+enum ProfileRuleV1 {
+    Exact,
+    Subset,
+    Count(usize),
+}
+
+impl From<ProfileRuleV1> for ProfileRule {
+    fn from(r: ProfileRuleV1) -> Self {
+        match r {
+            ProfileRuleV1::Exact => ProfileRule::Exact,
+            ProfileRuleV1::Subset => ProfileRule::Subset,
+            ProfileRuleV1::Count(n) => ProfileRule::Count(n),
+        }
+    }
+}
+
+// Monitor settings
+// 1 baseline
+// 2 EDID fingerprint and nickname
+// 3 bezel
+// 4 slide interval became a `SlideSchedule`
+// 5 time of day albums
+
+// The fixed slide intervals up to version 3.
+#[derive(Deserialize)]
+enum Interval {
+    OneMinute,
+    TenMinutes,
+    ThirtyMinutes,
+    OneHour,
+    SixHours,
+}
+
+impl Interval {
+    fn seconds(&self) -> u64 {
+        match self {
+            Interval::OneMinute => 60,
+            Interval::TenMinutes => 600,
+            Interval::ThirtyMinutes => 60 * 30,
+            Interval::OneHour => 60 * 60,
+            Interval::SixHours => 60 * 60 * 6,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MonitorSettingsV1 {
+    device_id: String,
+    mode: ModeV1,
+    fit: FitV1,
+    image: Option<PathBuf>,
+    album_path: Option<PathBuf>,
+    recent_images: Vec<PathBuf>,
+    slide_interval: Interval,
+    slide_time: u64,
+    selector: SelectorV1,
+}
+
+#[derive(Deserialize)]
+struct MonitorSettingsV2 {
+    device_id: String,
+    mode: ModeV1,
+    fit: FitV1,
+    image: Option<PathBuf>,
+    album_path: Option<PathBuf>,
+    recent_images: Vec<PathBuf>,
+    slide_interval: Interval,
+    slide_time: u64,
+    selector: SelectorV1,
+    fingerprint: Option<FingerprintV1>,
+    nickname: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MonitorSettingsV3 {
+    device_id: String,
+    mode: ModeV1,
+    fit: FitV1,
+    image: Option<PathBuf>,
+    album_path: Option<PathBuf>,
+    recent_images: Vec<PathBuf>,
+    slide_interval: Interval,
+    slide_time: u64,
+    selector: SelectorV1,
+    fingerprint: Option<FingerprintV1>,
+    nickname: Option<String>,
+    bezel: BezelV1,
+}
+
+#[derive(Deserialize)]
+struct MonitorSettingsV4 {
+    device_id: String,
+    mode: ModeV1,
+    fit: FitV1,
+    image: Option<PathBuf>,
+    album_path: Option<PathBuf>,
+    recent_images: Vec<PathBuf>,
+    slide_interval: ScheduleV1,
+    slide_time: u64,
+    selector: SelectorV1,
+    fingerprint: Option<FingerprintV1>,
+    nickname: Option<String>,
+    bezel: BezelV1,
+}
+
+#[derive(Deserialize)]
+struct MonitorSettingsV5 {
+    device_id: String,
+    mode: ModeV1,
+    fit: FitV1,
+    image: Option<PathBuf>,
+    album_path: Option<PathBuf>,
+    recent_images: Vec<PathBuf>,
+    slide_interval: ScheduleV1,
+    slide_time: u64,
+    selector: SelectorV1,
+    fingerprint: Option<FingerprintV1>,
+    nickname: Option<String>,
+    bezel: BezelV1,
+    time_albums: Vec<TimeAlbumV1>,
+}
+
+fn monitor_v1(s: MonitorSettingsV1) -> OsicMonitorSettings {
+    monitor_v2(MonitorSettingsV2 {
+        device_id: s.device_id,
+        mode: s.mode,
+        fit: s.fit,
+        image: s.image,
+        album_path: s.album_path,
+        recent_images: s.recent_images,
+        slide_interval: s.slide_interval,
+        slide_time: s.slide_time,
+        selector: s.selector,
+        fingerprint: None,
+        nickname: None,
+    })
+}
+
+fn monitor_v2(s: MonitorSettingsV2) -> OsicMonitorSettings {
+    monitor_v3(MonitorSettingsV3 {
+        device_id: s.device_id,
+        mode: s.mode,
+        fit: s.fit,
+        image: s.image,
+        album_path: s.album_path,
+        recent_images: s.recent_images,
+        slide_interval: s.slide_interval,
+        slide_time: s.slide_time,
+        selector: s.selector,
+        fingerprint: s.fingerprint,
+        nickname: s.nickname,
+        bezel: BezelV1 {
+            left: 0.0,
+            top: 0.0,
+            right: 0.0,
+            bottom: 0.0,
+        },
+    })
+}
+
+fn monitor_v3(s: MonitorSettingsV3) -> OsicMonitorSettings {
+    monitor_v4(MonitorSettingsV4 {
+        device_id: s.device_id,
+        mode: s.mode,
+        fit: s.fit,
+        image: s.image,
+        album_path: s.album_path,
+        recent_images: s.recent_images,
+        slide_interval: ScheduleV1::Every {
+            seconds: s.slide_interval.seconds(),
+            jitter: 0,
+        },
+        slide_time: s.slide_time,
+        selector: s.selector,
+        fingerprint: s.fingerprint,
+        nickname: s.nickname,
+        bezel: s.bezel,
+    })
+}
+
+fn monitor_v4(s: MonitorSettingsV4) -> OsicMonitorSettings {
+    monitor_v5(MonitorSettingsV5 {
+        device_id: s.device_id,
+        mode: s.mode,
+        fit: s.fit,
+        image: s.image,
+        album_path: s.album_path,
+        recent_images: s.recent_images,
+        slide_interval: s.slide_interval,
+        slide_time: s.slide_time,
+        selector: s.selector,
+        fingerprint: s.fingerprint,
+        nickname: s.nickname,
+        bezel: s.bezel,
+        time_albums: Vec::new(),
+    })
+}
+
+fn monitor_v5(s: MonitorSettingsV5) -> OsicMonitorSettings {
+    OsicMonitorSettings::from(MonitorConfig {
+        device_id: s.device_id,
+        nickname: s.nickname,
+        mode: s.mode.into(),
+        fit: s.fit.into(),
+        image: s.image,
+        album_path: s.album_path,
+        slide_interval: s.slide_interval.into(),
+        slide_time: s.slide_time,
+        shuffle: s.selector.shuffle,
+        ratio: s.selector.ratio,
+        ratio_range: s.selector.ratio_range,
+        bezel: s.bezel.into(),
+        time_albums: s.time_albums.into_iter().map(TimeAlbum::from).collect(),
+        recent_images: s.recent_images,
+        fingerprint: s.fingerprint.map(MonitorFingerprint::from),
+    })
+}
+
+fn monitor_version(version: u16, payload: &[u8]) -> Option<OsicMonitorSettings> {
+    match version {
+        1 => read(payload).map(monitor_v1),
+        2 => read(payload).map(monitor_v2),
+        3 => read(payload).map(monitor_v3),
+        4 => read(payload).map(monitor_v4),
+        5 => read(payload).map(monitor_v5),
+        _ => None,
+    }
+}
+
+pub fn decode_monitor_settings(bytes: &[u8]) -> Option<OsicMonitorSettings> {
+    match open(MONITOR_MAGIC, bytes) {
+        (0, payload) => (1..=MONITOR_SETTINGS_VERSION)
+            .rev()
+            .find_map(|v| monitor_version(v, payload)),
+        (v, _) if v > MONITOR_SETTINGS_VERSION => {
+            newer("Monitor settings", v);
+            None
+        }
+        (v, payload) => monitor_version(v, payload),
+    }
+}
+
+// App settings
+// 1 location
+// 2 monitor groups
+// 3 active profile
+
+#[derive(Deserialize)]
+struct AppSettingsV1 {
+    location: Option<LocationV1>,
+}
+
+#[derive(Deserialize)]
+struct AppSettingsV2 {
+    location: Option<LocationV1>,
+    groups: Vec<GroupV1>,
+}
+
+#[derive(Deserialize)]
+struct AppSettingsV3 {
+    location: Option<LocationV1>,
+    groups: Vec<GroupV1>,
+    active_profile: Option<String>,
+}
+
+fn app_v1(s: AppSettingsV1) -> OsicAppSettings {
+    app_v2(AppSettingsV2 {
+        location: s.location,
+        groups: Vec::new(),
+    })
+}
+
+fn app_v2(s: AppSettingsV2) -> OsicAppSettings {
+    app_v3(AppSettingsV3 {
+        location: s.location,
+        groups: s.groups,
+        active_profile: None,
+    })
+}
+
+fn app_v3(s: AppSettingsV3) -> OsicAppSettings {
+    OsicAppSettings {
+        location: s.location.map(Location::from),
+        groups: s.groups.into_iter().map(MonitorGroup::from).collect(),
+        active_profile: s.active_profile,
+    }
+}
+
+fn app_version(version: u16, payload: &[u8]) -> Option<OsicAppSettings> {
+    match version {
+        1 => read(payload).map(app_v1),
+        2 => read(payload).map(app_v2),
+        3 => read(payload).map(app_v3),
+        _ => None,
+    }
+}
+
+pub fn decode_app_settings(bytes: &[u8]) -> Option<OsicAppSettings> {
+    match open(APP_MAGIC, bytes) {
+        (0, payload) => (1..=APP_SETTINGS_VERSION)
+            .rev()
+            .find_map(|v| app_version(v, payload)),
+        (v, _) if v > APP_SETTINGS_VERSION => {
+            newer("App settings", v);
+            None
+        }
+        (v, payload) => app_version(v, payload),
+    }
+}
+
+// Profiles
+// 1 name, monitors and groups
+// 2 topology rule
+
+#[derive(Deserialize)]
+struct ProfileV1 {
+    name: String,
+    monitors: Vec<MonitorSettingsV5>,
+    groups: Vec<GroupV1>,
+}
+
+#[derive(Deserialize)]
+struct ProfileV2 {
+    name: String,
+    monitors: Vec<MonitorSettingsV5>,
+    groups: Vec<GroupV1>,
+    rule: Option<ProfileRuleV1>,
+}
+
+fn profile_v1(p: ProfileV1) -> OsicProfile {
+    profile_v2(ProfileV2 {
+        name: p.name,
+        monitors: p.monitors,
+        groups: p.groups,
+        rule: None,
+    })
+}
+
+fn profile_v2(p: ProfileV2) -> OsicProfile {
+    OsicProfile {
+        name: p.name,
+        monitors: p.monitors.into_iter().map(monitor_v5).collect(),
+        groups: p.groups.into_iter().map(MonitorGroup::from).collect(),
+        rule: p.rule.map(ProfileRule::from),
+    }
+}
+
+fn profiles_version(version: u16, payload: &[u8]) -> Option<Vec<OsicProfile>> {
+    match version {
+        1 => read::<Vec<ProfileV1>>(payload).map(|ps| ps.into_iter().map(profile_v1).collect()),
+        2 => read::<Vec<ProfileV2>>(payload).map(|ps| ps.into_iter().map(profile_v2).collect()),
+        _ => None,
+    }
+}
+
+pub fn decode_profiles(bytes: &[u8]) -> Option<Vec<OsicProfile>> {
+    match open(PROFILES_MAGIC, bytes) {
+        (0, payload) => (1..=PROFILES_VERSION)
+            .rev()
+            .find_map(|v| profiles_version(v, payload)),
+        (v, _) if v > PROFILES_VERSION => {
+            newer("Profiles", v);
+            None
+        }
+        (v, payload) => profiles_version(v, payload),
+    }
+}
+
+// Everything the temp folder still holds, for the first start with a config file.
+// Files that don't decode are skipped.
+pub fn temp_folder_config() -> OsicConfig {
+    let mut config = OsicConfig::default();
+    let entries = match fs::read_dir(cache::os_temp_folder()) {
+        Ok(entries) => entries,
+        Err(_) => return config,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(_) => continue,
+        };
+        match path.file_name().and_then(|n| n.to_str()) {
+            Some("app") => {
+                if let Some(s) = decode_app_settings(&bytes) {
+                    config.set_app_settings(&s);
+                }
+            }
+            Some("profiles") => {
+                if let Some(profiles) = decode_profiles(&bytes) {
+                    config.set_profiles(&profiles);
+                }
+            }
+            _ => {
+                if let Some(s) = decode_monitor_settings(&bytes) {
+                    config.set_monitor_settings(s);
+                }
+            }
+        }
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::ProfileConfig;
+
+    // Files as the released versions wrote them, see testdata/migrate. Up to
+    // version 5 of the monitor settings the payload went out bare, the last of
+    // each kind was also written behind the header.
+    const MONITOR_FILES: [&[u8]; 5] = [
+        include_bytes!("testdata/migrate/monitor-v1.bin"),
+        include_bytes!("testdata/migrate/monitor-v2.bin"),
+        include_bytes!("testdata/migrate/monitor-v3.bin"),
+        include_bytes!("testdata/migrate/monitor-v4.bin"),
+        include_bytes!("testdata/migrate/monitor-v5.bin"),
+    ];
+    const MONITOR_HEADED: &[u8] = include_bytes!("testdata/migrate/monitor-v5-header.bin");
+    const APP_FILES: [&[u8]; 3] = [
+        include_bytes!("testdata/migrate/app-v1.bin"),
+        include_bytes!("testdata/migrate/app-v2.bin"),
+        include_bytes!("testdata/migrate/app-v3.bin"),
+    ];
+    const APP_HEADED: &[u8] = include_bytes!("testdata/migrate/app-v3-header.bin");
+    const PROFILES_FILES: [&[u8]; 2] = [
+        include_bytes!("testdata/migrate/profiles-v1.bin"),
+        include_bytes!("testdata/migrate/profiles-v2.bin"),
+    ];
+    const PROFILES_HEADED: &[u8] = include_bytes!("testdata/migrate/profiles-v2-header.bin");
+
+    fn headed(magic: &[u8; 4], version: u16, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    // What the fixtures hold, in the newest layout.
+    fn monitor() -> MonitorConfig {
+        MonitorConfig {
+            device_id: "DP-1".to_string(),
+            nickname: Some("Left".to_string()),
+            mode: Modes::SlidShow,
+            fit: Fits::Center,
+            image: Some(PathBuf::from("/pics/a.jpg")),
+            album_path: Some(PathBuf::from("/pics")),
+            slide_interval: SlideSchedule::Every {
+                seconds: 3600,
+                jitter: 30,
+            },
+            slide_time: 1_700_000_000,
+            shuffle: true,
+            ratio: true,
+            ratio_range: 0.2,
+            bezel: Bezel {
+                left: 5.0,
+                top: 6.0,
+                right: 7.0,
+                bottom: 8.0,
+            },
+            time_albums: vec![
+                TimeAlbum {
+                    event: SolarEvent::Sunset,
+                    album_path: PathBuf::from("/pics/night"),
+                },
+                TimeAlbum {
+                    event: SolarEvent::Dawn,
+                    album_path: PathBuf::from("/pics/day"),
+                },
+            ],
+            recent_images: vec![PathBuf::from("/pics/b.jpg"), PathBuf::from("/pics/a.jpg")],
+            fingerprint: Some(MonitorFingerprint {
+                manufacturer: "DEL".to_string(),
+                product_code: 0xa0c4,
+                serial: 0x4c4b4b30,
+                serial_text: "9QKPN13".to_string(),
+                model: "DELL U2720Q".to_string(),
+                width_mm: 600,
+                height_mm: 340,
+            }),
+        }
+    }
+
+    // Each version keeps what it knew of `monitor()`, the rest comes back as default.
+    fn monitor_up_to(version: u16) -> MonitorConfig {
+        let mut c = monitor();
+        if version < 5 {
+            c.time_albums = Vec::new();
+        }
+        if version < 4 {
+            c.slide_interval = SlideSchedule::Every {
+                seconds: 3600,
+                jitter: 0,
+            };
+        }
+        if version < 3 {
+            c.bezel = Bezel::default();
+        }
+        if version < 2 {
+            c.fingerprint = None;
+            c.nickname = None;
+        }
+        c
+    }
+
+    #[test]
+    fn every_monitor_settings_version_decodes() {
+        for (version, bytes) in (1..).zip(MONITOR_FILES) {
+            let decoded = decode_monitor_settings(bytes).expect("without header");
+            assert_eq!(
+                MonitorConfig::from(decoded),
+                monitor_up_to(version),
+                "version {}",
+                version
+            );
+            // the album is listed again, from the saved choices
+            let selector = decode_monitor_settings(bytes).unwrap().selector;
+            assert_eq!(selector.path, PathBuf::from("/pics"));
+            assert!(selector.shuffle && selector.ratio);
+        }
+
+        let decoded = decode_monitor_settings(MONITOR_HEADED).expect("with header");
+        assert_eq!(MonitorConfig::from(decoded), monitor());
+    }
+
+    fn group() -> GroupConfig {
+        GroupConfig {
+            name: "Wall".to_string(),
+            members: vec!["DP-1".to_string(), "DP-2".to_string()],
+            mode: GroupMode::Distributed,
+            album_path: PathBuf::from("/pics/wall"),
+            slide_interval: SlideSchedule::Cron {
+                expression: "every weekday at 09:00".to_string(),
+                jitter: 60,
+            },
+            slide_time: 1_700_000_600,
+            cursor: 1,
+        }
+    }
+
+    #[test]
+    fn every_app_settings_version_decodes() {
+        let berlin = Location {
+            latitude: 52.52,
+            longitude: 13.405,
+        };
+        for (version, bytes) in (1..).zip(APP_FILES) {
+            let decoded = decode_app_settings(bytes).expect("app settings");
+            assert_eq!(decoded.location, Some(berlin), "version {}", version);
+            let groups: Vec<GroupConfig> =
+                decoded.groups.into_iter().map(GroupConfig::from).collect();
+            let expected = if version < 2 { vec![] } else { vec![group()] };
+            assert_eq!(groups, expected, "version {}", version);
+            let expected = (version == 3).then(|| "Work".to_string());
+            assert_eq!(decoded.active_profile, expected, "version {}", version);
+        }
+
+        let decoded = decode_app_settings(APP_HEADED).expect("with header");
+        assert_eq!(decoded.location, Some(berlin));
+        assert_eq!(decoded.groups.len(), 1);
+        assert_eq!(decoded.groups[0].selector.path, PathBuf::from("/pics/wall"));
+        assert_eq!(decoded.active_profile.as_deref(), Some("Work"));
+    }
+
+    #[test]
+    fn every_profiles_version_decodes() {
+        let work = ProfileConfig {
+            name: "Work".to_string(),
+            rule: Some(ProfileRule::Count(2)),
+            monitors: vec![monitor()],
+            groups: vec![group()],
+        };
+        let home = ProfileConfig {
+            name: "Home".to_string(),
+            rule: Some(ProfileRule::Subset),
+            monitors: Vec::new(),
+            groups: Vec::new(),
+        };
+        let configs = |profiles: Vec<OsicProfile>| -> Vec<ProfileConfig> {
+            profiles.into_iter().map(ProfileConfig::from).collect()
+        };
+
+        let decoded = decode_profiles(PROFILES_FILES[0]).expect("profiles version 1");
+        let v1 = ProfileConfig {
+            rule: None,
+            ..work.clone()
+        };
+        assert_eq!(configs(decoded), vec![v1]);
+
+        for bytes in [PROFILES_FILES[1], PROFILES_HEADED] {
+            let decoded = decode_profiles(bytes).expect("profiles version 2");
+            assert_eq!(configs(decoded), vec![work.clone(), home.clone()]);
+        }
+    }
+
+    #[test]
+    fn garbage_decodes_to_nothing() {
+        let garbage: [&[u8]; 3] = [b"", b"not settings at all", &[0xff; 64]];
+        for bytes in garbage {
+            assert!(decode_monitor_settings(bytes).is_none());
+            assert!(decode_app_settings(bytes).is_none());
+            assert!(decode_profiles(bytes).is_none());
+        }
+
+        // a byte too many or too few
+        let mut long = MONITOR_FILES[4].to_vec();
+        long.push(0);
+        assert!(decode_monitor_settings(&long).is_none());
+        let short = MONITOR_FILES[4];
+        assert!(decode_monitor_settings(&short[..short.len() - 1]).is_none());
+
+        // the header names a version the payload isn't, or one still to come
+        assert!(decode_monitor_settings(&headed(MONITOR_MAGIC, 5, MONITOR_FILES[2])).is_none());
+        assert!(decode_app_settings(&headed(APP_MAGIC, 9, APP_FILES[2])).is_none());
+        assert!(decode_profiles(&headed(PROFILES_MAGIC, 0xffff, &[0; 8])).is_none());
+    }
+}
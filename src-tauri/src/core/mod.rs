@@ -2,7 +2,35 @@
 
 mod utils;
 mod cache;
+mod config;
+mod backend;
+mod bundle;
+mod command;
+mod edid;
+mod fake;
+mod gnome;
+mod group;
+mod migrate;
+#[cfg(unix)]
+mod hyprpaper;
+#[cfg(unix)]
+mod kde;
+#[cfg(unix)]
+mod sway;
+pub mod profile;
+mod render;
+mod solar;
+mod span;
+mod store;
+mod sync;
+#[cfg(windows)]
 mod win32;
+#[cfg(unix)]
+mod x11;
 pub mod display;
+mod schedule;
+mod scheduler;
 mod selector;
-
+mod watcher;
+#[cfg(test)]
+mod testutil;
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+
+use super::cache::OsicMonitorSettings;
+use super::display::Monitor;
+use super::edid;
+use super::group::MonitorGroup;
+
+// Everything the monitors show, saved under a name like "Work" or "Gaming".
+// Monitors are matched like restored settings, by EDID first, so a profile follows
+// a monitor to another port.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsicProfile {
+    pub name: String,
+    pub monitors: Vec<OsicMonitorSettings>,
+    pub groups: Vec<MonitorGroup>,
+    // when to switch to the profile on its own, None only switches by hand
+    pub rule: Option<ProfileRule>,
+}
+
+// Which monitor setups a profile is picked for. The profile's own monitors are the
+// ones it was saved with.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProfileRule {
+    // exactly the profile's monitors are connected
+    Exact,
+    // all of the profile's monitors are connected, others may be too
+    Subset,
+    // this many monitors are connected, whichever they are
+    Count(usize),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileSummary {
+    pub name: String,
+    pub active: bool,
+    pub monitors: usize,
+    pub rule: Option<ProfileRule>,
+}
+
+pub fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("A profile needs a name".to_string());
+    }
+    if name.len() > 64 {
+        return Err(format!("Profile name is too long: {}", name));
+    }
+    Ok(name.to_string())
+}
+
+// How well the profile fits the connected monitors, higher is better. None when
+// its rule doesn't hold. An exact match beats a subset, a subset with more monitors
+// beats a smaller one, and a count is the last resort.
+fn score(profile: &OsicProfile, monitors: &[Monitor]) -> Option<(u8, usize)> {
+    let mut matched = Vec::new();
+    for m in monitors {
+        if let Some(s) = edid::match_settings(m, &profile.monitors) {
+            let i = profile
+                .monitors
+                .iter()
+                .position(|p| std::ptr::eq(p, s))
+                .unwrap();
+            if !matched.contains(&i) {
+                matched.push(i);
+            }
+        }
+    }
+    let all_present = !profile.monitors.is_empty() && matched.len() == profile.monitors.len();
+
+    match profile.rule? {
+        ProfileRule::Exact if all_present && monitors.len() == matched.len() => {
+            Some((3, matched.len()))
+        }
+        ProfileRule::Subset if all_present => Some((2, matched.len())),
+        ProfileRule::Count(n) if n == monitors.len() => Some((1, n)),
+        _ => None,
+    }
+}
+
+// The profile that belongs to the connected monitors. Among equally good ones the
+// active profile stays, otherwise the first saved one wins.
+pub fn pick<'a>(
+    profiles: &'a [OsicProfile],
+    monitors: &[Monitor],
+    active: Option<&str>,
+) -> Option<&'a OsicProfile> {
+    let mut best: Option<(&OsicProfile, (u8, usize))> = None;
+    for p in profiles {
+        let s = match score(p, monitors) {
+            Some(s) => s,
+            None => continue,
+        };
+        let better = match best {
+            None => true,
+            Some((_, b)) => s > b || (s == b && active == Some(p.name.as_str())),
+        };
+        if better {
+            best = Some((p, s));
+        }
+    }
+    best.map(|(p, _)| p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::display::{Fits, Modes};
+    use crate::core::edid::MonitorFingerprint;
+    use crate::core::schedule::SlideSchedule;
+    use crate::core::selector::OsicSlideSelector;
+    use crate::core::span::Bezel;
+
+    fn fingerprint(serial: u32) -> Option<MonitorFingerprint> {
+        Some(MonitorFingerprint {
+            manufacturer: "DEL".to_string(),
+            product_code: 0xa0b1,
+            serial,
+            model: "DELL U2720Q".to_string(),
+            ..Default::default()
+        })
+    }
+
+    // Monitors A, B, C... are separate units of the same model on their own ports.
+    fn monitor(unit: char) -> Monitor {
+        Monitor {
+            device_id: format!("DP-{}", unit),
+            fingerprint: fingerprint(unit as u32),
+            ..Default::default()
+        }
+    }
+
+    fn profile(name: &str, units: &str, rule: Option<ProfileRule>) -> OsicProfile {
+        let monitors = units
+            .chars()
+            .map(|unit| OsicMonitorSettings {
+                device_id: format!("DP-{}", unit),
+                mode: Modes::Picture,
+                fit: Fits::Fill,
+                image: None,
+                album_path: None,
+                recent_images: Vec::new(),
+                slide_interval: SlideSchedule::default(),
+                slide_time: 0,
+                selector: OsicSlideSelector::default(),
+                fingerprint: fingerprint(unit as u32),
+                nickname: None,
+                bezel: Bezel::default(),
+                time_albums: Vec::new(),
+            })
+            .collect();
+        OsicProfile {
+            name: name.to_string(),
+            monitors,
+            groups: Vec::new(),
+            rule,
+        }
+    }
+
+    fn picked(profiles: &[OsicProfile], units: &str, active: Option<&str>) -> Option<String> {
+        let monitors = units.chars().map(monitor).collect::<Vec<Monitor>>();
+        pick(profiles, &monitors, active).map(|p| p.name.clone())
+    }
+
+    #[test]
+    fn exact_needs_the_same_monitors_and_no_others() {
+        let profiles = [profile("Desk", "AB", Some(ProfileRule::Exact))];
+        assert_eq!(picked(&profiles, "BA", None), Some("Desk".to_string()));
+        assert_eq!(picked(&profiles, "ABC", None), None);
+        assert_eq!(picked(&profiles, "A", None), None);
+    }
+
+    #[test]
+    fn subset_allows_more_monitors() {
+        let profiles = [profile("Laptop", "A", Some(ProfileRule::Subset))];
+        assert_eq!(picked(&profiles, "A", None), Some("Laptop".to_string()));
+        assert_eq!(picked(&profiles, "CA", None), Some("Laptop".to_string()));
+        assert_eq!(picked(&profiles, "C", None), None);
+    }
+
+    #[test]
+    fn count_takes_any_monitors() {
+        let profiles = [profile("Two", "AB", Some(ProfileRule::Count(2)))];
+        assert_eq!(picked(&profiles, "CD", None), Some("Two".to_string()));
+        assert_eq!(picked(&profiles, "ABC", None), None);
+    }
+
+    #[test]
+    fn better_rule_wins() {
+        let profiles = [
+            profile("Count", "AB", Some(ProfileRule::Count(2))),
+            profile("Small", "A", Some(ProfileRule::Subset)),
+            profile("Large", "AB", Some(ProfileRule::Subset)),
+            profile("Exact", "AB", Some(ProfileRule::Exact)),
+        ];
+        assert_eq!(picked(&profiles, "AB", None), Some("Exact".to_string()));
+        assert_eq!(picked(&profiles, "ABC", None), Some("Large".to_string()));
+        assert_eq!(picked(&profiles, "AC", None), Some("Small".to_string()));
+        assert_eq!(picked(&profiles, "CD", None), Some("Count".to_string()));
+        // a worse match doesn't stay because it is active
+        assert_eq!(
+            picked(&profiles, "AB", Some("Large")),
+            Some("Exact".to_string())
+        );
+    }
+
+    #[test]
+    fn ties_keep_the_active_profile_or_the_first() {
+        let profiles = [
+            profile("Work", "A", Some(ProfileRule::Subset)),
+            profile("Gaming", "A", Some(ProfileRule::Subset)),
+        ];
+        assert_eq!(picked(&profiles, "A", None), Some("Work".to_string()));
+        assert_eq!(
+            picked(&profiles, "A", Some("Gaming")),
+            Some("Gaming".to_string())
+        );
+        assert_eq!(
+            picked(&profiles, "A", Some("Other")),
+            Some("Work".to_string())
+        );
+    }
+
+    #[test]
+    fn nothing_matches() {
+        assert_eq!(picked(&[], "A", None), None);
+        // no rule only switches by hand, an empty profile has nothing to be a subset of
+        let profiles = [
+            profile("Manual", "A", None),
+            profile("Empty", "", Some(ProfileRule::Subset)),
+            profile("Three", "ABC", Some(ProfileRule::Count(3))),
+        ];
+        assert_eq!(picked(&profiles, "A", None), None);
+    }
+}
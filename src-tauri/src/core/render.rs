@@ -0,0 +1,100 @@
+use image::{imageops, imageops::FilterType, DynamicImage, Rgba, RgbaImage};
+use std::path::PathBuf;
+
+use super::display::{Fits, Monitor};
+
+pub fn rgba(color: u32) -> Rgba<u8> {
+    Rgba([(color >> 16) as u8, (color >> 8) as u8, color as u8, 255])
+}
+
+// Render an image the way the desktop would show it on a width x height screen.
+pub fn render_fit(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    fit: &Fits,
+    background: Rgba<u8>,
+) -> RgbaImage {
+    let mut canvas = RgbaImage::from_pixel(width, height, background);
+    match fit {
+        Fits::Stretch => {
+            return img
+                .resize_exact(width, height, FilterType::Triangle)
+                .to_rgba8()
+        }
+        Fits::Fill | Fits::Span => {
+            return img
+                .resize_to_fill(width, height, FilterType::Triangle)
+                .to_rgba8()
+        }
+        Fits::Fit => {
+            let r = img.resize(width, height, FilterType::Triangle).to_rgba8();
+            let x = (width as i64 - r.width() as i64) / 2;
+            let y = (height as i64 - r.height() as i64) / 2;
+            imageops::overlay(&mut canvas, &r, x, y);
+        }
+        Fits::Center => {
+            let x = (width as i64 - img.width() as i64) / 2;
+            let y = (height as i64 - img.height() as i64) / 2;
+            imageops::overlay(&mut canvas, &img.to_rgba8(), x, y);
+        }
+        Fits::Tile => {
+            let tile = img.to_rgba8();
+            if tile.width() == 0 || tile.height() == 0 {
+                return canvas;
+            }
+            for y in (0..height).step_by(tile.height() as usize) {
+                for x in (0..width).step_by(tile.width() as usize) {
+                    imageops::overlay(&mut canvas, &tile, x as i64, y as i64);
+                }
+            }
+        }
+    }
+    canvas
+}
+
+// left, top, width, height of the virtual desktop covering every monitor
+pub fn desktop_bounds(monitors: &[Monitor]) -> (i32, i32, i32, i32) {
+    if monitors.is_empty() {
+        return (0, 0, 0, 0);
+    }
+    let left = monitors.iter().map(|m| m.letf).min().unwrap();
+    let top = monitors.iter().map(|m| m.top).min().unwrap();
+    let right = monitors.iter().map(|m| m.right).max().unwrap();
+    let bottom = monitors.iter().map(|m| m.bottom).max().unwrap();
+    (left, top, right - left, bottom - top)
+}
+
+// Paint every monitor's wallpaper into one image covering the whole virtual desktop.
+// Monitors without a layer keep the background color.
+pub fn compose_desktop(
+    monitors: &[Monitor],
+    layers: &[(String, PathBuf, Fits)],
+    background: Rgba<u8>,
+) -> Result<RgbaImage, String> {
+    let (left, top, width, height) = desktop_bounds(monitors);
+    let mut canvas = RgbaImage::from_pixel(width as u32, height as u32, background);
+    compose_into(&mut canvas, left, top, monitors, layers, background)?;
+    Ok(canvas)
+}
+
+// Same as compose_desktop, on a canvas whose top left corner sits at (left, top)
+// in desktop coordinates.
+pub fn compose_into(
+    canvas: &mut RgbaImage,
+    left: i32,
+    top: i32,
+    monitors: &[Monitor],
+    layers: &[(String, PathBuf, Fits)],
+    background: Rgba<u8>,
+) -> Result<(), String> {
+    for (device_id, path, fit) in layers {
+        if let Some(m) = monitors.iter().find(|m| &m.device_id == device_id) {
+            let img = image::open(path)
+                .map_err(|e| format!("Open image {} Error: {}", path.display(), e))?;
+            let part = render_fit(&img, m.width as u32, m.height as u32, fit, background);
+            imageops::replace(canvas, &part, (m.letf - left) as i64, (m.top - top) as i64);
+        }
+    }
+    Ok(())
+}
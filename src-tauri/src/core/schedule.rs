@@ -0,0 +1,587 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+
+// When a slideshow moves on. Jitter is in seconds and moves every change randomly
+// by up to that much either way.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SlideSchedule {
+    Every { seconds: u64, jitter: u64 },
+    // cron line or a phrase like "every weekday at 09:00", see `Cron::parse`
+    Cron { expression: String, jitter: u64 },
+}
+
+impl Default for SlideSchedule {
+    fn default() -> Self {
+        SlideSchedule::Every {
+            seconds: 600,
+            jitter: 0,
+        }
+    }
+}
+
+impl SlideSchedule {
+    // A duration ("90s", "2h30m", "10 minutes") or a cron schedule.
+    pub fn parse(text: &str, jitter: u64) -> Result<Self, String> {
+        if let Ok(seconds) = parse_duration(text) {
+            if seconds == 0 {
+                return Err("Interval must be longer than 0s".to_string());
+            }
+            return Ok(SlideSchedule::Every { seconds, jitter });
+        }
+        match Cron::parse(text) {
+            Ok(_) => Ok(SlideSchedule::Cron {
+                expression: text.trim().to_string(),
+                jitter,
+            }),
+            Err(e) => Err(format!(
+                "\"{}\" is neither a duration like 2h30m nor a schedule: {}",
+                text.trim(),
+                e
+            )),
+        }
+    }
+
+    // Next change after a change at `from`, unix seconds. The jitter is picked from
+    // `from`, so the same slide always gets the same deadline.
+    pub fn next_after(&self, from: u64) -> Option<u64> {
+        let (next, jitter) = match self {
+            SlideSchedule::Every { seconds, jitter } => (from + seconds, *jitter),
+            SlideSchedule::Cron { expression, jitter } => {
+                (Cron::parse(expression).ok()?.next_after(from)?, *jitter)
+            }
+        };
+        if jitter == 0 {
+            return Some(next);
+        }
+        let offset = fastrand::Rng::with_seed(from).i64(-(jitter as i64)..=jitter as i64);
+        Some(((next as i64 + offset) as u64).max(from + 1))
+    }
+}
+
+// "90s", "2h30m", "1h 15m", "10 minutes", "1 hour". Units: s, m, h, d and their words.
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let text = text.trim().to_lowercase();
+    if text.is_empty() {
+        return Err("Empty duration".to_string());
+    }
+    let mut total = 0u64;
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("Expected a number in: {}", text));
+        }
+        let n = rest[..digits]
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid number in {}: {}", text, e))?;
+        rest = rest[digits..].trim_start();
+
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 60 * 60 * 24,
+            "" => return Err(format!("Missing unit in: {}", text)),
+            u => return Err(format!("Unknown unit {} in: {}", u, text)),
+        };
+        total = n
+            .checked_mul(unit)
+            .and_then(|s| total.checked_add(s))
+            .ok_or_else(|| format!("Duration too long: {}", text))?;
+        rest = rest[unit_len..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+    Ok(total)
+}
+
+const DAYS: &[&str] = &[
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+// A classic five field cron line in local time: minute hour day-of-month month
+// day-of-week, with *, lists, ranges and steps. Day and month names work too.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cron {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn name_value(s: &str, min: u32) -> Option<u32> {
+    let s = s.to_lowercase();
+    if min == 0 {
+        if let Some(i) = DAYS.iter().position(|d| s.len() >= 3 && d.starts_with(&s)) {
+            return Some(i as u32);
+        }
+    } else if let Some(i) = MONTHS.iter().position(|m| s.starts_with(m)) {
+        return Some(i as u32 + 1);
+    }
+    None
+}
+
+// One cron field as a bit set. `names` allows day or month names.
+fn parse_field(field: &str, min: u32, max: u32, names: bool) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        s.parse::<u32>()
+            .ok()
+            .or_else(|| if names { name_value(s, min) } else { None })
+            .filter(|v| *v >= min && *v <= max)
+            .ok_or_else(|| format!("{} is not between {} and {}", s, min, max))
+    };
+
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (
+                r,
+                s.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("Invalid step: {}", s))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            r => match r.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                None if step > 1 => (value(r)?, max),
+                None => (value(r)?, value(r)?),
+            },
+        };
+        if start > end {
+            return Err(format!("Empty range: {}", range));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+// "09:00", "9:30", "9am", "6:30pm" as (hour, minute)
+fn parse_time(s: &str) -> Option<(u32, u32)> {
+    let s = s.trim();
+    let (s, pm) = if let Some(t) = s.strip_suffix("pm") {
+        (t.trim(), Some(true))
+    } else if let Some(t) = s.strip_suffix("am") {
+        (t.trim(), Some(false))
+    } else {
+        (s, None)
+    };
+    let (h, m) = match s.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None => (s.parse::<u32>().ok()?, 0),
+    };
+    let h = match pm {
+        Some(_) if h == 0 || h > 12 => return None,
+        Some(true) => h % 12 + 12,
+        Some(false) => h % 12,
+        None => h,
+    };
+    if h > 23 || m > 59 {
+        return None;
+    }
+    Some((h, m))
+}
+
+// "every weekday at 09:00", "every monday and friday at 8am", "daily at 18:30",
+// "every hour" as a cron line
+fn phrase_to_cron(text: &str) -> Option<String> {
+    let text = text.trim().to_lowercase();
+    match text.as_str() {
+        "hourly" | "every hour" => return Some("0 * * * *".to_string()),
+        "daily" | "every day" => return Some("0 0 * * *".to_string()),
+        _ => {}
+    }
+
+    let (days, time) = match text.rsplit_once(" at ") {
+        Some((d, t)) => (d.trim(), t),
+        None => (text.strip_prefix("at ")?, ""),
+    };
+    let (hour, minute) = parse_time(if time.is_empty() { days } else { time })?;
+    let days = if time.is_empty() { "every day" } else { days };
+
+    let weekdays = match days {
+        "daily" | "every day" => "*".to_string(),
+        "every weekday" | "weekdays" => "1-5".to_string(),
+        "every weekend" | "weekends" => "0,6".to_string(),
+        d => {
+            let d = d.strip_prefix("every ").unwrap_or(d);
+            let list = d
+                .split([',', ' '])
+                .map(|w| w.trim())
+                .filter(|w| !w.is_empty() && *w != "and")
+                .map(|w| name_value(w.trim_end_matches('s'), 0))
+                .collect::<Option<Vec<u32>>>()?;
+            if list.is_empty() {
+                return None;
+            }
+            list.iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        }
+    };
+    Some(format!("{} {} * * {}", minute, hour, weekdays))
+}
+
+impl Cron {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let line = phrase_to_cron(text).unwrap_or_else(|| text.trim().to_string());
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return Err(format!("Expected 5 cron fields in: {}", line));
+        }
+        // 7 is sunday as well
+        let weekdays = parse_field(fields[4], 0, 7, true)?;
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59, false)?,
+            hours: parse_field(fields[1], 0, 23, false)? as u32,
+            days: parse_field(fields[2], 1, 31, false)? as u32,
+            months: parse_field(fields[3], 1, 12, true)? as u16,
+            weekdays: ((weekdays | (weekdays >> 7)) & 0x7f) as u8,
+            any_day: fields[2].starts_with('*'),
+            any_weekday: fields[4].starts_with('*'),
+        })
+    }
+
+    // Like cron, when day of month and day of week are both restricted either one
+    // matches, when one starts with * ("*/2" too) both have to.
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+
+    // First matching minute after `from`, unix seconds.
+    pub fn next_after(&self, from: u64) -> Option<u64> {
+        let start = Local.timestamp_opt(from as i64, 0).single()?;
+        let mut date = start.date_naive();
+        // every schedule matches within a leap year cycle
+        for _ in 0..(366 * 4) {
+            if self.day_matches(date) {
+                for hour in 0..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+                    for minute in 0..60 {
+                        if self.minutes & (1 << minute) == 0 {
+                            continue;
+                        }
+                        // skipped by a DST change
+                        let t = match date
+                            .and_hms_opt(hour, minute, 0)
+                            .and_then(|t| Local.from_local_datetime(&t).earliest())
+                        {
+                            Some(t) => t,
+                            None => continue,
+                        };
+                        if t.timestamp() > from as i64 {
+                            return Some(t.timestamp() as u64);
+                        }
+                    }
+                }
+            }
+            date += Duration::days(1);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Timelike};
+
+    // Local time as unix seconds
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> u64 {
+        Local
+            .with_ymd_and_hms(y, mo, d, h, mi, 0)
+            .earliest()
+            .unwrap()
+            .timestamp() as u64
+    }
+
+    fn next(expression: &str, from: u64) -> (i32, u32, u32, u32, u32) {
+        let t = Cron::parse(expression).unwrap().next_after(from).unwrap();
+        let t: DateTime<Local> = Local.timestamp_opt(t as i64, 0).unwrap();
+        (t.year(), t.month(), t.day(), t.hour(), t.minute())
+    }
+
+    fn bits(values: &[u32]) -> u64 {
+        values.iter().fold(0, |b, v| b | 1 << v)
+    }
+
+    #[test]
+    fn durations() {
+        let cases = [
+            ("90s", 90),
+            ("2h30m", 9000),
+            ("1h 15m", 4500),
+            ("10 minutes", 600),
+            ("1 hour", 3600),
+            ("1d", 86400),
+            ("2H, 5Min", 7500),
+            ("0s", 0),
+        ];
+        for (text, seconds) in cases {
+            assert_eq!(parse_duration(text), Ok(seconds), "{}", text);
+        }
+        for text in [
+            "",
+            "m",
+            "10",
+            "10 weeks",
+            "h10",
+            "1h x",
+            "99999999999999999999s",
+        ] {
+            assert!(parse_duration(text).is_err(), "{}", text);
+        }
+        // too long for seconds
+        assert!(parse_duration("999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn schedule_from_text() {
+        assert_eq!(
+            SlideSchedule::parse("10m", 5),
+            Ok(SlideSchedule::Every {
+                seconds: 600,
+                jitter: 5
+            })
+        );
+        assert_eq!(
+            SlideSchedule::parse(" */15 * * * * ", 0),
+            Ok(SlideSchedule::Cron {
+                expression: "*/15 * * * *".to_string(),
+                jitter: 0
+            })
+        );
+        assert!(SlideSchedule::parse("0s", 0).is_err());
+        assert!(SlideSchedule::parse("sometimes", 0).is_err());
+    }
+
+    #[test]
+    fn field_ranges_and_steps() {
+        assert_eq!(
+            parse_field("*", 0, 59, false),
+            Ok(bits(&(0..=59).collect::<Vec<u32>>()))
+        );
+        assert_eq!(
+            parse_field("*/15", 0, 59, false),
+            Ok(bits(&[0, 15, 30, 45]))
+        );
+        assert_eq!(parse_field("5/20", 0, 59, false), Ok(bits(&[5, 25, 45])));
+        assert_eq!(
+            parse_field("1-10/3", 1, 31, false),
+            Ok(bits(&[1, 4, 7, 10]))
+        );
+        assert_eq!(
+            parse_field("1,3,5-6", 0, 23, false),
+            Ok(bits(&[1, 3, 5, 6]))
+        );
+        assert_eq!(
+            parse_field("*/2", 1, 12, true),
+            Ok(bits(&[1, 3, 5, 7, 9, 11]))
+        );
+        assert_eq!(
+            parse_field("mon-fri", 0, 7, true),
+            Ok(bits(&[1, 2, 3, 4, 5]))
+        );
+        assert_eq!(parse_field("jan,DEC", 1, 12, true), Ok(bits(&[1, 12])));
+
+        for (field, min, max) in [
+            ("60", 0, 59),
+            ("24", 0, 23),
+            ("0", 1, 31),
+            ("32", 1, 31),
+            ("13", 1, 12),
+            ("8", 0, 7),
+            ("*/0", 0, 59),
+            ("10-5", 0, 59),
+            ("x", 0, 59),
+            ("", 0, 59),
+        ] {
+            assert!(parse_field(field, min, max, false).is_err(), "{}", field);
+        }
+        // names only where they make sense
+        assert!(parse_field("mon", 0, 59, false).is_err());
+    }
+
+    #[test]
+    fn cron_lines() {
+        assert!(Cron::parse("* * * *").is_err());
+        assert!(Cron::parse("* * * * * *").is_err());
+        // 7 is sunday too
+        assert_eq!(Cron::parse("0 0 * * 7"), Cron::parse("0 0 * * 0"));
+        assert_eq!(Cron::parse("0 0 * * sun").unwrap().weekdays, 1);
+    }
+
+    #[test]
+    fn next_minute_and_hour() {
+        let from = at(2026, 3, 10, 10, 15);
+        assert_eq!(next("30 * * * *", from), (2026, 3, 10, 10, 30));
+        // strictly after
+        assert_eq!(
+            next("30 * * * *", at(2026, 3, 10, 10, 30)),
+            (2026, 3, 10, 11, 30)
+        );
+        assert_eq!(next("*/20 9-17 * * *", from), (2026, 3, 10, 10, 20));
+        assert_eq!(
+            next("0 9 * * *", at(2026, 3, 10, 23, 59)),
+            (2026, 3, 11, 9, 0)
+        );
+    }
+
+    #[test]
+    fn next_rolls_over_months_and_years() {
+        assert_eq!(
+            next("0 0 1 * *", at(2026, 1, 31, 12, 0)),
+            (2026, 2, 1, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 1 * *", at(2026, 12, 15, 0, 0)),
+            (2027, 1, 1, 0, 0)
+        );
+        // April has no 31st
+        assert_eq!(
+            next("0 0 31 * *", at(2026, 4, 1, 0, 0)),
+            (2026, 5, 31, 0, 0)
+        );
+        assert_eq!(
+            next("0 8 * feb *", at(2026, 2, 28, 9, 0)),
+            (2027, 2, 1, 8, 0)
+        );
+    }
+
+    #[test]
+    fn next_leap_day() {
+        assert_eq!(
+            next("0 12 29 2 *", at(2026, 3, 1, 0, 0)),
+            (2028, 2, 29, 12, 0)
+        );
+        assert_eq!(
+            next("0 12 29 2 *", at(2028, 2, 29, 12, 0)),
+            (2032, 2, 29, 12, 0)
+        );
+        // february 30th never comes
+        assert_eq!(
+            Cron::parse("0 0 30 2 *")
+                .unwrap()
+                .next_after(at(2026, 1, 1, 0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // 2026-03-01 is a sunday: monday the 2nd matches by day, friday the 6th by weekday
+        let from = at(2026, 3, 1, 0, 0);
+        assert_eq!(next("0 9 2 * fri", from), (2026, 3, 2, 9, 0));
+        assert_eq!(
+            next("0 9 2 * fri", at(2026, 3, 2, 9, 0)),
+            (2026, 3, 6, 9, 0)
+        );
+        // only one restricted, only that one counts
+        assert_eq!(next("0 9 * * fri", from), (2026, 3, 6, 9, 0));
+        assert_eq!(next("0 9 2 * *", at(2026, 3, 2, 9, 0)), (2026, 4, 2, 9, 0));
+
+        // with */n both have to match: odd days that are mondays, the 2nd is even
+        assert_eq!(next("0 9 */2 * mon", from), (2026, 3, 9, 9, 0));
+        // the 3rd on a weekend, march and april fall on weekdays
+        assert_eq!(next("0 9 3 * */6", from), (2026, 5, 3, 9, 0));
+    }
+
+    #[test]
+    fn phrases() {
+        let cases = [
+            ("every weekday at 09:00", "0 9 * * 1-5"),
+            ("Every Monday and Friday at 8am", "0 8 * * 1,5"),
+            ("every tuesdays, thursdays at 7pm", "0 19 * * 2,4"),
+            ("daily at 18:30", "30 18 * * *"),
+            ("every weekend at 10", "0 10 * * 0,6"),
+            ("at 6:30pm", "30 18 * * *"),
+            ("at 12am", "0 0 * * *"),
+            ("every hour", "0 * * * *"),
+            ("daily", "0 0 * * *"),
+        ];
+        for (phrase, line) in cases {
+            assert_eq!(phrase_to_cron(phrase), Some(line.to_string()), "{}", phrase);
+        }
+        for phrase in [
+            "every blursday at 9",
+            "at 13pm",
+            "at 24:00",
+            "at 9:60",
+            "every at 9",
+        ] {
+            assert_eq!(phrase_to_cron(phrase), None, "{}", phrase);
+        }
+        assert_eq!(
+            Cron::parse("every weekday at 09:00"),
+            Cron::parse("0 9 * * 1-5")
+        );
+    }
+
+    #[test]
+    fn jitter_stays_in_range() {
+        let every = SlideSchedule::Every {
+            seconds: 600,
+            jitter: 60,
+        };
+        let mut seen = Vec::new();
+        for from in 1000..1100 {
+            let next = every.next_after(from).unwrap();
+            assert!((from + 540..=from + 660).contains(&next));
+            // the same slide gets the same deadline
+            assert_eq!(every.next_after(from), Some(next));
+            seen.push(next - from);
+        }
+        seen.sort();
+        seen.dedup();
+        assert!(seen.len() > 10);
+
+        // never before the change itself
+        let short = SlideSchedule::Every {
+            seconds: 10,
+            jitter: 100,
+        };
+        assert!((0..100).all(|from| short.next_after(from).unwrap() > from));
+
+        let cron = SlideSchedule::Cron {
+            expression: "0 * * * *".to_string(),
+            jitter: 30,
+        };
+        let from = at(2026, 3, 10, 10, 15);
+        let hour = at(2026, 3, 10, 11, 0);
+        let next = cron.next_after(from).unwrap();
+        assert!((hour - 30..=hour + 30).contains(&next));
+    }
+}
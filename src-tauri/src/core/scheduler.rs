@@ -0,0 +1,232 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+
+use super::display::{DisplayHandle, MonitorWrapper, SlideTarget, SlideTimes};
+use super::utils;
+
+// Longest nap between two checks, so changed settings and a resumed machine are
+// noticed without waiting for the old deadline.
+const MAX_SLEEP: u64 = 10;
+
+pub const WALLPAPER_CHANGED: &str = "wallpaper_changed";
+
+// Unix seconds. The scheduler never reads the time on its own, so it can be driven
+// by a fake clock.
+pub trait Clock: Send + 'static {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        utils::get_sys_time_in_secs()
+    }
+}
+
+pub struct Scheduler<C: Clock> {
+    clock: C,
+    last_tick: Option<u64>,
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            last_tick: None,
+        }
+    }
+
+    // The time of one round, read once so every step of the round agrees on it.
+    pub fn now(&mut self) -> u64 {
+        let now = self.clock.now();
+        match self.last_tick {
+            Some(last) if now < last => println!("Clock went back {}s", last - now),
+            Some(last) if now > last + 2 * MAX_SLEEP => {
+                println!("Slideshow resumed after {}s", now - last)
+            }
+            _ => {}
+        }
+        self.last_tick = Some(now);
+        now
+    }
+
+    // The slideshows to advance at `now`, from their saved slide times. However many
+    // deadlines were missed (machine asleep, app closed) a slideshow changes once and
+    // its next deadline counts from now, so waking up never fires a burst of changes.
+    // A slide time after `now` means the clock went back, that slideshow changes now
+    // rather than wait for the clock to catch up.
+    pub fn tick(&self, slides: &[SlideTimes], now: u64) -> Vec<SlideTarget> {
+        slides
+            .iter()
+            .filter(|s| s.deadline <= now || s.slide_time > now)
+            .map(|s| s.target.clone())
+            .collect()
+    }
+
+    // How long to wait before the next deadline or time of day album switch is due.
+    pub fn sleep_for(&self, slides: &[SlideTimes], album_switch: Option<u64>) -> Duration {
+        let now = self.clock.now();
+        let next = slides
+            .iter()
+            .map(|s| s.deadline)
+            .chain(album_switch)
+            .min()
+            .map_or(MAX_SLEEP, |d| d.saturating_sub(now));
+        Duration::from_secs(next.clamp(1, MAX_SLEEP))
+    }
+}
+
+// Switch monitors to the album of the time of day, then advance every slideshow the
+// scheduler finds due. Returns what changed and how long to sleep.
+fn run<C: Clock>(
+    scheduler: &mut Scheduler<C>,
+    handle: &mut DisplayHandle,
+) -> (Vec<MonitorWrapper>, Duration) {
+    let now = scheduler.now();
+    let mut changed = Vec::new();
+    for w in handle.displays() {
+        match handle.switch_time_album(&w.device_id, now) {
+            Ok(Some(w)) => changed.push(w),
+            Ok(None) => {}
+            Err(e) => println!("Time of day album {} Error: {}", w.device_id, e),
+        }
+    }
+
+    for target in scheduler.tick(&handle.slide_times(), now) {
+        match &target {
+            SlideTarget::Monitor(device_id) => match handle.advance_slide(device_id, now) {
+                Ok(w) => changed.push(w),
+                Err(e) => println!("Slideshow {} Error: {}", device_id, e),
+            },
+            SlideTarget::Group(name) => match handle.advance_group(name, now, false) {
+                Ok(ws) => changed.extend(ws),
+                Err(e) => println!("Group {} Error: {}", name, e),
+            },
+        }
+    }
+
+    let sleep = scheduler.sleep_for(&handle.slide_times(), handle.next_time_album_switch(now));
+    (changed, sleep)
+}
+
+pub fn spawn<C: Clock>(app: AppHandle, handle: Arc<Mutex<DisplayHandle>>, clock: C) {
+    thread::spawn(move || {
+        let mut scheduler = Scheduler::new(clock);
+        loop {
+            let (changed, sleep) = run(&mut scheduler, &mut handle.lock().unwrap());
+            for w in changed {
+                let _ = app.emit_all(WALLPAPER_CHANGED, w);
+            }
+            thread::sleep(sleep);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // A clock the test sets by hand.
+    #[derive(Clone, Default)]
+    struct FakeClock(Arc<AtomicU64>);
+
+    impl FakeClock {
+        fn set(&self, now: u64) {
+            self.0.store(now, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn scheduler(now: u64) -> (Scheduler<FakeClock>, FakeClock) {
+        let clock = FakeClock::default();
+        clock.set(now);
+        (Scheduler::new(clock.clone()), clock)
+    }
+
+    fn monitor(device_id: &str, slide_time: u64, deadline: u64) -> SlideTimes {
+        SlideTimes {
+            target: SlideTarget::Monitor(device_id.to_string()),
+            slide_time,
+            deadline,
+        }
+    }
+
+    #[test]
+    fn only_passed_deadlines_are_due() {
+        let (mut scheduler, _) = scheduler(1000);
+        let slides = [
+            monitor("DP-1", 400, 1000),
+            monitor("DP-2", 900, 1001),
+            // asleep for many intervals, still changes once
+            monitor("DP-3", 0, 60),
+            SlideTimes {
+                target: SlideTarget::Group("Wall".to_string()),
+                slide_time: 700,
+                deadline: 900,
+            },
+        ];
+        let now = scheduler.now();
+        assert_eq!(
+            scheduler.tick(&slides, now),
+            vec![
+                SlideTarget::Monitor("DP-1".to_string()),
+                SlideTarget::Monitor("DP-3".to_string()),
+                SlideTarget::Group("Wall".to_string()),
+            ]
+        );
+        assert!(scheduler.tick(&slides[1..2], now).is_empty());
+        assert!(scheduler.tick(&[], now).is_empty());
+    }
+
+    #[test]
+    fn clock_going_back_does_not_stall_a_slideshow() {
+        let (mut scheduler, clock) = scheduler(10_000);
+        assert_eq!(scheduler.now(), 10_000);
+
+        clock.set(4_000);
+        let now = scheduler.now();
+        assert_eq!(now, 4_000);
+        let slides = [
+            // saved before the jump, the deadline lies hours ahead
+            monitor("DP-1", 9_990, 10_590),
+            monitor("DP-2", 3_990, 4_590),
+        ];
+        assert_eq!(
+            scheduler.tick(&slides, now),
+            vec![SlideTarget::Monitor("DP-1".to_string())]
+        );
+    }
+
+    #[test]
+    fn sleeps_until_the_next_deadline_within_bounds() {
+        let (scheduler, clock) = scheduler(1000);
+        let slides = [monitor("DP-1", 400, 1004), monitor("DP-2", 700, 1300)];
+        assert_eq!(scheduler.sleep_for(&slides, None), Duration::from_secs(4));
+        assert_eq!(
+            scheduler.sleep_for(&slides, Some(1002)),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            scheduler.sleep_for(&slides[1..], None),
+            Duration::from_secs(MAX_SLEEP)
+        );
+        assert_eq!(
+            scheduler.sleep_for(&[], None),
+            Duration::from_secs(MAX_SLEEP)
+        );
+
+        // overdue, check again shortly
+        clock.set(2000);
+        assert_eq!(scheduler.sleep_for(&slides, None), Duration::from_secs(1));
+    }
+}
@@ -1,7 +1,10 @@
 use image::ImageFormat;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 
+use super::config::picture_id;
+
 fn is_image_file(path: &PathBuf) -> bool {
     match ImageFormat::from_path(path) {
         Ok(_) => true,
@@ -39,6 +42,12 @@ pub struct OsicSlideSelector {
     pictures: Option<Vec<OsicImageWrapper>>,
     ratio_pool: Vec<usize>,
     wallpaper_index: usize,
+    // picked ahead by `peek`, `one` hands it out next
+    #[serde(skip)]
+    queued: Option<OsicImageWrapper>,
+    // pictures by `config::picture_id` that are never picked
+    #[serde(skip)]
+    blacklist: BTreeSet<String>,
 }
 
 impl OsicSlideSelector {
@@ -48,24 +57,40 @@ impl OsicSlideSelector {
         return s;
     }
 
-    fn set_shuffle(&mut self, need_shuffle: bool) {
+    pub fn set_shuffle(&mut self, need_shuffle: bool) {
         self.shuffle = need_shuffle;
+        self.queued = None;
     }
 
-    fn set_ratio(&mut self, keep_ratio: bool) {
+    pub fn set_ratio(&mut self, keep_ratio: bool) {
         self.ratio = keep_ratio;
         let _ = self.refresh_ratio_pool();
     }
 
-    fn set_ratio_value(&mut self, ratio_value: f32) {
+    pub fn set_ratio_value(&mut self, ratio_value: f32) {
         self.ratio_value = ratio_value;
+        let _ = self.refresh_ratio_pool();
     }
 
-    fn set_ratio_range(&mut self, range: f32) {
+    pub fn set_ratio_range(&mut self, range: f32) {
         self.ratio_range = range;
         let _ = self.refresh_ratio_pool();
     }
 
+    pub fn set_blacklist(&mut self, blacklist: &BTreeSet<String>) {
+        if &self.blacklist == blacklist {
+            return;
+        }
+        self.blacklist = blacklist.clone();
+        if self.queued.as_ref().is_some_and(|p| !self.allowed(p)) {
+            self.queued = None;
+        }
+    }
+
+    fn allowed(&self, p: &OsicImageWrapper) -> bool {
+        !picture_id(&p.path).is_some_and(|id| self.blacklist.contains(&id))
+    }
+
     pub fn set_album_path(&mut self, p: PathBuf) {
         self.new_settings(
             p,
@@ -86,6 +111,7 @@ impl OsicSlideSelector {
     ) {
         if !self.path.eq(&path) {
             self.path = path;
+            self.pictures = None;
             self.fetch_picture();
             self.wallpaper_index = 0;
         }
@@ -134,30 +160,102 @@ impl OsicSlideSelector {
         return self.pictures.as_ref().unwrap().get(next).cloned();
     }
 
+    // Read the album again, pictures may have come and gone while the app was closed.
+    // The sequence goes on after `current` when that picture is still there.
+    pub fn reload(&mut self, current: Option<&PathBuf>) {
+        self.pictures = None;
+        self.fetch_picture();
+        self.refresh_ratio_pool();
+
+        let sequence = self.pool();
+        let found = current.and_then(|c| {
+            sequence
+                .iter()
+                .position(|i| self.get_picture(*i).map_or(false, |p| &p.path == c))
+        });
+        self.wallpaper_index = match found {
+            Some(i) => i,
+            None => self.wallpaper_index.min(sequence.len().saturating_sub(1)),
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        self.pictures.as_ref().map_or(0, |p| p.len())
+    }
+
     pub fn one(&mut self) -> Option<OsicImageWrapper> {
-        if let Some(p) = &self.pictures {
-            if p.is_empty() {
-                return None;
-            };
+        match self.queued.take() {
+            Some(p) => Some(p),
+            None => self.pick(),
+        }
+    }
 
-            if self.shuffle {
-                if self.ratio {
-                    let i = fastrand::usize(..self.ratio_pool.len());
-                    let index = self.ratio_pool.get(i).unwrap();
-                    return self.get_picture(*index);
-                }
-                let index = fastrand::usize(..self.pictures.as_ref().unwrap().len());
-                return self.get_picture(index);
+    // Like `one`, but never a picture in `exclude`. None when every picture is.
+    pub fn one_except(&mut self, exclude: &[PathBuf]) -> Option<OsicImageWrapper> {
+        match self.queued.take() {
+            Some(p) if !exclude.contains(&p.path) => return Some(p),
+            queued => self.queued = queued,
+        }
+        if self.shuffle {
+            return self.shuffled(exclude);
+        }
+        for _ in 0..self.len() {
+            let p = self.pick()?;
+            if !exclude.contains(&p.path) {
+                return Some(p);
             }
+        }
+        None
+    }
 
-            if self.ratio {
-                return self.sequence_ratio_picture();
-            }
+    // The picture `one` returns next.
+    pub fn peek(&mut self) -> Option<OsicImageWrapper> {
+        if self.queued.is_none() {
+            self.queued = self.pick();
+        }
+        self.queued.clone()
+    }
 
-            return self.sequence_picture();
+    // The pictures to draw from, by index: those of the monitor's ratio or all.
+    fn pool(&self) -> Vec<usize> {
+        if self.ratio {
+            self.ratio_pool.clone()
         } else {
+            (0..self.len()).collect()
+        }
+    }
+
+    // A random picture of the pool, neither blacklisted nor in `exclude`.
+    fn shuffled(&self, exclude: &[PathBuf]) -> Option<OsicImageWrapper> {
+        let pictures = self.pictures.as_ref()?;
+        let left = self
+            .pool()
+            .into_iter()
+            .filter_map(|i| pictures.get(i))
+            .filter(|p| self.allowed(p) && !exclude.contains(&p.path))
+            .collect::<Vec<&OsicImageWrapper>>();
+        if left.is_empty() {
             return None;
-        };
+        }
+        Some(left[fastrand::usize(..left.len())].clone())
+    }
+
+    fn pick(&mut self) -> Option<OsicImageWrapper> {
+        if self.shuffle {
+            return self.shuffled(&[]);
+        }
+        // blacklisted pictures are stepped over, once round the album at most
+        for _ in 0..self.pool().len() {
+            let p = if self.ratio {
+                self.sequence_ratio_picture()?
+            } else {
+                self.sequence_picture()?
+            };
+            if self.allowed(&p) {
+                return Some(p);
+            }
+        }
+        None
     }
 
     fn ratio_check(image: &OsicImageWrapper, ratio: f32, range: f32) -> bool {
@@ -166,6 +264,7 @@ impl OsicSlideSelector {
     }
 
     fn refresh_ratio_pool(&mut self) -> usize {
+        self.queued = None;
         if !self.ratio || self.pictures.is_none() {
             return 0;
         };
@@ -218,3 +317,90 @@ impl OsicSlideSelector {
         return n;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn album(name: &str, sizes: &[(u32, u32)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("osic-test-{}-{:x}", name, fastrand::u64(..)));
+        fs::create_dir_all(&dir).unwrap();
+        for (i, (width, height)) in sizes.iter().enumerate() {
+            image::RgbImage::new(*width, *height)
+                .save(dir.join(format!("{}.png", i)))
+                .unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn peek_shows_what_one_returns_next() {
+        let dir = album("peek", &[(4, 3), (4, 3), (4, 3)]);
+        let mut s = OsicSlideSelector::new(4.0 / 3.0);
+        s.set_album_path(dir.clone());
+
+        let next = s.peek().unwrap().path;
+        assert_eq!(s.peek().unwrap().path, next);
+        assert_eq!(s.one().unwrap().path, next);
+        assert_ne!(s.one().unwrap().path, next);
+
+        s.set_shuffle(true);
+        let next = s.peek().unwrap().path;
+        assert_eq!(s.one().unwrap().path, next);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn blacklisted_pictures_are_never_picked() {
+        let dir = album("blacklist", &[(4, 3), (4, 3), (4, 3), (3, 4)]);
+        let id = |i: usize| picture_id(&dir.join(format!("{}.png", i))).unwrap();
+        let mut s = OsicSlideSelector::new(4.0 / 3.0);
+        s.set_album_path(dir.clone());
+        let peeked = s.peek().unwrap().path;
+
+        let blacklist = [id(0), id(2)].into_iter().collect::<BTreeSet<String>>();
+        s.set_blacklist(&blacklist);
+        let allowed = [dir.join("1.png"), dir.join("3.png")];
+        if !allowed.contains(&peeked) {
+            assert_ne!(s.peek().unwrap().path, peeked);
+        }
+        for shuffle in [false, true] {
+            s.set_shuffle(shuffle);
+            for _ in 0..20 {
+                assert!(allowed.contains(&s.one().unwrap().path));
+            }
+        }
+        assert_eq!(
+            s.one_except(&[dir.join("1.png")]).unwrap().path,
+            dir.join("3.png")
+        );
+
+        // of the pictures with the monitor's ratio one is left, then none
+        s.set_ratio_range(0.1);
+        s.set_ratio(true);
+        assert_eq!(s.one().unwrap().path, dir.join("1.png"));
+        s.set_blacklist(&[id(0), id(1), id(2)].into_iter().collect());
+        for shuffle in [false, true] {
+            s.set_shuffle(shuffle);
+            assert!(s.one().is_none());
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn new_settings_drop_the_peeked_picture() {
+        let dir = album("peek-ratio", &[(4, 3), (3, 4)]);
+        let mut s = OsicSlideSelector::new(4.0 / 3.0);
+        s.set_album_path(dir.clone());
+
+        let landscape = |p: &OsicImageWrapper| p.width > p.height;
+        while s.peek().map_or(false, |p| landscape(&p)) {
+            s.one();
+        }
+        s.set_ratio_range(0.1);
+        s.set_ratio(true);
+        assert!(landscape(&s.one().unwrap()));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,339 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub latitude: f64,
+    // east is positive
+    pub longitude: f64,
+}
+
+impl Location {
+    pub fn new(latitude: f64, longitude: f64) -> Result<Self, String> {
+        if !latitude.is_finite() || latitude.abs() > 90.0 {
+            return Err(format!("Latitude must be between -90 and 90: {}", latitude));
+        }
+        if !longitude.is_finite() || longitude.abs() > 180.0 {
+            return Err(format!(
+                "Longitude must be between -180 and 180: {}",
+                longitude
+            ));
+        }
+        Ok(Self {
+            latitude,
+            longitude,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum SolarEvent {
+    // civil twilight begins, the sun is 6° below the horizon
+    Dawn,
+    Sunrise,
+    Sunset,
+    // civil twilight ends
+    Dusk,
+}
+
+impl SolarEvent {
+    // Sun altitude in degrees at the event, sunrise and sunset include refraction.
+    fn altitude(&self) -> f64 {
+        match self {
+            SolarEvent::Dawn | SolarEvent::Dusk => -6.0,
+            SolarEvent::Sunrise | SolarEvent::Sunset => -0.833,
+        }
+    }
+
+    fn rising(&self) -> bool {
+        matches!(self, SolarEvent::Dawn | SolarEvent::Sunrise)
+    }
+}
+
+// The album shown from `event` until the next event in the list.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeAlbum {
+    pub event: SolarEvent,
+    pub album_path: PathBuf,
+}
+
+const SECONDS_PER_DAY: i64 = 86400;
+// 2000-01-01 in days since the unix epoch
+const J2000_DAY: i64 = 10957;
+
+fn sin_deg(d: f64) -> f64 {
+    (d * PI / 180.0).sin()
+}
+
+fn cos_deg(d: f64) -> f64 {
+    (d * PI / 180.0).cos()
+}
+
+// Time of `event` on the UTC day `day` (days since the unix epoch), unix seconds.
+// None when the sun doesn't get there that day (polar day or night).
+// https://en.wikipedia.org/wiki/Sunrise_equation
+pub fn event_time(location: &Location, day: i64, event: SolarEvent) -> Option<i64> {
+    let n = (day - J2000_DAY) as f64;
+    let mean_solar_noon = n - location.longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_noon).rem_euclid(360.0);
+    let center =
+        1.9148 * sin_deg(anomaly) + 0.02 * sin_deg(2.0 * anomaly) + 0.0003 * sin_deg(3.0 * anomaly);
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = 2451545.0 + mean_solar_noon + 0.0053 * sin_deg(anomaly)
+        - 0.0069 * sin_deg(2.0 * ecliptic_longitude);
+    let declination = (sin_deg(ecliptic_longitude) * sin_deg(23.4397)).asin() * 180.0 / PI;
+
+    let cos_hour_angle = (sin_deg(event.altitude())
+        - sin_deg(location.latitude) * sin_deg(declination))
+        / (cos_deg(location.latitude) * cos_deg(declination));
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos() * 180.0 / PI;
+    let julian = if event.rising() {
+        transit - hour_angle / 360.0
+    } else {
+        transit + hour_angle / 360.0
+    };
+    Some(((julian - 2440587.5) * SECONDS_PER_DAY as f64).round() as i64)
+}
+
+// Whether the sun is above the horizon at noon of `day`, for days without events.
+fn polar_day(location: &Location, day: i64) -> bool {
+    let n = (day - J2000_DAY) as f64;
+    let anomaly = (357.5291 + 0.98560028 * n).rem_euclid(360.0);
+    let center = 1.9148 * sin_deg(anomaly) + 0.02 * sin_deg(2.0 * anomaly);
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let declination = (sin_deg(ecliptic_longitude) * sin_deg(23.4397)).asin() * 180.0 / PI;
+    90.0 - (location.latitude - declination).abs() > 0.0
+}
+
+// The album that belongs to `now` and when the next switch is due, unix seconds.
+pub fn current_album(
+    albums: &[TimeAlbum],
+    location: &Location,
+    now: u64,
+) -> Option<(PathBuf, Option<u64>)> {
+    if albums.is_empty() {
+        return None;
+    }
+    let now = now as i64;
+    let today = now.div_euclid(SECONDS_PER_DAY);
+
+    let mut events = Vec::new();
+    for day in (today - 1)..=(today + 1) {
+        for album in albums {
+            if let Some(t) = event_time(location, day, album.event) {
+                events.push((t, album));
+            }
+        }
+    }
+    events.sort_by_key(|(t, _)| *t);
+
+    let next = events
+        .iter()
+        .find(|(t, _)| *t > now)
+        .map(|(t, _)| *t as u64);
+    let album = match events.iter().rev().find(|(t, _)| *t <= now) {
+        Some((_, album)) => album,
+        // the sun never crosses the event's altitude, go by day or night
+        None => {
+            let day = polar_day(location, today);
+            albums
+                .iter()
+                .find(|a| a.event.rising() == day)
+                .unwrap_or(&albums[0])
+        }
+    };
+    Some((album.album_path.clone(), next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    fn day(y: i32, m: u32, d: u32) -> i64 {
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .signed_duration_since(epoch)
+            .num_days()
+    }
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0)
+            .unwrap()
+            .timestamp()
+    }
+
+    // Published times are rounded to the minute and the formula is good to about
+    // one more.
+    fn near(t: Option<i64>, expected: i64) -> bool {
+        t.is_some_and(|t| (t - expected).abs() <= 3 * 60)
+    }
+
+    const BERLIN: Location = Location {
+        latitude: 52.52,
+        longitude: 13.405,
+    };
+    const NEW_YORK: Location = Location {
+        latitude: 40.7128,
+        longitude: -74.006,
+    };
+    const TOKYO: Location = Location {
+        latitude: 35.6762,
+        longitude: 139.6503,
+    };
+    const SVALBARD: Location = Location {
+        latitude: 78.22,
+        longitude: 15.65,
+    };
+
+    fn albums() -> Vec<TimeAlbum> {
+        vec![
+            TimeAlbum {
+                event: SolarEvent::Sunrise,
+                album_path: PathBuf::from("day"),
+            },
+            TimeAlbum {
+                event: SolarEvent::Sunset,
+                album_path: PathBuf::from("night"),
+            },
+        ]
+    }
+
+    #[test]
+    fn locations_are_checked() {
+        assert!(Location::new(52.52, 13.405).is_ok());
+        assert!(Location::new(90.5, 0.0).is_err());
+        assert!(Location::new(0.0, -180.5).is_err());
+        assert!(Location::new(f64::NAN, 0.0).is_err());
+    }
+
+    #[test]
+    fn sunrise_and_sunset_in_berlin() {
+        // 04:43 and 21:33 CEST on the longest day
+        let june = day(2024, 6, 21);
+        assert!(near(
+            event_time(&BERLIN, june, SolarEvent::Sunrise),
+            utc(2024, 6, 21, 2, 43)
+        ));
+        assert!(near(
+            event_time(&BERLIN, june, SolarEvent::Sunset),
+            utc(2024, 6, 21, 19, 33)
+        ));
+        // 08:15 and 15:54 CET on the shortest
+        let december = day(2024, 12, 21);
+        assert!(near(
+            event_time(&BERLIN, december, SolarEvent::Sunrise),
+            utc(2024, 12, 21, 7, 15)
+        ));
+        assert!(near(
+            event_time(&BERLIN, december, SolarEvent::Sunset),
+            utc(2024, 12, 21, 14, 54)
+        ));
+
+        // civil twilight around them
+        let dawn = event_time(&BERLIN, june, SolarEvent::Dawn).unwrap();
+        let dusk = event_time(&BERLIN, june, SolarEvent::Dusk).unwrap();
+        assert!(dawn < utc(2024, 6, 21, 2, 43) - 30 * 60);
+        assert!(dusk > utc(2024, 6, 21, 19, 33) + 30 * 60);
+    }
+
+    #[test]
+    fn west_of_greenwich() {
+        // 05:25 and 20:31 EDT, the sunset is on the next UTC day
+        let june = day(2024, 6, 21);
+        assert!(near(
+            event_time(&NEW_YORK, june, SolarEvent::Sunrise),
+            utc(2024, 6, 21, 9, 25)
+        ));
+        assert!(near(
+            event_time(&NEW_YORK, june, SolarEvent::Sunset),
+            utc(2024, 6, 22, 0, 31)
+        ));
+        // 07:16 and 16:32 EST
+        let december = day(2024, 12, 21);
+        assert!(near(
+            event_time(&NEW_YORK, december, SolarEvent::Sunrise),
+            utc(2024, 12, 21, 12, 16)
+        ));
+        assert!(near(
+            event_time(&NEW_YORK, december, SolarEvent::Sunset),
+            utc(2024, 12, 21, 21, 32)
+        ));
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let june = day(2024, 6, 21);
+        let december = day(2024, 12, 21);
+        for event in [
+            SolarEvent::Dawn,
+            SolarEvent::Sunrise,
+            SolarEvent::Sunset,
+            SolarEvent::Dusk,
+        ] {
+            assert_eq!(event_time(&SVALBARD, june, event), None);
+            assert_eq!(event_time(&SVALBARD, december, event), None);
+        }
+        assert!(polar_day(&SVALBARD, june));
+        assert!(!polar_day(&SVALBARD, december));
+
+        // no switch coming, day or night decides
+        let noon = utc(2024, 6, 21, 12, 0) as u64;
+        assert_eq!(
+            current_album(&albums(), &SVALBARD, noon),
+            Some((PathBuf::from("day"), None))
+        );
+        let noon = utc(2024, 12, 21, 12, 0) as u64;
+        assert_eq!(
+            current_album(&albums(), &SVALBARD, noon),
+            Some((PathBuf::from("night"), None))
+        );
+    }
+
+    #[test]
+    fn next_switch_across_midnight_utc() {
+        let sunset = event_time(&NEW_YORK, day(2024, 6, 21), SolarEvent::Sunset).unwrap();
+        let sunrise = event_time(&NEW_YORK, day(2024, 6, 22), SolarEvent::Sunrise).unwrap();
+        let album = |now: i64| current_album(&albums(), &NEW_YORK, now as u64).unwrap();
+
+        // an evening in New York is the next UTC day
+        assert_eq!(
+            album(utc(2024, 6, 21, 23, 0)),
+            (PathBuf::from("day"), Some(sunset as u64))
+        );
+        assert_eq!(
+            album(utc(2024, 6, 22, 0, 10)),
+            (PathBuf::from("day"), Some(sunset as u64))
+        );
+        assert_eq!(
+            album(sunset),
+            (PathBuf::from("night"), Some(sunrise as u64))
+        );
+
+        // east of Greenwich the morning is the UTC day before
+        let sunrise = event_time(&TOKYO, day(2024, 6, 21), SolarEvent::Sunrise).unwrap();
+        let sunset = event_time(&TOKYO, day(2024, 6, 21), SolarEvent::Sunset).unwrap();
+        assert!(near(Some(sunrise), utc(2024, 6, 20, 19, 25)));
+        let album = |now: i64| current_album(&albums(), &TOKYO, now as u64).unwrap();
+        assert_eq!(
+            album(utc(2024, 6, 20, 19, 0)),
+            (PathBuf::from("night"), Some(sunrise as u64))
+        );
+        assert_eq!(
+            album(utc(2024, 6, 21, 0, 30)),
+            (PathBuf::from("day"), Some(sunset as u64))
+        );
+    }
+
+    #[test]
+    fn no_albums_no_answer() {
+        assert_eq!(current_album(&[], &BERLIN, 0), None);
+    }
+}
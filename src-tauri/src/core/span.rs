@@ -0,0 +1,383 @@
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::display::Monitor;
+
+// Frame around the visible area of a screen, in millimetres.
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bezel {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+// A monitor placed in physical space, millimetres.
+#[derive(Debug, Clone)]
+struct PhysicalRect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+// Millimetres per pixel of a monitor, None when it doesn't report its size.
+fn density(m: &Monitor) -> Option<f32> {
+    if m.width_mm == 0 || m.height_mm == 0 || m.width <= 0 || m.height <= 0 {
+        return None;
+    }
+    Some((m.width_mm as f32 / m.width as f32 + m.height_mm as f32 / m.height as f32) / 2.0)
+}
+
+fn overlaps(a0: i32, a1: i32, b0: i32, b1: i32) -> bool {
+    a0 < b1 && b0 < a1
+}
+
+// Lay the monitors out the way they stand on the desk. Screens keep their arrangement
+// from the pixel layout, but every screen gets its real size and neighbours are pushed
+// apart by their bezels, so a picture continues behind the frames instead of jumping.
+fn physical_layout(monitors: &[Monitor], bezels: &HashMap<String, Bezel>) -> Vec<PhysicalRect> {
+    let known = monitors.iter().filter_map(density).collect::<Vec<f32>>();
+    let fallback = if known.is_empty() {
+        1.0
+    } else {
+        known.iter().sum::<f32>() / known.len() as f32
+    };
+    let none = Bezel::default();
+    let bezel = |m: &Monitor| bezels.get(&m.device_id).unwrap_or(&none);
+
+    let mut rects = monitors
+        .iter()
+        .map(|m| {
+            let d = density(m).unwrap_or(fallback);
+            PhysicalRect {
+                x: m.letf as f32 * fallback,
+                y: m.top as f32 * fallback,
+                width: m.width as f32 * d,
+                height: m.height as f32 * d,
+            }
+        })
+        .collect::<Vec<PhysicalRect>>();
+
+    // left to right, each screen starts where its left neighbours end
+    let mut order = (0..monitors.len()).collect::<Vec<usize>>();
+    order.sort_by_key(|&i| monitors[i].letf);
+    for &i in &order {
+        let m = &monitors[i];
+        let x = order
+            .iter()
+            .filter(|&&j| {
+                monitors[j].right <= m.letf
+                    && overlaps(monitors[j].top, monitors[j].bottom, m.top, m.bottom)
+            })
+            .map(|&j| rects[j].x + rects[j].width + bezel(&monitors[j]).right + bezel(m).left)
+            .fold(None, |acc: Option<f32>, x| {
+                Some(acc.map_or(x, |a| a.max(x)))
+            });
+        if let Some(x) = x {
+            rects[i].x = x;
+        }
+    }
+
+    // top to bottom, the same for the screens above
+    order.sort_by_key(|&i| monitors[i].top);
+    for &i in &order {
+        let m = &monitors[i];
+        let y = order
+            .iter()
+            .filter(|&&j| {
+                monitors[j].bottom <= m.top
+                    && overlaps(monitors[j].letf, monitors[j].right, m.letf, m.right)
+            })
+            .map(|&j| rects[j].y + rects[j].height + bezel(&monitors[j]).bottom + bezel(m).top)
+            .fold(None, |acc: Option<f32>, y| {
+                Some(acc.map_or(y, |a| a.max(y)))
+            });
+        if let Some(y) = y {
+            rects[i].y = y;
+        }
+    }
+    rects
+}
+
+// Cut one picture into a crop per monitor. The picture fills the physical bounding box
+// of all screens, every crop is exactly the monitor's resolution.
+pub fn span_crops(
+    img: &DynamicImage,
+    monitors: &[Monitor],
+    bezels: &HashMap<String, Bezel>,
+) -> Result<Vec<(String, RgbaImage)>, String> {
+    if img.width() == 0 || img.height() == 0 {
+        return Err("Empty image".to_string());
+    }
+    let rects = physical_layout(monitors, bezels);
+    if rects.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let left = rects.iter().map(|r| r.x).fold(f32::MAX, f32::min);
+    let top = rects.iter().map(|r| r.y).fold(f32::MAX, f32::min);
+    let right = rects.iter().map(|r| r.x + r.width).fold(f32::MIN, f32::max);
+    let bottom = rects
+        .iter()
+        .map(|r| r.y + r.height)
+        .fold(f32::MIN, f32::max);
+    let (width, height) = (right - left, bottom - top);
+
+    // image pixels per millimetre, the picture covers the whole box and is centered
+    let k = (img.width() as f32 / width).min(img.height() as f32 / height);
+    let offset_x = (img.width() as f32 - width * k) / 2.0;
+    let offset_y = (img.height() as f32 - height * k) / 2.0;
+
+    let mut crops = Vec::new();
+    for (m, r) in monitors.iter().zip(rects.iter()) {
+        if m.width <= 0 || m.height <= 0 {
+            continue;
+        }
+        let x = ((r.x - left) * k + offset_x).round().max(0.0) as u32;
+        let y = ((r.y - top) * k + offset_y).round().max(0.0) as u32;
+        let w = ((r.width * k).round() as u32).clamp(1, img.width() - x.min(img.width() - 1));
+        let h = ((r.height * k).round() as u32).clamp(1, img.height() - y.min(img.height() - 1));
+        let part = img.crop_imm(x.min(img.width() - 1), y.min(img.height() - 1), w, h);
+        let part = part
+            .resize_exact(m.width as u32, m.height as u32, FilterType::Triangle)
+            .to_rgba8();
+        crops.push((m.device_id.clone(), part));
+    }
+    Ok(crops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::fake::fake_monitor;
+    use image::Rgba;
+
+    fn monitor(id: &str, x: i32, y: i32, size: (i32, i32), mm: (u32, u32)) -> Monitor {
+        Monitor {
+            width_mm: mm.0,
+            height_mm: mm.1,
+            ..fake_monitor(id, id, x, y, size.0, size.1)
+        }
+    }
+
+    fn bezels(list: &[(&str, Bezel)]) -> HashMap<String, Bezel> {
+        list.iter()
+            .map(|(id, b)| (id.to_string(), b.clone()))
+            .collect()
+    }
+
+    fn bezel(left: f32, top: f32, right: f32, bottom: f32) -> Bezel {
+        Bezel {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    #[test]
+    fn physical_layout_table() {
+        let hd = (1920, 1080);
+        let uhd = (3840, 2160);
+        let desk = (480, 270);
+        let cases: Vec<(&str, Vec<Monitor>, HashMap<String, Bezel>, Vec<[f32; 4]>)> = vec![
+            (
+                "side by side",
+                vec![
+                    monitor("A", 0, 0, hd, desk),
+                    monitor("B", 1920, 0, hd, desk),
+                ],
+                bezels(&[]),
+                vec![[0.0, 0.0, 480.0, 270.0], [480.0, 0.0, 480.0, 270.0]],
+            ),
+            (
+                "bezels push the right screen away",
+                vec![
+                    monitor("A", 0, 0, hd, desk),
+                    monitor("B", 1920, 0, hd, desk),
+                ],
+                bezels(&[
+                    ("A", bezel(10.0, 0.0, 12.0, 0.0)),
+                    ("B", bezel(8.0, 0.0, 10.0, 0.0)),
+                ]),
+                vec![[0.0, 0.0, 480.0, 270.0], [500.0, 0.0, 480.0, 270.0]],
+            ),
+            (
+                "stacked with bezels",
+                vec![
+                    monitor("A", 0, 0, hd, desk),
+                    monitor("B", 0, 1080, hd, desk),
+                ],
+                bezels(&[
+                    ("A", bezel(0.0, 0.0, 0.0, 5.0)),
+                    ("B", bezel(0.0, 7.0, 0.0, 0.0)),
+                ]),
+                vec![[0.0, 0.0, 480.0, 270.0], [0.0, 282.0, 480.0, 270.0]],
+            ),
+            (
+                "mixed dpi, same physical size",
+                vec![
+                    monitor("A", 0, 0, uhd, desk),
+                    monitor("B", 3840, 0, hd, desk),
+                ],
+                bezels(&[]),
+                vec![[0.0, 0.0, 480.0, 270.0], [480.0, 0.0, 480.0, 270.0]],
+            ),
+            (
+                "mixed dpi, laptop below a big screen",
+                vec![
+                    monitor("A", 0, 0, uhd, (960, 540)),
+                    monitor("B", 960, 2160, hd, (288, 162)),
+                ],
+                bezels(&[]),
+                // 0.25 and 0.15 mm per pixel, x from the pixel layout at 0.2
+                vec![[0.0, 0.0, 960.0, 540.0], [192.0, 540.0, 288.0, 162.0]],
+            ),
+            (
+                "negative coordinates",
+                vec![
+                    monitor("A", 0, 0, hd, desk),
+                    monitor("B", -1920, 0, hd, desk),
+                ],
+                bezels(&[("B", bezel(0.0, 0.0, 15.0, 0.0))]),
+                vec![[15.0, 0.0, 480.0, 270.0], [-480.0, 0.0, 480.0, 270.0]],
+            ),
+            (
+                "above the origin",
+                vec![
+                    monitor("A", 0, 0, hd, desk),
+                    monitor("B", 0, -1080, hd, desk),
+                ],
+                bezels(&[]),
+                vec![[0.0, 0.0, 480.0, 270.0], [0.0, -270.0, 480.0, 270.0]],
+            ),
+            (
+                "no sizes reported, pixels",
+                vec![
+                    monitor("A", 0, 0, hd, (0, 0)),
+                    monitor("B", 1920, 0, hd, (0, 0)),
+                ],
+                bezels(&[("A", bezel(0.0, 0.0, 30.0, 0.0))]),
+                vec![[0.0, 0.0, 1920.0, 1080.0], [1950.0, 0.0, 1920.0, 1080.0]],
+            ),
+        ];
+
+        for (name, monitors, bezels, expected) in cases {
+            let rects = physical_layout(&monitors, &bezels);
+            let got = rects
+                .iter()
+                .map(|r| [r.x, r.y, r.width, r.height])
+                .collect::<Vec<[f32; 4]>>();
+            let close = got.len() == expected.len()
+                && got
+                    .iter()
+                    .flatten()
+                    .zip(expected.iter().flatten())
+                    .all(|(a, b)| (a - b).abs() < 0.5);
+            assert!(close, "{}: {:?} != {:?}", name, got, expected);
+        }
+    }
+
+    // Vertical bands of colour, `(width, colour)` from the left.
+    fn stripes(height: u32, bands: &[(u32, [u8; 4])]) -> DynamicImage {
+        let width = bands.iter().map(|b| b.0).sum();
+        let mut img = RgbaImage::new(width, height);
+        let mut x0 = 0;
+        for (w, color) in bands {
+            for x in x0..x0 + w {
+                for y in 0..height {
+                    img.put_pixel(x, y, Rgba(*color));
+                }
+            }
+            x0 += w;
+        }
+        DynamicImage::ImageRgba8(img)
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    fn only(img: &RgbaImage, color: [u8; 4]) -> bool {
+        img.pixels().all(|p| p.0 == color)
+    }
+
+    #[test]
+    fn crops_skip_the_bezels() {
+        let monitors = vec![
+            monitor("A", 0, 0, (100, 50), (100, 50)),
+            monitor("B", 100, 0, (100, 50), (100, 50)),
+        ];
+        let bezels = bezels(&[
+            ("A", bezel(0.0, 0.0, 10.0, 0.0)),
+            ("B", bezel(10.0, 0.0, 0.0, 0.0)),
+        ]);
+        // the green band is hidden behind the frames
+        let img = stripes(50, &[(100, RED), (20, GREEN), (100, BLUE)]);
+        let crops = span_crops(&img, &monitors, &bezels).unwrap();
+        assert_eq!(crops[0].0, "A");
+        assert_eq!(crops[0].1.dimensions(), (100, 50));
+        assert!(only(&crops[0].1, RED));
+        assert_eq!(crops[1].0, "B");
+        assert!(only(&crops[1].1, BLUE));
+    }
+
+    #[test]
+    fn crops_have_the_monitor_resolution() {
+        // the same 100x50 mm, twice the pixels on the left
+        let monitors = vec![
+            monitor("A", 0, 0, (200, 100), (100, 50)),
+            monitor("B", 200, 0, (100, 50), (100, 50)),
+        ];
+        let img = stripes(50, &[(100, RED), (100, BLUE)]);
+        let crops = span_crops(&img, &monitors, &HashMap::new()).unwrap();
+        assert_eq!(crops[0].1.dimensions(), (200, 100));
+        assert!(only(&crops[0].1, RED));
+        assert_eq!(crops[1].1.dimensions(), (100, 50));
+        assert!(only(&crops[1].1, BLUE));
+    }
+
+    #[test]
+    fn crops_with_negative_coordinates() {
+        // B left of the origin gets the left part of the picture
+        let monitors = vec![
+            monitor("A", 0, 0, (100, 50), (100, 50)),
+            monitor("B", -100, 0, (100, 50), (100, 50)),
+        ];
+        let img = stripes(50, &[(100, RED), (100, BLUE)]);
+        let crops = span_crops(&img, &monitors, &HashMap::new()).unwrap();
+        assert_eq!(crops[0].0, "A");
+        assert!(only(&crops[0].1, BLUE));
+        assert_eq!(crops[1].0, "B");
+        assert!(only(&crops[1].1, RED));
+    }
+
+    #[test]
+    fn picture_is_centered_on_the_screens() {
+        // 200x50 mm of screens on a 400x200 picture: 2 pixels per mm, 50 rows cut
+        // off above and below
+        let monitors = vec![
+            monitor("A", 0, 0, (100, 50), (100, 50)),
+            monitor("B", 100, 0, (100, 50), (100, 50)),
+        ];
+        let mut img = RgbaImage::from_pixel(400, 200, Rgba(GREEN));
+        for x in 0..400 {
+            for y in 50..150 {
+                img.put_pixel(x, y, Rgba(if x < 200 { RED } else { BLUE }));
+            }
+        }
+        let crops = span_crops(&DynamicImage::ImageRgba8(img), &monitors, &HashMap::new()).unwrap();
+        assert!(only(&crops[0].1, RED));
+        assert!(only(&crops[1].1, BLUE));
+    }
+
+    #[test]
+    fn empty_picture_is_an_error() {
+        let monitors = vec![monitor("A", 0, 0, (100, 50), (100, 50))];
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(0, 0));
+        assert!(span_crops(&img, &monitors, &HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::cache;
+use super::config::{self, OsicConfig};
+use super::migrate;
+
+// Changes are written once they stop coming for this long, so dragging a slider
+// doesn't write the file on every step.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+// Wait before trying again after a failed write.
+const RETRY: Duration = Duration::from_secs(5);
+
+// The config as the app sees it. The file follows after `DEBOUNCE`.
+struct Store {
+    // None is `config::config_path()`
+    path: Option<PathBuf>,
+    config: Option<OsicConfig>,
+    // last change that isn't written yet
+    dirty: Option<Instant>,
+    // hash of the file content the app last read or wrote, anything else on disk
+    // is a hand edit the watcher hasn't applied yet
+    known: u64,
+    // why the last write failed, until one succeeds
+    failed: Option<String>,
+}
+
+impl Store {
+    fn path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(config::config_path)
+    }
+
+    fn backup_path(&self) -> PathBuf {
+        self.path().with_extension("json.bak")
+    }
+}
+
+// A store with the thread that writes it.
+struct SharedStore {
+    store: Mutex<Store>,
+    wake: Condvar,
+    flusher: Once,
+    debounce: Duration,
+    retry: Duration,
+}
+
+impl SharedStore {
+    const fn new(path: Option<PathBuf>, debounce: Duration, retry: Duration) -> Self {
+        Self {
+            store: Mutex::new(Store {
+                path,
+                config: None,
+                dirty: None,
+                known: 0,
+                failed: None,
+            }),
+            wake: Condvar::new(),
+            flusher: Once::new(),
+            debounce,
+            retry,
+        }
+    }
+}
+
+static STORE: SharedStore = SharedStore::new(None, DEBOUNCE, RETRY);
+
+fn text_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Write next to the target, flush it to the disk and move it over the target. A
+// crash leaves either the old or the new file, never half of one.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), io::Error> {
+    let tmp = path.with_extension("tmp");
+    let mut f = File::create(&tmp)?;
+    f.write_all(content)?;
+    f.sync_all()?;
+    drop(f);
+    fs::rename(&tmp, path)?;
+    // the rename itself lives in the folder
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let _ = File::open(dir).and_then(|d| d.sync_all());
+    }
+    Ok(())
+}
+
+// Write `config` now. The file it replaces becomes the backup when it was good.
+fn write(store: &mut Store, config: &OsicConfig) -> Result<(), io::Error> {
+    let text = serde_json::to_string_pretty(config).map_err(|e| io::Error::other(e.to_string()))?;
+    let path = store.path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if let Ok(old) = fs::read_to_string(&path) {
+        if config::parse(&old).is_ok() {
+            write_atomic(&store.backup_path(), old.as_bytes())?;
+        }
+    }
+    write_atomic(&path, text.as_bytes())?;
+    store.known = text_hash(&text);
+    Ok(())
+}
+
+// The backup in place of a config that is missing or doesn't parse. The broken
+// file is kept next to it for a look.
+fn restore_backup(store: &mut Store) -> OsicConfig {
+    let path = store.path();
+    if path.exists() {
+        let broken = path.with_extension("json.broken");
+        println!("Keeping the unreadable config as {}", broken.display());
+        let _ = fs::rename(&path, broken);
+    }
+    let restored = fs::read_to_string(store.backup_path())
+        .map_err(|e| e.to_string())
+        .and_then(|text| config::parse(&text));
+    match restored {
+        Ok(config) => {
+            println!("Restored config from {}", store.backup_path().display());
+            if let Err(e) = write(store, &config) {
+                println!("Write config Error: {}", e);
+            }
+            config
+        }
+        Err(_) => OsicConfig::default(),
+    }
+}
+
+// The config file at start. Without one, the backup or the settings older versions
+// kept in the temp folder take its place.
+fn read(store: &mut Store) -> OsicConfig {
+    match fs::read_to_string(store.path()) {
+        Ok(text) => match config::parse(&text) {
+            Ok(config) => {
+                store.known = text_hash(&text);
+                config
+            }
+            Err(e) => {
+                println!("{}", e);
+                restore_backup(store)
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if store.backup_path().exists() {
+                return restore_backup(store);
+            }
+            let config = migrate::temp_folder_config();
+            if config != OsicConfig::default() {
+                println!(
+                    "Moving settings from {} to {}",
+                    cache::os_temp_folder().display(),
+                    store.path().display()
+                );
+                if let Err(e) = write(store, &config) {
+                    println!("Write config Error: {}", e);
+                }
+            }
+            config
+        }
+        Err(e) => {
+            println!("Read config Error: {}, using defaults", e);
+            OsicConfig::default()
+        }
+    }
+}
+
+fn config(store: &mut Store) -> &mut OsicConfig {
+    if store.config.is_none() {
+        store.config = Some(read(store));
+    }
+    store.config.as_mut().unwrap()
+}
+
+// Write pending changes unless the file holds a hand edit that isn't applied yet,
+// that one wins.
+fn flush_store(store: &mut Store) -> Result<(), io::Error> {
+    if store.dirty.is_none() {
+        return Ok(());
+    }
+    if let Ok(text) = fs::read_to_string(store.path()) {
+        if text_hash(&text) != store.known {
+            return Err(io::Error::other(
+                "config was edited, waiting until the edit is applied",
+            ));
+        }
+    }
+    let pending = config(store).clone();
+    if let Err(e) = write(store, &pending) {
+        store.failed = Some(e.to_string());
+        return Err(e);
+    }
+    store.dirty = None;
+    store.failed = None;
+    Ok(())
+}
+
+fn spawn_flusher(shared: &'static SharedStore) {
+    thread::spawn(move || loop {
+        let mut store = shared.store.lock().unwrap();
+        while store.dirty.is_none() {
+            store = shared.wake.wait(store).unwrap();
+        }
+        let quiet = store.dirty.unwrap().elapsed();
+        if quiet < shared.debounce {
+            drop(store);
+            thread::sleep(shared.debounce - quiet);
+            continue;
+        }
+        if let Err(e) = flush_store(&mut store) {
+            println!("Write config Error: {}", e);
+            drop(store);
+            thread::sleep(shared.retry);
+        }
+    });
+}
+
+impl SharedStore {
+    fn load(&self) -> OsicConfig {
+        config(&mut self.store.lock().unwrap()).clone()
+    }
+
+    fn update<F>(&'static self, f: F) -> Result<(), io::Error>
+    where
+        F: FnOnce(&mut OsicConfig),
+    {
+        self.flusher.call_once(|| spawn_flusher(self));
+        let mut store = self.store.lock().unwrap();
+        f(config(&mut store));
+        store.dirty = Some(Instant::now());
+        self.wake.notify_one();
+        match &store.failed {
+            Some(e) => Err(io::Error::other(format!(
+                "the config couldn't be written, trying again: {}",
+                e
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        flush_store(&mut self.store.lock().unwrap())
+    }
+
+    fn replace(&self, config: OsicConfig, text: &str) {
+        let mut store = self.store.lock().unwrap();
+        store.config = Some(config);
+        store.dirty = None;
+        store.known = text_hash(text);
+    }
+
+    fn is_known(&self, text: &str) -> bool {
+        self.store.lock().unwrap().known == text_hash(text)
+    }
+}
+
+pub fn load() -> OsicConfig {
+    STORE.load()
+}
+
+// Change the config, the file is written a moment later. While the last write
+// failed the change is kept in memory and the error returned, writing goes on
+// being retried.
+pub fn update<F>(f: F) -> Result<(), io::Error>
+where
+    F: FnOnce(&mut OsicConfig),
+{
+    STORE.update(f)
+}
+
+// Write pending changes right away, before the app quits.
+pub fn flush() -> Result<(), io::Error> {
+    STORE.flush()
+}
+
+// A hand edit of the file replaces what the app holds, changes not written yet
+// are dropped.
+pub fn replace(config: OsicConfig, text: &str) {
+    STORE.replace(config, text)
+}
+
+// Whether `text` is what the app last read or wrote itself.
+pub fn is_known(text: &str) -> bool {
+    STORE.is_known(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::TempDir;
+
+    // A store of its own on a config file in `dir`, never dropped like the real one.
+    fn store_in(dir: &TempDir, debounce: Duration, retry: Duration) -> &'static SharedStore {
+        let path = dir.path().join("config.json");
+        Box::leak(Box::new(SharedStore::new(Some(path), debounce, retry)))
+    }
+
+    fn profile(name: &str) -> OsicConfig {
+        OsicConfig {
+            active_profile: Some(name.to_string()),
+            ..OsicConfig::default()
+        }
+    }
+
+    fn on_disk(dir: &TempDir, name: &str) -> Option<OsicConfig> {
+        let text = fs::read_to_string(dir.path().join(name)).ok()?;
+        config::parse(&text).ok()
+    }
+
+    #[test]
+    fn crash_between_tmp_and_rename_keeps_the_old_file() {
+        let dir = TempDir::new("store-crash");
+        let store = store_in(&dir, DEBOUNCE, RETRY);
+        store.update(|c| *c = profile("Old")).unwrap();
+        store.flush().unwrap();
+
+        // the new content made it to the tmp file but never over the config
+        fs::write(dir.path().join("config.tmp"), "{\"version\": 1, \"activ").unwrap();
+
+        let restarted = store_in(&dir, DEBOUNCE, RETRY);
+        assert_eq!(restarted.load(), profile("Old"));
+        restarted.update(|c| *c = profile("New")).unwrap();
+        restarted.flush().unwrap();
+        assert_eq!(on_disk(&dir, "config.json"), Some(profile("New")));
+        assert!(!dir.path().join("config.tmp").exists());
+    }
+
+    #[test]
+    fn corrupt_file_falls_back_to_the_backup() {
+        let dir = TempDir::new("store-corrupt");
+        let store = store_in(&dir, DEBOUNCE, RETRY);
+        store.update(|c| *c = profile("First")).unwrap();
+        store.flush().unwrap();
+        store.update(|c| *c = profile("Second")).unwrap();
+        store.flush().unwrap();
+        assert_eq!(on_disk(&dir, "config.json.bak"), Some(profile("First")));
+
+        let corrupt = "{\"version\": 1, \"activeProfile\": ";
+        fs::write(dir.path().join("config.json"), corrupt).unwrap();
+
+        let restarted = store_in(&dir, DEBOUNCE, RETRY);
+        assert_eq!(restarted.load(), profile("First"));
+        let broken = fs::read_to_string(dir.path().join("config.json.broken")).unwrap();
+        assert_eq!(broken, corrupt);
+        // the backup is written back in place of the broken file
+        assert_eq!(on_disk(&dir, "config.json"), Some(profile("First")));
+    }
+
+    fn wait_for(dir: &TempDir, expected: &OsicConfig, limit: Duration) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < limit {
+            if on_disk(dir, "config.json").as_ref() == Some(expected) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn failed_write_is_retried_and_reported() {
+        let debounce = Duration::from_millis(100);
+        let retry = Duration::from_millis(600);
+        let dir = TempDir::new("store-retry");
+        let text = serde_json::to_string_pretty(&profile("Start")).unwrap();
+        fs::write(dir.path().join("config.json"), text).unwrap();
+        let store = store_in(&dir, debounce, retry);
+        assert_eq!(store.load(), profile("Start"));
+
+        // a folder where the tmp file goes makes every write fail
+        let tmp = dir.path().join("config.tmp");
+        fs::create_dir(&tmp).unwrap();
+        let start = Instant::now();
+        store.update(|c| *c = profile("Changed")).unwrap();
+        assert!(!wait_for(&dir, &profile("Changed"), debounce * 3));
+        let err = store.update(|c| c.location = None).unwrap_err();
+        assert!(err.to_string().contains("trying again"), "{}", err);
+
+        fs::remove_dir(&tmp).unwrap();
+        assert!(wait_for(&dir, &profile("Changed"), Duration::from_secs(3)));
+        // not before the retry is due
+        assert!(start.elapsed() >= debounce + retry);
+        store.update(|_| {}).unwrap();
+    }
+
+    #[test]
+    fn changes_wait_for_the_debounce() {
+        let debounce = Duration::from_millis(300);
+        let dir = TempDir::new("store-debounce");
+        let text = serde_json::to_string_pretty(&profile("Start")).unwrap();
+        fs::write(dir.path().join("config.json"), text).unwrap();
+        let store = store_in(&dir, debounce, RETRY);
+
+        let start = Instant::now();
+        store.update(|c| *c = profile("Moved")).unwrap();
+        thread::sleep(debounce / 2);
+        store.update(|c| *c = profile("Settled")).unwrap();
+        assert!(wait_for(&dir, &profile("Settled"), Duration::from_secs(3)));
+        // the second change started the wait over
+        assert!(start.elapsed() >= debounce / 2 + debounce);
+    }
+}
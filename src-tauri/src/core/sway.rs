@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::env;
+use std::process::{Child, Command, Stdio};
+
+use serde::Deserialize;
+
+use super::backend::WallpaperBackend;
+use super::display::{Fits, Monitor};
+use super::edid::{self, MonitorFingerprint};
+use super::utils;
+
+#[derive(Deserialize, Default)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+// refresh in mHz
+#[derive(Deserialize, Default)]
+struct SwayMode {
+    #[serde(default)]
+    refresh: i32,
+}
+
+#[derive(Deserialize)]
+struct SwayOutput {
+    name: String,
+    #[serde(default)]
+    make: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    serial: String,
+    #[serde(default)]
+    active: bool,
+    #[serde(default)]
+    rect: Rect,
+    #[serde(default)]
+    current_mode: SwayMode,
+    scale: Option<f32>,
+    transform: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Deserialize)]
+struct WlrMode {
+    width: i32,
+    height: i32,
+    #[serde(default)]
+    refresh: f32,
+    #[serde(default)]
+    current: bool,
+}
+
+#[derive(Deserialize, Default)]
+struct PhysicalSize {
+    width: u32,
+    height: u32,
+}
+
+// `wlr-randr --json`, for river and other wlroots compositors without swaymsg
+#[derive(Deserialize)]
+struct WlrOutput {
+    name: String,
+    #[serde(default)]
+    make: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    serial: String,
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    modes: Vec<WlrMode>,
+    #[serde(default)]
+    position: Position,
+    #[serde(default)]
+    physical_size: PhysicalSize,
+    scale: Option<f32>,
+    transform: Option<String>,
+}
+
+fn output_name(name: &str, make: &str, model: &str) -> String {
+    let desc = format!("{} {}", make, model).trim().to_string();
+    if desc.is_empty() || desc == "Unknown Unknown" {
+        name.to_string()
+    } else {
+        desc
+    }
+}
+
+pub fn parse_sway_outputs(json: &str) -> Result<Vec<Monitor>, String> {
+    let outputs: Vec<SwayOutput> =
+        serde_json::from_str(json).map_err(|e| format!("Parse sway outputs Error: {}", e))?;
+    Ok(outputs
+        .into_iter()
+        .filter(|o| o.active)
+        .map(|o| {
+            let transform = o.transform.unwrap_or_else(|| "normal".to_string());
+            Monitor {
+                fingerprint: MonitorFingerprint::from_description(&o.make, &o.model, &o.serial),
+                name: output_name(&o.name, &o.make, &o.model),
+                device_id: o.name,
+                letf: o.rect.x,
+                top: o.rect.y,
+                right: o.rect.x + o.rect.width,
+                bottom: o.rect.y + o.rect.height,
+                width: o.rect.width,
+                height: o.rect.height,
+                make: o.make,
+                model: o.model,
+                serial: o.serial,
+                scale: o.scale.unwrap_or(1.0),
+                rotation: utils::transform_rotation(&transform),
+                transform,
+                refresh_rate: o.current_mode.refresh as f32 / 1000.0,
+                ..Default::default()
+            }
+        })
+        .collect())
+}
+
+// wlr-randr reports the mode in physical pixels, scale and rotation are applied here so
+// the rect is in layout coordinates like the one from sway.
+pub fn parse_wlr_outputs(json: &str) -> Result<Vec<Monitor>, String> {
+    let outputs: Vec<WlrOutput> =
+        serde_json::from_str(json).map_err(|e| format!("Parse wlr-randr outputs Error: {}", e))?;
+    Ok(outputs
+        .into_iter()
+        .filter(|o| o.enabled)
+        .filter_map(|o| {
+            let mode = o.modes.iter().find(|m| m.current)?;
+            let scale = o.scale.unwrap_or(1.0);
+            let transform = o.transform.unwrap_or_else(|| "normal".to_string());
+            let (mut width, mut height) = (
+                (mode.width as f32 / scale).round() as i32,
+                (mode.height as f32 / scale).round() as i32,
+            );
+            let rotation = utils::transform_rotation(&transform);
+            let (mut width_mm, mut height_mm) = (o.physical_size.width, o.physical_size.height);
+            if rotation % 180 == 90 {
+                std::mem::swap(&mut width, &mut height);
+                std::mem::swap(&mut width_mm, &mut height_mm);
+            }
+            Some(Monitor {
+                fingerprint: MonitorFingerprint::from_description(&o.make, &o.model, &o.serial),
+                name: output_name(&o.name, &o.make, &o.model),
+                device_id: o.name,
+                letf: o.position.x,
+                top: o.position.y,
+                right: o.position.x + width,
+                bottom: o.position.y + height,
+                width,
+                height,
+                make: o.make,
+                model: o.model,
+                serial: o.serial,
+                scale,
+                transform,
+                rotation,
+                refresh_rate: mode.refresh,
+                width_mm,
+                height_mm,
+            })
+        })
+        .collect())
+}
+
+fn swaybg_mode(fit: &Fits) -> &'static str {
+    match fit {
+        Fits::Fill | Fits::Span => "fill",
+        Fits::Fit => "fit",
+        Fits::Stretch => "stretch",
+        Fits::Tile => "tile",
+        Fits::Center => "center",
+    }
+}
+
+// wlroots compositors draw no wallpaper themselves, it is a layer-shell client.
+// One swaybg per output is kept running and replaced whenever its picture changes.
+pub struct SwayBackend {
+    fit: Fits,
+    background: u32,
+    wallpapers: HashMap<String, String>,
+    children: HashMap<String, Child>,
+}
+
+impl SwayBackend {
+    pub fn new() -> Self {
+        Self {
+            fit: Fits::Fill,
+            background: 0,
+            wallpapers: HashMap::new(),
+            children: HashMap::new(),
+        }
+    }
+
+    // Start the new swaybg before stopping the old one, so the output never shows
+    // the bare compositor background in between.
+    fn restart(&mut self, output: &str) -> Result<(), String> {
+        let mut cmd = Command::new("swaybg");
+        cmd.arg("-o")
+            .arg(output)
+            .arg("-c")
+            .arg(format!("#{:06x}", self.background));
+        if let Some(wallpaper) = self.wallpapers.get(output) {
+            cmd.arg("-i")
+                .arg(wallpaper)
+                .arg("-m")
+                .arg(swaybg_mode(&self.fit));
+        } else {
+            cmd.arg("-m").arg("solid_color");
+        }
+        let child = cmd
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Run swaybg Error: {}", e))?;
+
+        if let Some(mut old) = self.children.insert(output.to_string(), child) {
+            let _ = old.kill();
+            let _ = old.wait();
+        }
+        Ok(())
+    }
+
+    fn restart_all(&mut self) -> Result<(), String> {
+        let outputs = self.wallpapers.keys().cloned().collect::<Vec<String>>();
+        for output in outputs {
+            self.restart(&output)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SwayBackend {
+    fn drop(&mut self) {
+        for (_, mut child) in self.children.drain() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl WallpaperBackend for SwayBackend {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+        let (program, args, parse): (&str, &[&str], fn(&str) -> Result<Vec<Monitor>, String>) =
+            if env::var("SWAYSOCK").is_ok() {
+                ("swaymsg", &["-t", "get_outputs", "-r"], parse_sway_outputs)
+            } else {
+                ("wlr-randr", &["--json"], parse_wlr_outputs)
+            };
+
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("Run {} Error: {}", program, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} Error: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let mut monitors = parse(&String::from_utf8_lossy(&output.stdout))?;
+        for m in &mut monitors {
+            if let Some(f) = edid::fingerprint_sysfs(&m.device_id) {
+                m.fingerprint = Some(f);
+            }
+        }
+        Ok(monitors)
+    }
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
+        self.wallpapers
+            .insert(monitor_id.to_string(), wallpaper.to_string());
+        self.restart(monitor_id)
+    }
+
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String> {
+        Ok(self.wallpapers.get(monitor_id).cloned().unwrap_or_default())
+    }
+
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String> {
+        self.fit = fit;
+        self.restart_all()
+    }
+
+    fn set_background_color(&mut self, color: u32) -> Result<(), String> {
+        self.background = color & 0xffffff;
+        self.restart_all()
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        Ok(self.background)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::Stubs;
+
+    const SWAY_OUTPUTS: &str = r#"[{"name":"DP-1","make":"Dell Inc.","model":"U2720Q","serial":"ABC123","active":true,"rect":{"x":0,"y":0,"width":1280,"height":720},"scale":1.5,"transform":"90","current_mode":{"width":1920,"height":1080,"refresh":59951}},{"name":"HDMI-A-1","active":false,"rect":{"x":0,"y":0,"width":0,"height":0}}]"#;
+
+    const WLR_OUTPUTS: &str = r#"[{"name":"eDP-1","make":"BOE","model":"0x095F","serial":"","enabled":true,"modes":[{"width":2256,"height":1504,"refresh":59.999,"current":true}],"position":{"x":0,"y":0},"physical_size":{"width":285,"height":190},"scale":2.0,"transform":"270"}]"#;
+
+    fn stubs() -> Stubs {
+        let stubs = Stubs::new("sway");
+        stubs.tool(
+            "swaymsg",
+            &format!(
+                "echo \"swaymsg $@\" >> \"$LOG\"\ncat <<'J'\n{}\nJ",
+                SWAY_OUTPUTS
+            ),
+        );
+        stubs.tool(
+            "wlr-randr",
+            &format!(
+                "echo \"wlr-randr $@\" >> \"$LOG\"\ncat <<'J'\n{}\nJ",
+                WLR_OUTPUTS
+            ),
+        );
+        stubs.tool("swaybg", "echo \"swaybg $@\" >> \"$LOG\"\nexec sleep 30");
+        stubs
+    }
+
+    fn running(pid: u32) -> bool {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .map_or(false, |s| s.success())
+    }
+
+    #[test]
+    fn monitors_from_swaymsg() {
+        let stubs = stubs();
+        env::set_var("SWAYSOCK", "/tmp/osic-test-sway.sock");
+        let monitors = SwayBackend::new().get_monitor_device_path();
+        env::remove_var("SWAYSOCK");
+
+        let monitors = monitors.unwrap();
+        assert_eq!(stubs.log(), "swaymsg -t get_outputs -r\n");
+        assert_eq!(monitors.len(), 1);
+        let m = &monitors[0];
+        assert_eq!(
+            (m.device_id.as_str(), m.name.as_str()),
+            ("DP-1", "Dell Inc. U2720Q")
+        );
+        assert_eq!((m.width, m.height, m.rotation), (1280, 720, 90));
+        assert_eq!(m.refresh_rate, 59.951);
+        assert!(m.fingerprint.is_some());
+    }
+
+    #[test]
+    fn monitors_from_wlr_randr_without_sway() {
+        let stubs = stubs();
+        env::remove_var("SWAYSOCK");
+        let monitors = SwayBackend::new().get_monitor_device_path().unwrap();
+        assert_eq!(stubs.log(), "wlr-randr --json\n");
+        let m = &monitors[0];
+        // 2256x1504 at scale 2, turned by 270 degrees
+        assert_eq!((m.width, m.height), (752, 1128));
+        assert_eq!((m.width_mm, m.height_mm), (190, 285));
+    }
+
+    #[test]
+    fn swaybg_per_output() {
+        let stubs = stubs();
+        let mut backend = SwayBackend::new();
+        backend.set_background_color(0x102030).unwrap();
+        backend.set_wallpaper("DP-1", "/pics/a b.png").unwrap();
+        // a swaybg only logs once it runs, wait for it before it is replaced
+        let fill = "swaybg -o DP-1 -c #102030 -i /pics/a b.png -m fill\n";
+        assert_eq!(stubs.wait_log(1), fill);
+        backend.set_fit(Fits::Center).unwrap();
+        assert_eq!(
+            stubs.wait_log(2),
+            format!(
+                "{}swaybg -o DP-1 -c #102030 -i /pics/a b.png -m center\n",
+                fill
+            )
+        );
+        assert_eq!(backend.children.len(), 1);
+    }
+
+    #[test]
+    fn old_swaybg_is_replaced() {
+        let _stubs = stubs();
+        let mut backend = SwayBackend::new();
+        backend.set_wallpaper("DP-1", "/pics/a.png").unwrap();
+        let old = backend.children["DP-1"].id();
+        assert!(running(old));
+
+        backend.set_wallpaper("DP-1", "/pics/b.png").unwrap();
+        let new = backend.children["DP-1"].id();
+        assert_ne!(old, new);
+        assert!(!running(old));
+        assert!(running(new));
+
+        drop(backend);
+        assert!(!running(new));
+    }
+
+    #[test]
+    fn missing_swaybg_is_reported() {
+        let stubs = Stubs::new("sway-missing");
+        let path = env::var_os("PATH");
+        env::set_var("PATH", &stubs.dir);
+        let result = SwayBackend::new().set_wallpaper("DP-1", "/pics/a.png");
+        env::set_var("PATH", path.unwrap_or_default());
+        assert!(result.unwrap_err().starts_with("Run swaybg Error"));
+    }
+}
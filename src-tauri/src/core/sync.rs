@@ -0,0 +1,778 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+
+use super::cache;
+use super::config::{MonitorConfig, OsicConfig, ProfileConfig};
+use super::display::DisplayHandle;
+use super::edid::MonitorFingerprint;
+use super::store;
+use crate::tray;
+
+pub const SYNC_VERSION: u32 = 1;
+pub const SETTINGS_SYNCED: &str = "settings_synced";
+
+// How often the shared folder is read for changes of other machines.
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+// Every machine writes its own file in here, so the folder never has two writers
+// of one file and the sync tool has nothing to turn into a conflict copy.
+const SYNC_DIR: &str = "osic-sync";
+
+// Settings of a profile monitor that mean the same on every machine. Album and image
+// paths, device ids and slideshow positions stay with the machine.
+const MONITOR_FIELDS: &[&str] = &[
+    "nickname",
+    "mode",
+    "fit",
+    "slideInterval",
+    "shuffle",
+    "ratio",
+    "ratioRange",
+    "bezel",
+];
+
+// One synced setting, who changed it last and when (unix millis).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncField {
+    pub value: Value,
+    pub modified: u64,
+    pub machine: String,
+    // when the value it replaced was written, tells a change made on top of
+    // another from one made next to it
+    #[serde(default)]
+    pub base: u64,
+}
+
+impl SyncField {
+    // Last writer wins, the machine id breaks a tie so every machine picks the same.
+    fn newer_than(&self, other: &SyncField) -> bool {
+        (self.modified, &self.machine) > (other.modified, &other.machine)
+    }
+}
+
+// The file a machine keeps in the shared folder. Settings are flat keys:
+//   profile/<name>                               true, null once removed
+//   profile/<name>/rule                          the topology rule
+//   profile/<name>/monitor/<monitor>/<field>     one of `MONITOR_FIELDS`
+//   favorite/<picture>                           true, null once unmarked
+//   rating/<picture>                             1 to 5, null once cleared
+//   blacklist/<picture>                          true, null once unmarked
+// Monitors go by their EDID identity and pictures by `config::picture_id`, which
+// are the same on every machine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDoc {
+    pub version: u32,
+    pub machine: String,
+    pub fields: BTreeMap<String, SyncField>,
+}
+
+// What this machine synced last, kept next to the config and never shared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub machine: String,
+    #[serde(default)]
+    pub folder: Option<PathBuf>,
+    #[serde(default)]
+    pub last_sync: u64,
+    #[serde(default)]
+    pub fields: BTreeMap<String, SyncField>,
+    // newest version of each setting read from another machine
+    #[serde(default)]
+    pub seen: BTreeMap<String, u64>,
+    // settings of profile monitors this machine has neither in the profile nor
+    // connected, taken over once the monitor turns up
+    #[serde(default)]
+    pub pending: BTreeMap<String, Value>,
+}
+
+impl SyncState {
+    pub fn new(machine: &str) -> Self {
+        Self {
+            machine: machine.to_string(),
+            folder: None,
+            last_sync: 0,
+            fields: BTreeMap::new(),
+            seen: BTreeMap::new(),
+            pending: BTreeMap::new(),
+        }
+    }
+}
+
+// A setting changed here and on another machine since the last sync. The newer one
+// is kept, the other is reported.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConflict {
+    pub key: String,
+    pub local: Value,
+    pub remote: Value,
+    pub remote_machine: String,
+    pub kept: Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    // keys another machine changed here
+    pub changed: Vec<String>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncInfo {
+    pub machine: String,
+    pub folder: Option<PathBuf>,
+    pub last_sync: u64,
+}
+
+// Profile names may hold anything, keep them from being read as a key separator.
+fn escape(name: &str) -> String {
+    name.replace('%', "%25").replace('/', "%2F")
+}
+
+fn unescape(name: &str) -> String {
+    name.replace("%2F", "/").replace("%25", "%")
+}
+
+// The unit where the EDID has a serial, the model otherwise.
+fn monitor_key(f: &MonitorFingerprint) -> String {
+    let mut key = format!("{}-{:04x}", f.manufacturer, f.product_code);
+    if !f.serial_text.is_empty() {
+        key.push_str(&format!("-{}", escape(&f.serial_text)));
+    } else if f.serial != 0 {
+        key.push_str(&format!("-{}", f.serial));
+    }
+    key
+}
+
+fn profile_monitor_fields(prefix: &str, m: &MonitorConfig, fields: &mut BTreeMap<String, Value>) {
+    let key = match &m.fingerprint {
+        Some(f) => monitor_key(f),
+        None => return,
+    };
+    let value = serde_json::to_value(m).unwrap();
+    for field in MONITOR_FIELDS {
+        fields.insert(
+            format!("{}/monitor/{}/{}", prefix, key, field),
+            value[*field].clone(),
+        );
+    }
+}
+
+// Kinds of keys with nothing below them, one missing here was removed.
+const REMOVABLE: &[&str] = &["profile", "favorite", "rating", "blacklist"];
+
+// The synced settings of a config. Profiles and picture marks `state` knows of that
+// are gone here count as removed, anything else missing is left as it is.
+pub fn local_fields(config: &OsicConfig, state: &SyncState) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+    for p in &config.profiles {
+        let prefix = format!("profile/{}", escape(&p.name));
+        fields.insert(
+            format!("{}/rule", prefix),
+            serde_json::to_value(p.rule).unwrap(),
+        );
+        for m in &p.monitors {
+            profile_monitor_fields(&prefix, m, &mut fields);
+        }
+        fields.insert(prefix, Value::Bool(true));
+    }
+    for id in &config.favorites {
+        fields.insert(format!("favorite/{}", escape(id)), Value::Bool(true));
+    }
+    for (id, rating) in &config.ratings {
+        fields.insert(format!("rating/{}", escape(id)), Value::from(*rating));
+    }
+    for id in &config.blacklist {
+        fields.insert(format!("blacklist/{}", escape(id)), Value::Bool(true));
+    }
+    for (key, field) in &state.fields {
+        let removable = match key.split_once('/') {
+            Some((kind, name)) => REMOVABLE.contains(&kind) && !name.contains('/'),
+            None => false,
+        };
+        if removable && !field.value.is_null() && !fields.contains_key(key) {
+            fields.insert(key.clone(), Value::Null);
+        }
+    }
+    fields
+}
+
+// Merge the files of other machines into what this machine has. Settings changed
+// here since the last sync get `now`, every key then goes to its newest writer.
+// Returns the settings this machine has to take over and the conflicts on the way.
+pub fn merge(
+    state: &mut SyncState,
+    local: &BTreeMap<String, Value>,
+    remotes: &[SyncDoc],
+    now: u64,
+) -> (BTreeMap<String, Value>, Vec<SyncConflict>) {
+    let mut mine = state.fields.clone();
+    for (key, value) in local {
+        match mine.get(key) {
+            Some(f) if &f.value == value => {}
+            previous => {
+                let base = previous.map_or(0, |f| f.modified);
+                mine.insert(
+                    key.clone(),
+                    SyncField {
+                        value: value.clone(),
+                        modified: now,
+                        machine: state.machine.clone(),
+                        base,
+                    },
+                );
+            }
+        }
+    }
+
+    let mut merged = mine.clone();
+    let mut seen = state.seen.clone();
+    let mut conflicts = Vec::new();
+    let mut reported = BTreeSet::new();
+    for doc in remotes.iter().filter(|d| d.machine != state.machine) {
+        for (key, theirs) in &doc.fields {
+            let news = theirs.modified > state.seen.get(key).copied().unwrap_or(0);
+            if theirs.modified > seen.get(key).copied().unwrap_or(0) {
+                seen.insert(key.clone(), theirs.modified);
+            }
+            // a change here and one there, neither made on top of the other
+            if let Some(ours) = mine.get(key) {
+                if news
+                    && ours.machine == state.machine
+                    && ours.value != theirs.value
+                    && theirs.base < ours.modified
+                    && ours.base < theirs.modified
+                    && reported.insert(key.clone())
+                {
+                    let kept = if theirs.newer_than(ours) {
+                        theirs
+                    } else {
+                        ours
+                    };
+                    conflicts.push(SyncConflict {
+                        key: key.clone(),
+                        local: ours.value.clone(),
+                        remote: theirs.value.clone(),
+                        remote_machine: theirs.machine.clone(),
+                        kept: kept.value.clone(),
+                    });
+                }
+            }
+            match merged.get(key) {
+                Some(current) if !theirs.newer_than(current) => {}
+                _ => {
+                    merged.insert(key.clone(), theirs.clone());
+                }
+            }
+        }
+    }
+
+    let mut changed = BTreeMap::new();
+    for (key, field) in &merged {
+        if local.get(key) != Some(&field.value) && mine.get(key) != Some(field) {
+            changed.insert(key.clone(), field.value.clone());
+        }
+    }
+    state.fields = merged;
+    state.seen = seen;
+    state.last_sync = now;
+    (changed, conflicts)
+}
+
+fn set_monitor_field(m: &mut MonitorConfig, field: &str, value: &Value) -> Result<(), String> {
+    let mut config = serde_json::to_value(&*m).unwrap();
+    config[field] = value.clone();
+    *m = serde_json::from_value(config).map_err(|e| format!("{}: {}", field, e))?;
+    Ok(())
+}
+
+fn mark(marked: &mut BTreeSet<String>, id: String, value: &Value) {
+    if value.is_null() {
+        marked.remove(&id);
+    } else {
+        marked.insert(id);
+    }
+}
+
+// Returns false when the setting belongs to a monitor that isn't here yet.
+fn apply_field(
+    config: &mut OsicConfig,
+    key: &str,
+    value: &Value,
+    connected: &[MonitorConfig],
+) -> Result<bool, String> {
+    let (kind, rest) = match key.split_once('/') {
+        Some(parts) => parts,
+        None => return Ok(true),
+    };
+    match kind {
+        "profile" => return apply_profile_field(config, rest, value, connected),
+        "favorite" => mark(&mut config.favorites, unescape(rest), value),
+        "blacklist" => mark(&mut config.blacklist, unescape(rest), value),
+        "rating" if value.is_null() => {
+            config.ratings.remove(&unescape(rest));
+        }
+        "rating" => match value.as_u64() {
+            Some(rating) if (1..=5).contains(&rating) => {
+                config.ratings.insert(unescape(rest), rating as u8);
+            }
+            _ => return Err(format!("not a rating: {}", value)),
+        },
+        _ => {}
+    }
+    Ok(true)
+}
+
+// `rest` is the key below "profile/".
+fn apply_profile_field(
+    config: &mut OsicConfig,
+    rest: &str,
+    value: &Value,
+    connected: &[MonitorConfig],
+) -> Result<bool, String> {
+    let mut parts = rest.splitn(2, '/');
+    let name = unescape(parts.next().unwrap_or_default());
+    let field = parts.next();
+    let index = config.profiles.iter().position(|p| p.name == name);
+
+    match (field, index) {
+        (None, Some(i)) if value.is_null() => {
+            config.profiles.remove(i);
+            if config.active_profile.as_ref() == Some(&name) {
+                config.active_profile = None;
+            }
+        }
+        (None, None) if !value.is_null() => config.profiles.push(ProfileConfig {
+            name,
+            rule: None,
+            monitors: Vec::new(),
+            groups: Vec::new(),
+        }),
+        (Some("rule"), Some(i)) => {
+            config.profiles[i].rule =
+                serde_json::from_value(value.clone()).map_err(|e| format!("rule: {}", e))?;
+        }
+        (Some(field), Some(i)) => {
+            // monitor/<monitor>/<field>, a monitor the profile doesn't have here
+            // joins it from the connected ones with its local settings
+            let mut parts = field.splitn(3, '/');
+            if let (Some("monitor"), Some(monitor), Some(field)) =
+                (parts.next(), parts.next(), parts.next())
+            {
+                let is_it = |m: &MonitorConfig| {
+                    m.fingerprint.as_ref().map(monitor_key).as_deref() == Some(monitor)
+                };
+                let monitors = &mut config.profiles[i].monitors;
+                if !monitors.iter().any(is_it) {
+                    match connected.iter().find(|m| is_it(m)) {
+                        Some(m) => monitors.push(m.clone()),
+                        None => return Ok(false),
+                    }
+                }
+                for m in monitors.iter_mut().filter(|m| is_it(m)) {
+                    set_monitor_field(m, field, value)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
+// Take over settings from `merge`. Profiles come before their settings, keys are
+// sorted that way. Returns the settings still waiting for their monitor.
+pub fn apply(
+    config: &mut OsicConfig,
+    changed: &BTreeMap<String, Value>,
+    connected: &[MonitorConfig],
+) -> BTreeMap<String, Value> {
+    let mut waiting = BTreeMap::new();
+    for (key, value) in changed {
+        match apply_field(config, key, value, connected) {
+            Ok(true) => {}
+            Ok(false) => {
+                waiting.insert(key.clone(), value.clone());
+            }
+            Err(e) => println!("Sync {} Error: {}", key, e),
+        }
+    }
+    waiting
+}
+
+// Everything of one round but the files: the settings waiting for a monitor are tried
+// again first, so a monitor that turned up takes them instead of passing its own
+// settings off as changes. Then the other machines are merged into `config`.
+pub fn merge_config(
+    state: &mut SyncState,
+    config: &mut OsicConfig,
+    remotes: &[SyncDoc],
+    connected: &[MonitorConfig],
+    now: u64,
+) -> SyncReport {
+    let pending = std::mem::take(&mut state.pending);
+    state.pending = apply(config, &pending, connected);
+    let mut taken = pending.into_keys().collect::<BTreeSet<String>>();
+
+    let local = local_fields(config, state);
+    let (changed, conflicts) = merge(state, &local, remotes, now);
+    state.pending.extend(apply(config, &changed, connected));
+    taken.extend(changed.into_keys());
+    SyncReport {
+        changed: taken
+            .into_iter()
+            .filter(|k| !state.pending.contains_key(k))
+            .collect(),
+        conflicts,
+    }
+}
+
+fn state_path() -> PathBuf {
+    cache::os_config_folder().join("sync.json")
+}
+
+// A new machine id is written right away, the shared folder knows this machine by it.
+pub fn load_state() -> SyncState {
+    let state = fs::read_to_string(state_path())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok());
+    match state {
+        Some(state) => state,
+        None => {
+            let state = SyncState::new(&format!("{:016x}", fastrand::u64(..)));
+            if let Err(e) = write_state(&state) {
+                println!("{}", e);
+            }
+            state
+        }
+    }
+}
+
+pub fn write_state(state: &SyncState) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(state).unwrap();
+    fs::create_dir_all(cache::os_config_folder())
+        .and_then(|_| store::write_atomic(&state_path(), text.as_bytes()))
+        .map_err(|e| format!("Write sync state Error: {}", e))
+}
+
+// The files of the other machines. One that doesn't parse is probably still being
+// copied and is read on the next round.
+fn read_docs(dir: &Path, machine: &str) -> Vec<SyncDoc> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut docs = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let doc: SyncDoc = match fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+        {
+            Some(doc) => doc,
+            None => continue,
+        };
+        if doc.version > SYNC_VERSION {
+            println!(
+                "Sync file {} was written by a newer Osic, skipping it",
+                path.display()
+            );
+            continue;
+        }
+        if doc.machine != machine {
+            docs.push(doc);
+        }
+    }
+    docs
+}
+
+// One round with the shared folder: merge the other machines into `config` and
+// leave this machine's view there for them. Nothing happens without a folder.
+// `connected` are the settings of the monitors connected now.
+pub fn sync(
+    config: &mut OsicConfig,
+    connected: &[MonitorConfig],
+    now: u64,
+) -> Result<Option<SyncReport>, String> {
+    let mut state = load_state();
+    let folder = match &state.folder {
+        Some(folder) => folder.clone(),
+        None => return Ok(None),
+    };
+    if !folder.is_dir() {
+        return Err(format!("Sync folder not found: {}", folder.display()));
+    }
+    let dir = folder.join(SYNC_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Sync folder Error: {}", e))?;
+
+    let docs = read_docs(&dir, &state.machine);
+    let report = merge_config(&mut state, config, &docs, connected, now);
+    for c in &report.conflicts {
+        println!(
+            "Sync conflict on {}: {} here, {} on {}, keeping {}",
+            c.key, c.local, c.remote, c.remote_machine, c.kept
+        );
+    }
+
+    let doc = SyncDoc {
+        version: SYNC_VERSION,
+        machine: state.machine.clone(),
+        fields: state.fields.clone(),
+    };
+    let text = serde_json::to_string_pretty(&doc).unwrap();
+    store::write_atomic(
+        &dir.join(format!("{}.json", state.machine)),
+        text.as_bytes(),
+    )
+    .map_err(|e| format!("Write sync file Error: {}", e))?;
+    write_state(&state)?;
+    Ok(Some(report))
+}
+
+// Sync with the shared folder for the lifetime of the app.
+pub fn spawn(app: AppHandle, handle: Arc<Mutex<DisplayHandle>>) {
+    thread::spawn(move || loop {
+        let result = handle.lock().unwrap().sync_shared();
+        match result {
+            Ok(Some(report)) if !report.changed.is_empty() || !report.conflicts.is_empty() => {
+                if !report.changed.is_empty() {
+                    tray::update_menu(&app, &handle.lock().unwrap().profiles());
+                }
+                let _ = app.emit_all(SETTINGS_SYNCED, report);
+            }
+            Ok(_) => {}
+            Err(e) => println!("Sync Error: {}", e),
+        }
+        thread::sleep(SYNC_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::{picture_id, PictureMarks};
+    use crate::core::display::Fits;
+    use crate::core::profile::ProfileRule;
+
+    fn monitor(device_id: &str, serial: u32, album: &str) -> MonitorConfig {
+        let mut m: MonitorConfig =
+            serde_json::from_value(serde_json::json!({ "deviceId": device_id })).unwrap();
+        m.album_path = Some(PathBuf::from(album));
+        m.fingerprint = Some(MonitorFingerprint {
+            manufacturer: "DEL".to_string(),
+            product_code: 0xa0b1,
+            serial,
+            ..Default::default()
+        });
+        m
+    }
+
+    // One simulated machine, its files are handed to the others directly.
+    struct Machine {
+        state: SyncState,
+        config: OsicConfig,
+        connected: Vec<MonitorConfig>,
+    }
+
+    impl Machine {
+        fn new(machine: &str, connected: Vec<MonitorConfig>) -> Self {
+            Self {
+                state: SyncState::new(machine),
+                config: OsicConfig::default(),
+                connected,
+            }
+        }
+
+        fn doc(&self) -> SyncDoc {
+            SyncDoc {
+                version: SYNC_VERSION,
+                machine: self.state.machine.clone(),
+                fields: self.state.fields.clone(),
+            }
+        }
+
+        fn sync(&mut self, others: &[&Machine], now: u64) -> SyncReport {
+            let docs = others.iter().map(|m| m.doc()).collect::<Vec<SyncDoc>>();
+            merge_config(
+                &mut self.state,
+                &mut self.config,
+                &docs,
+                &self.connected,
+                now,
+            )
+        }
+
+        fn profile(&mut self, name: &str) -> &mut ProfileConfig {
+            self.config
+                .profiles
+                .iter_mut()
+                .find(|p| p.name == name)
+                .unwrap()
+        }
+    }
+
+    fn work(a: &Machine) -> ProfileConfig {
+        let mut left = a.connected[0].clone();
+        left.fit = Fits::Center;
+        left.nickname = Some("Left".to_string());
+        let mut right = a.connected[1].clone();
+        right.fit = Fits::Tile;
+        ProfileConfig {
+            name: "Work".to_string(),
+            rule: Some(ProfileRule::Subset),
+            monitors: vec![left, right],
+            groups: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn profile_takes_the_monitors_of_the_other_machine() {
+        let mut a = Machine::new(
+            "a",
+            vec![monitor("DP-1", 1, "/a/pics"), monitor("DP-2", 2, "/a/more")],
+        );
+        let mut b = Machine::new("b", vec![monitor("HDMI-A-1", 1, "/b/pics")]);
+        a.config.profiles.push(work(&a));
+        assert!(a.sync(&[], 1000).changed.is_empty());
+
+        // the profile is new here, the connected monitor joins it with its own paths
+        let report = b.sync(&[&a], 2000);
+        assert!(report.changed.contains(&"profile/Work".to_string()));
+        assert!(report.conflicts.is_empty());
+        let p = b.profile("Work").clone();
+        assert_eq!(p.rule, Some(ProfileRule::Subset));
+        assert_eq!(p.monitors.len(), 1);
+        assert_eq!(p.monitors[0].device_id, "HDMI-A-1");
+        assert_eq!(p.monitors[0].album_path, Some(PathBuf::from("/b/pics")));
+        assert_eq!(p.monitors[0].fit, Fits::Center);
+        assert_eq!(p.monitors[0].nickname, Some("Left".to_string()));
+
+        // the other monitor waits until it is plugged in here
+        assert_eq!(b.state.pending.len(), MONITOR_FIELDS.len());
+        b.connected.push(monitor("HDMI-A-2", 2, "/b/more"));
+        let report = b.sync(&[&a], 3000);
+        assert_eq!(report.changed.len(), MONITOR_FIELDS.len());
+        assert!(b.state.pending.is_empty());
+        let p = b.profile("Work").clone();
+        assert_eq!(p.monitors[1].device_id, "HDMI-A-2");
+        assert_eq!(p.monitors[1].fit, Fits::Tile);
+        // taken over, not changed here
+        assert!(b.state.fields.values().all(|f| f.machine == "a"));
+
+        // a change here goes back, the paths stay on each machine
+        b.profile("Work").monitors[0].fit = Fits::Stretch;
+        b.sync(&[&a], 4000);
+        let report = a.sync(&[&b], 5000);
+        let key = "profile/Work/monitor/DEL-a0b1-1/fit".to_string();
+        assert_eq!(report.changed, vec![key]);
+        let p = a.profile("Work").clone();
+        assert_eq!(p.monitors[0].fit, Fits::Stretch);
+        assert_eq!(p.monitors[0].device_id, "DP-1");
+        assert_eq!(p.monitors[0].album_path, Some(PathBuf::from("/a/pics")));
+        assert!(a.sync(&[&b], 6000).changed.is_empty());
+    }
+
+    #[test]
+    fn changes_on_both_machines_end_up_the_same() {
+        let mut a = Machine::new(
+            "a",
+            vec![monitor("DP-1", 1, "/a/pics"), monitor("DP-2", 2, "/a/more")],
+        );
+        let mut b = Machine::new("b", a.connected.clone());
+        a.config.profiles.push(work(&a));
+        a.sync(&[], 1000);
+        b.sync(&[&a], 2000);
+
+        // both change the rule before they see each other
+        a.profile("Work").rule = Some(ProfileRule::Exact);
+        a.sync(&[], 3000);
+        b.profile("Work").rule = Some(ProfileRule::Count(2));
+        b.sync(&[], 4000);
+
+        let conflict = SyncConflict {
+            key: "profile/Work/rule".to_string(),
+            local: serde_json::json!("exact"),
+            remote: serde_json::json!({ "count": 2 }),
+            remote_machine: "b".to_string(),
+            kept: serde_json::json!({ "count": 2 }),
+        };
+        assert_eq!(a.sync(&[&b], 5000).conflicts, vec![conflict]);
+        // a already settled on b's rule, b has nothing to report
+        let report = b.sync(&[&a], 6000);
+        assert!(report.conflicts.is_empty() && report.changed.is_empty());
+        assert_eq!(a.profile("Work").rule, Some(ProfileRule::Count(2)));
+        assert_eq!(b.profile("Work").rule, Some(ProfileRule::Count(2)));
+
+        // a later change on top of it is no conflict
+        a.profile("Work").rule = None;
+        a.sync(&[&b], 7000);
+        assert!(b.sync(&[&a], 8000).conflicts.is_empty());
+        assert_eq!(b.profile("Work").rule, None);
+
+        // removed on one machine, removed on the other
+        a.config.profiles.clear();
+        a.sync(&[&b], 9000);
+        b.sync(&[&a], 10000);
+        assert!(b.config.profiles.is_empty());
+        a.sync(&[&b], 11000);
+        assert_eq!(a.state.fields, b.state.fields);
+    }
+
+    #[test]
+    fn picture_marks_follow_the_picture() {
+        let mut a = Machine::new("a", Vec::new());
+        let mut b = Machine::new("b", Vec::new());
+        let lake = picture_id(Path::new("/home/a/Pictures/Nature/lake.jpg")).unwrap();
+        assert_eq!(lake, "Nature/lake.jpg");
+        a.config.favorites.insert(lake.clone());
+        a.config.ratings.insert(lake.clone(), 4);
+        a.config.blacklist.insert("Nature/dark.jpg".to_string());
+        a.sync(&[], 1000);
+
+        let report = b.sync(&[&a], 2000);
+        assert_eq!(
+            report.changed,
+            vec![
+                "blacklist/Nature%2Fdark.jpg",
+                "favorite/Nature%2Flake.jpg",
+                "rating/Nature%2Flake.jpg",
+            ]
+        );
+        assert_eq!(
+            b.config.marks(&lake),
+            PictureMarks {
+                favorite: true,
+                rating: Some(4),
+                blacklisted: false,
+            }
+        );
+        assert!(b.config.marks("Nature/dark.jpg").blacklisted);
+
+        // unmarked on one machine, unmarked on the other
+        b.config.favorites.clear();
+        b.config.ratings.clear();
+        b.config.blacklist.clear();
+        b.sync(&[&a], 3000);
+        assert_eq!(a.sync(&[&b], 4000).changed.len(), 3);
+        assert_eq!(a.config, OsicConfig::default());
+
+        let mut config = OsicConfig::default();
+        let changed = BTreeMap::from([("rating/Nature%2Flake.jpg".to_string(), Value::from(9))]);
+        assert!(apply(&mut config, &changed, &[]).is_empty());
+        assert!(config.ratings.is_empty());
+    }
+}
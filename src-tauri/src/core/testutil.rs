@@ -0,0 +1,104 @@
+use std::env;
+#[cfg(unix)]
+use std::ffi::OsString;
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::{Mutex, MutexGuard};
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+
+// A directory under the system temp dir, removed with everything in it when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("osic-test-{}-{:x}", name, fastrand::u64(..)));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// PATH belongs to the whole test process, tests that put tools on it take turns.
+#[cfg(unix)]
+static PATH_LOCK: Mutex<()> = Mutex::new(());
+
+// Shell scripts standing in for the tools a backend runs. They are found first on
+// PATH until this is dropped. A script appends to `$LOG` to show how it was called.
+#[cfg(unix)]
+pub struct Stubs {
+    pub dir: PathBuf,
+    path: Option<OsString>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+#[cfg(unix)]
+impl Stubs {
+    pub fn new(name: &str) -> Self {
+        let lock = PATH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("osic-test-{}-{:x}", name, fastrand::u64(..)));
+        fs::create_dir_all(&dir).unwrap();
+        let path = env::var_os("PATH");
+        let mut dirs = vec![dir.clone()];
+        dirs.extend(env::split_paths(&path.clone().unwrap_or_default()));
+        env::set_var("PATH", env::join_paths(dirs).unwrap());
+        Self {
+            dir,
+            path,
+            _lock: lock,
+        }
+    }
+
+    pub fn tool(&self, name: &str, script: &str) {
+        let path = self.dir.join(name);
+        let log = self.dir.join("log");
+        fs::write(
+            &path,
+            format!("#!/bin/sh\nLOG='{}'\n{}\n", log.display(), script),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    pub fn log(&self) -> String {
+        fs::read_to_string(self.dir.join("log")).unwrap_or_default()
+    }
+
+    // For tools left running in the background: the log once it has `lines` lines,
+    // or whatever it has after a few seconds.
+    pub fn wait_log(&self, lines: usize) -> String {
+        for _ in 0..100 {
+            let log = self.log();
+            if log.lines().count() >= lines {
+                return log;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        self.log()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Stubs {
+    fn drop(&mut self) {
+        match &self.path {
+            Some(path) => env::set_var("PATH", path),
+            None => env::remove_var("PATH"),
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -2,7 +2,7 @@ use image::{imageops::FilterType, *};
 use std::fs::File;
 use std::io;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // pub fn imgbuff_to_egui_imgdata<P, Container>(i: ImageBuffer<P, Container>) -> ImageData
 // where
@@ -77,3 +77,77 @@ pub fn get_sys_time_in_secs() -> u64 {
         .unwrap()
         .as_secs()
 }
+
+pub fn get_sys_time_in_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
+
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for b in path.to_string_lossy().bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> String {
+    let s = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] == b'%' && i + 2 < s.len() {
+            let hex = std::str::from_utf8(&s[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                bytes.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        bytes.push(s[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+// Physical size from an xrandr WIDTH/mmxHEIGHT/mm+X+Y geometry.
+pub fn parse_geometry_mm(s: &str) -> Option<(u32, u32)> {
+    let size = &s[..s.find(|c| c == '+' || c == '-')?];
+    let (w, h) = size.split_once('x')?;
+    Some((
+        w.split_once('/')?.1.parse().ok()?,
+        h.split_once('/')?.1.parse().ok()?,
+    ))
+}
+
+// Clockwise degrees of a wayland output transform such as "90" or "flipped-270".
+pub fn transform_rotation(transform: &str) -> u32 {
+    match transform
+        .trim_start_matches("flipped")
+        .trim_start_matches('-')
+    {
+        "90" => 90,
+        "180" => 180,
+        "270" => 270,
+        _ => 0,
+    }
+}
+
+// "2560/597x1440/336+0+0" or "1920x1080+1920+0"
+pub fn parse_geometry(s: &str) -> Option<(i32, i32, i32, i32)> {
+    let (size, offset) = s.split_at(s.find(|c| c == '+' || c == '-')?);
+    let (w, h) = size.split_once('x')?;
+    let w = w.split('/').next()?.parse().ok()?;
+    let h = h.split('/').next()?.parse().ok()?;
+
+    let sign_at = offset[1..].find(|c| c == '+' || c == '-')? + 1;
+    let (x, y) = offset.split_at(sign_at);
+    Some((w, h, x.parse().ok()?, y.parse().ok()?))
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+
+use super::config;
+use super::display::DisplayHandle;
+use super::store;
+use crate::tray;
+
+// Not every backend can tell when monitors come and go, polling works for all of them.
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub const MONITORS_CHANGED: &str = "monitors_changed";
+
+pub fn spawn(app: AppHandle, handle: Arc<Mutex<DisplayHandle>>) {
+    thread::spawn(move || {
+        let mut last_error: Option<String> = None;
+        // None until the first look, so a profile is picked for the monitors found at start
+        let mut topology = None;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let displays = {
+                let mut handle = handle.lock().unwrap();
+                let changed = match handle.refresh_monitors() {
+                    Ok(changed) => changed,
+                    Err(e) => {
+                        if last_error.as_ref() != Some(&e) {
+                            println!("Refresh monitors Error: {}", e);
+                            last_error = Some(e);
+                        }
+                        continue;
+                    }
+                };
+
+                let mut switched = false;
+                let now = handle.topology();
+                if topology.as_ref() != Some(&now) {
+                    topology = Some(now);
+                    match handle.apply_profile_for_topology() {
+                        Ok(Some(_)) => {
+                            tray::update_menu(&app, &handle.profiles());
+                            switched = true;
+                        }
+                        Ok(None) => {}
+                        Err(e) => println!("Automatic profile Error: {}", e),
+                    }
+                }
+                if !changed && !switched {
+                    continue;
+                }
+                handle.displays()
+            };
+            last_error = None;
+            let _ = app.emit_all(MONITORS_CHANGED, displays);
+        }
+    });
+}
+
+// Apply hand edits of the config file while the app runs. A file that doesn't parse
+// is reported and ignored until the next save fixes it.
+pub fn spawn_config(app: AppHandle, handle: Arc<Mutex<DisplayHandle>>) {
+    thread::spawn(move || {
+        let path = config::config_path();
+        let mut modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        loop {
+            thread::sleep(CONFIG_POLL_INTERVAL);
+
+            let now = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            if now == modified {
+                continue;
+            }
+            modified = now;
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+            if store::is_known(&text) {
+                continue;
+            }
+            let config = match config::parse(&text) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            };
+
+            println!("Config edited, applying {}", path.display());
+            store::replace(config.clone(), &text);
+            let displays = {
+                let mut handle = handle.lock().unwrap();
+                handle.reload_config(config);
+                handle.displays()
+            };
+            let _ = app.emit_all(MONITORS_CHANGED, displays);
+        }
+    });
+}
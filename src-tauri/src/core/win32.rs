@@ -7,14 +7,83 @@ use std::os::windows::prelude::OsStringExt;
 use windows::Win32::Devices::Display::{
     DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig,
     DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME, DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO,
-    DISPLAYCONFIG_TARGET_DEVICE_NAME, QDC_ONLY_ACTIVE_PATHS,
+    DISPLAYCONFIG_ROTATION_ROTATE180, DISPLAYCONFIG_ROTATION_ROTATE270,
+    DISPLAYCONFIG_ROTATION_ROTATE90, DISPLAYCONFIG_TARGET_DEVICE_NAME, QDC_ONLY_ACTIVE_PATHS,
 };
-use windows::Win32::Foundation::{ERROR_SUCCESS, WIN32_ERROR};
+use windows::Win32::Foundation::{COLORREF, ERROR_SUCCESS, POINT, WIN32_ERROR};
+use windows::Win32::Graphics::Gdi::{MonitorFromPoint, MONITOR_DEFAULTTONEAREST};
+use windows::Win32::UI::Shell::GetScaleFactorForMonitor;
 use windows::Win32::System::Com::*;
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_BINARY};
 use windows::Win32::UI::Shell::DesktopWallpaper;
 use windows::Win32::UI::Shell::IDesktopWallpaper;
 
-use super::display::Monitor;
+use super::backend::WallpaperBackend;
+use super::display::{Fits, Monitor};
+use super::edid::MonitorFingerprint;
+
+// The monitor device path is \\?\DISPLAY#<hardware id>#<instance>#{guid}, the EDID
+// is stored under the same hardware id and instance in the registry.
+fn read_registry_edid(device_path: &str) -> Option<Vec<u8>> {
+    let parts = device_path.split('#').collect::<Vec<&str>>();
+    if parts.len() < 3 {
+        return None;
+    }
+    let key = HSTRING::from(format!(
+        "SYSTEM\\CurrentControlSet\\Enum\\DISPLAY\\{}\\{}\\Device Parameters",
+        parts[1], parts[2]
+    ));
+    let value = HSTRING::from("EDID");
+
+    // the size first, extension blocks make it 256 bytes or more
+    let mut size = 0u32;
+    let ret = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            &key,
+            &value,
+            RRF_RT_REG_BINARY,
+            None,
+            None,
+            Some(&mut size),
+        )
+    };
+    if ret != ERROR_SUCCESS || size == 0 {
+        return None;
+    }
+    let mut data = vec![0u8; size as usize];
+    let ret = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            &key,
+            &value,
+            RRF_RT_REG_BINARY,
+            None,
+            Some(data.as_mut_ptr() as *mut _),
+            Some(&mut size),
+        )
+    };
+    if ret != ERROR_SUCCESS {
+        return None;
+    }
+    data.truncate(size as usize);
+    Some(data)
+}
+
+// Display scaling set in Windows settings, 1.0 is 100%.
+fn monitor_scale(monitor: &Monitor) -> f32 {
+    let center = POINT {
+        x: monitor.letf + monitor.width / 2,
+        y: monitor.top + monitor.height / 2,
+    };
+    unsafe {
+        let hmonitor = MonitorFromPoint(center, MONITOR_DEFAULTTONEAREST);
+        match GetScaleFactorForMonitor(hmonitor) {
+            Ok(f) if f.0 > 0 => f.0 as f32 / 100.0,
+            _ => 1.0,
+        }
+    }
+}
 
 // Convert a UCS2 wide char string to a Rust String
 fn wstr(slice: &[u16]) -> String {
@@ -60,26 +129,63 @@ impl Win32API {
             }
         }
     }
+}
 
-    pub fn set_wallpaper(
-        &self,
-        monitor_id: &str,
-        wallpaper: &str,
-    ) -> Result<(), windows::core::Error> {
+impl WallpaperBackend for Win32API {
+    fn name(&self) -> &'static str {
+        "win32"
+    }
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
         unsafe {
-            self.wm.SetWallpaper(
-                PCWSTR::from_raw(HSTRING::from(monitor_id).as_ptr()),
-                PCWSTR::from_raw(HSTRING::from(wallpaper).as_ptr()),
-            )
+            self.wm
+                .SetWallpaper(
+                    PCWSTR::from_raw(HSTRING::from(monitor_id).as_ptr()),
+                    PCWSTR::from_raw(HSTRING::from(wallpaper).as_ptr()),
+                )
+                .map_err(|e| format!("SetWallpaper Error: {}", e))
         }
     }
 
-    pub fn set_fit(&self, fits: i32) -> Result<(), windows::core::Error> {
-        let i = windows::Win32::UI::Shell::DESKTOP_WALLPAPER_POSITION(fits);
-        unsafe { self.wm.SetPosition(i) }
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String> {
+        unsafe {
+            let p = self
+                .wm
+                .GetWallpaper(PCWSTR::from_raw(HSTRING::from(monitor_id).as_ptr()))
+                .map_err(|e| format!("GetWallpaper Error: {}", e))?;
+            let wallpaper = p.to_string().unwrap_or_default();
+            CoTaskMemFree(Some(p.as_ptr() as _));
+            Ok(wallpaper)
+        }
+    }
+
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String> {
+        let i = windows::Win32::UI::Shell::DESKTOP_WALLPAPER_POSITION(fit as i32);
+        unsafe {
+            self.wm
+                .SetPosition(i)
+                .map_err(|e| format!("SetPosition Error: {}", e))
+        }
+    }
+
+    // COLORREF is 0x00BBGGRR
+    fn set_background_color(&mut self, color: u32) -> Result<(), String> {
+        let (r, g, b) = ((color >> 16) & 0xff, (color >> 8) & 0xff, color & 0xff);
+        unsafe {
+            self.wm
+                .SetBackgroundColor(COLORREF(b << 16 | g << 8 | r))
+                .map_err(|e| format!("SetBackgroundColor Error: {}", e))
+        }
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        let c = unsafe { self.wm.GetBackgroundColor() }
+            .map_err(|e| format!("GetBackgroundColor Error: {}", e))?;
+        let (b, g, r) = ((c.0 >> 16) & 0xff, (c.0 >> 8) & 0xff, c.0 & 0xff);
+        Ok(r << 16 | g << 8 | b)
     }
 
-    pub fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
         let mut monitors: Vec<Monitor> = Vec::<Monitor>::new();
 
         unsafe {
@@ -154,13 +260,31 @@ impl Win32API {
 
             let name = wstr(&target_name.monitorFriendlyDeviceName);
             let device_path = wstr(&target_name.monitorDevicePath);
+            let rotation = match path.targetInfo.rotation {
+                DISPLAYCONFIG_ROTATION_ROTATE90 => 90,
+                DISPLAYCONFIG_ROTATION_ROTATE180 => 180,
+                DISPLAYCONFIG_ROTATION_ROTATE270 => 270,
+                _ => 0,
+            };
+            let refresh = path.targetInfo.refreshRate;
             for monitor in &mut monitors {
                 if monitor.device_id == device_path {
                     monitor.name = name.clone();
+                    monitor.rotation = rotation;
+                    if refresh.Denominator > 0 {
+                        monitor.refresh_rate =
+                            refresh.Numerator as f32 / refresh.Denominator as f32;
+                    }
                 }
             }
         }
 
+        for monitor in &mut monitors {
+            monitor.fingerprint =
+                read_registry_edid(&monitor.device_id).and_then(|e| MonitorFingerprint::parse(&e));
+            monitor.scale = monitor_scale(monitor);
+        }
+
         return Ok(monitors);
     }
 }
@@ -0,0 +1,639 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use image::RgbaImage;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::randr::{ConnectionExt as _, GetScreenResourcesCurrentReply, Rotation};
+use x11rb::protocol::xproto::{
+    AtomEnum, ChangeWindowAttributesAux, CloseDown, ConnectionExt as _, CreateGCAux, ImageFormat,
+    ImageOrder, PropMode, Screen,
+};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+use super::backend::WallpaperBackend;
+use super::display::{Fits, Monitor};
+use super::edid::{self, MonitorFingerprint};
+use super::render;
+
+// Plain X11 window managers (i3, bspwm, openbox...) have no wallpaper service, the
+// wallpaper is the root window background. Every monitor is painted into one pixmap
+// which is published the way xsetroot/feh do, so compositors pick it up as well.
+pub struct X11Backend {
+    fit: Fits,
+    background: u32,
+    wallpapers: HashMap<String, String>,
+}
+
+fn x11_err<E: std::fmt::Display>(e: E) -> String {
+    format!("X11 Error: {}", e)
+}
+
+fn connect() -> Result<(RustConnection, usize), String> {
+    x11rb::connect(None).map_err(|e| format!("Connect X display Error: {}", e))
+}
+
+// EDID output property of the first output behind a RandR monitor.
+fn output_fingerprint(conn: &RustConnection, output: u32) -> Option<MonitorFingerprint> {
+    let atom = conn.intern_atom(true, b"EDID").ok()?.reply().ok()?.atom;
+    if atom == 0 {
+        return None;
+    }
+    let reply = conn
+        .randr_get_output_property(output, atom, AtomEnum::ANY, 0, 256, false, false)
+        .ok()?
+        .reply()
+        .ok()?;
+    MonitorFingerprint::parse(&reply.data)
+}
+
+// Rotation and refresh rate of the CRTC driving an output.
+fn output_mode(
+    conn: &RustConnection,
+    resources: &GetScreenResourcesCurrentReply,
+    output: u32,
+) -> Option<(u32, f32)> {
+    let info = conn
+        .randr_get_output_info(output, resources.config_timestamp)
+        .ok()?
+        .reply()
+        .ok()?;
+    if info.crtc == 0 {
+        return None;
+    }
+    let crtc = conn
+        .randr_get_crtc_info(info.crtc, resources.config_timestamp)
+        .ok()?
+        .reply()
+        .ok()?;
+    let rotation = if crtc.rotation.contains(Rotation::ROTATE90) {
+        90
+    } else if crtc.rotation.contains(Rotation::ROTATE180) {
+        180
+    } else if crtc.rotation.contains(Rotation::ROTATE270) {
+        270
+    } else {
+        0
+    };
+    let refresh_rate = resources
+        .modes
+        .iter()
+        .find(|m| m.id == crtc.mode)
+        .filter(|m| m.htotal > 0 && m.vtotal > 0)
+        .map_or(0.0, |m| {
+            m.dot_clock as f32 / (m.htotal as f32 * m.vtotal as f32)
+        });
+    Some((rotation, refresh_rate))
+}
+
+fn list_monitors(conn: &RustConnection, screen: &Screen) -> Result<Vec<Monitor>, String> {
+    let mut monitors = Vec::new();
+    let resources = conn
+        .randr_get_screen_resources_current(screen.root)
+        .map_err(x11_err)?
+        .reply()
+        .ok();
+
+    let reply = conn
+        .randr_get_monitors(screen.root, true)
+        .map_err(x11_err)?
+        .reply();
+    if let Ok(reply) = reply {
+        for info in reply.monitors {
+            let name = conn
+                .get_atom_name(info.name)
+                .map_err(x11_err)?
+                .reply()
+                .map(|n| String::from_utf8_lossy(&n.name).to_string())
+                .unwrap_or_default();
+            let (x, y) = (info.x as i32, info.y as i32);
+            let (width, height) = (info.width as i32, info.height as i32);
+            let fingerprint = info
+                .outputs
+                .first()
+                .and_then(|&o| output_fingerprint(conn, o))
+                .or_else(|| edid::fingerprint_sysfs(&name));
+            let (rotation, refresh_rate) = match (&resources, info.outputs.first()) {
+                (Some(r), Some(&o)) => output_mode(conn, r, o).unwrap_or_default(),
+                _ => (0, 0.0),
+            };
+            monitors.push(Monitor {
+                fingerprint,
+                rotation,
+                refresh_rate,
+                width_mm: info.width_in_millimeters,
+                height_mm: info.height_in_millimeters,
+                name: name.clone(),
+                device_id: name,
+                letf: x,
+                top: y,
+                right: x + width,
+                bottom: y + height,
+                width,
+                height,
+                ..Default::default()
+            });
+        }
+    }
+
+    // no RandR 1.5, the whole screen is one monitor
+    if monitors.is_empty() {
+        let (width, height) = (
+            screen.width_in_pixels as i32,
+            screen.height_in_pixels as i32,
+        );
+        monitors.push(Monitor {
+            name: "Screen".to_string(),
+            device_id: "screen".to_string(),
+            right: width,
+            bottom: height,
+            width,
+            height,
+            ..Default::default()
+        });
+    }
+    Ok(monitors)
+}
+
+// Z pixmap data for a 24/32 bit TrueColor visual, 4 bytes per pixel.
+fn pixmap_data(img: &RgbaImage, order: ImageOrder) -> Vec<u8> {
+    let mut data = Vec::with_capacity((img.width() * img.height() * 4) as usize);
+    for p in img.pixels() {
+        let [r, g, b, _] = p.0;
+        if order == ImageOrder::LSB_FIRST {
+            data.extend_from_slice(&[b, g, r, 0]);
+        } else {
+            data.extend_from_slice(&[0, r, g, b]);
+        }
+    }
+    data
+}
+
+impl X11Backend {
+    pub fn new() -> Self {
+        Self {
+            fit: Fits::Fill,
+            background: 0,
+            wallpapers: HashMap::new(),
+        }
+    }
+
+    // The whole root window: every monitor's picture at its place, or one picture
+    // over all of them for `Fits::Span`.
+    fn render_root(
+        &self,
+        monitors: &[Monitor],
+        width: u32,
+        height: u32,
+    ) -> Result<RgbaImage, String> {
+        let background = render::rgba(self.background);
+        let layers = monitors
+            .iter()
+            .filter_map(|m| {
+                self.wallpapers
+                    .get(&m.device_id)
+                    .map(|w| (m.device_id.clone(), PathBuf::from(w), self.fit.clone()))
+            })
+            .collect::<Vec<(String, PathBuf, Fits)>>();
+        let mut canvas = RgbaImage::from_pixel(width, height, background);
+        match (&self.fit, layers.first()) {
+            // one picture stretched over the whole root window
+            (Fits::Span, Some((_, path, _))) => {
+                let img = image::open(path)
+                    .map_err(|e| format!("Open image {} Error: {}", path.display(), e))?;
+                canvas = render::render_fit(&img, width, height, &Fits::Fill, background);
+            }
+            _ => render::compose_into(&mut canvas, 0, 0, monitors, &layers, background)?,
+        }
+        Ok(canvas)
+    }
+
+    fn apply(&self) -> Result<(), String> {
+        let (conn, screen_num) = connect()?;
+        let screen = conn.setup().roots[screen_num].clone();
+        if screen.root_depth != 24 && screen.root_depth != 32 {
+            return Err(format!("Unsupported root depth: {}", screen.root_depth));
+        }
+        let monitors = list_monitors(&conn, &screen)?;
+
+        let (width, height) = (screen.width_in_pixels, screen.height_in_pixels);
+        let canvas = self.render_root(&monitors, width as u32, height as u32)?;
+        let data = pixmap_data(&canvas, conn.setup().image_byte_order);
+
+        let root = screen.root;
+        let pixmap = conn.generate_id().map_err(x11_err)?;
+        conn.create_pixmap(screen.root_depth, pixmap, root, width, height)
+            .map_err(x11_err)?;
+        let gc = conn.generate_id().map_err(x11_err)?;
+        conn.create_gc(gc, pixmap, &CreateGCAux::new())
+            .map_err(x11_err)?;
+
+        let stride = width as usize * 4;
+        let rows = strip_rows(width, conn.maximum_request_bytes());
+        for (i, strip) in data.chunks(rows * stride).enumerate() {
+            conn.put_image(
+                ImageFormat::Z_PIXMAP,
+                pixmap,
+                gc,
+                width,
+                (strip.len() / stride) as u16,
+                0,
+                (i * rows) as i16,
+                0,
+                screen.root_depth,
+                strip,
+            )
+            .map_err(x11_err)?;
+        }
+        conn.free_gc(gc).map_err(x11_err)?;
+
+        publish(&RootWindow { conn: &conn, root }, pixmap)?;
+
+        // keep the pixmap after this connection is closed
+        conn.set_close_down_mode(CloseDown::RETAIN_PERMANENT)
+            .map_err(x11_err)?;
+        conn.get_input_focus()
+            .map_err(x11_err)?
+            .reply()
+            .map_err(x11_err)?;
+        Ok(())
+    }
+}
+
+// Rows per PutImage, one request can't exceed the maximum request length.
+fn strip_rows(width: u16, maximum_request_bytes: usize) -> usize {
+    (maximum_request_bytes.saturating_sub(64) / (width as usize * 4)).max(1)
+}
+
+// What publishing a root pixmap needs from the X server, apart from the connection
+// so the order of things can be checked without a server.
+trait RootPixmap {
+    fn atom(&self, name: &[u8]) -> Result<u32, String>;
+
+    fn pixmap_property(&self, atom: u32) -> Result<Option<u32>, String>;
+
+    fn set_pixmap_property(&self, atom: u32, pixmap: u32) -> Result<(), String>;
+
+    fn kill_client(&self, resource: u32) -> Result<(), String>;
+
+    // Show the pixmap as the root window background.
+    fn set_background(&self, pixmap: u32) -> Result<(), String>;
+}
+
+struct RootWindow<'a> {
+    conn: &'a RustConnection,
+    root: u32,
+}
+
+impl RootPixmap for RootWindow<'_> {
+    fn atom(&self, name: &[u8]) -> Result<u32, String> {
+        Ok(self
+            .conn
+            .intern_atom(false, name)
+            .map_err(x11_err)?
+            .reply()
+            .map_err(x11_err)?
+            .atom)
+    }
+
+    fn pixmap_property(&self, atom: u32) -> Result<Option<u32>, String> {
+        let reply = self
+            .conn
+            .get_property(false, self.root, atom, AtomEnum::PIXMAP, 0, 1)
+            .map_err(x11_err)?
+            .reply()
+            .map_err(x11_err)?;
+        Ok(reply.value32().and_then(|mut v| v.next()))
+    }
+
+    fn set_pixmap_property(&self, atom: u32, pixmap: u32) -> Result<(), String> {
+        self.conn
+            .change_property32(
+                PropMode::REPLACE,
+                self.root,
+                atom,
+                AtomEnum::PIXMAP,
+                &[pixmap],
+            )
+            .map_err(x11_err)?;
+        Ok(())
+    }
+
+    fn kill_client(&self, resource: u32) -> Result<(), String> {
+        self.conn.kill_client(resource).map_err(x11_err)?;
+        Ok(())
+    }
+
+    fn set_background(&self, pixmap: u32) -> Result<(), String> {
+        self.conn
+            .change_window_attributes(
+                self.root,
+                &ChangeWindowAttributesAux::new().background_pixmap(pixmap),
+            )
+            .map_err(x11_err)?;
+        self.conn
+            .clear_area(false, self.root, 0, 0, 0, 0)
+            .map_err(x11_err)?;
+        Ok(())
+    }
+}
+
+// Hand the pixmap out the way xsetroot and feh do: _XROOTPMAP_ID for compositors and
+// pseudo transparent terminals, ESETROOT_PMAP_ID for the next setter to free it.
+fn publish(root: &dyn RootPixmap, pixmap: u32) -> Result<(), String> {
+    let xroot = root.atom(b"_XROOTPMAP_ID")?;
+    let esetroot = root.atom(b"ESETROOT_PMAP_ID")?;
+
+    // the previous pixmap was kept alive by its client, release it like Esetroot does
+    if let Some(old) = root.pixmap_property(esetroot)? {
+        if old != pixmap {
+            let _ = root.kill_client(old);
+        }
+    }
+    root.set_pixmap_property(xroot, pixmap)?;
+    root.set_pixmap_property(esetroot, pixmap)?;
+    root.set_background(pixmap)
+}
+
+impl WallpaperBackend for X11Backend {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn get_monitor_device_path(&self) -> Result<Vec<Monitor>, String> {
+        let (conn, screen_num) = connect()?;
+        let screen = &conn.setup().roots[screen_num];
+        list_monitors(&conn, screen)
+    }
+
+    fn set_wallpaper(&mut self, monitor_id: &str, wallpaper: &str) -> Result<(), String> {
+        self.wallpapers
+            .insert(monitor_id.to_string(), wallpaper.to_string());
+        self.apply()
+    }
+
+    fn get_wallpaper(&self, monitor_id: &str) -> Result<String, String> {
+        Ok(self.wallpapers.get(monitor_id).cloned().unwrap_or_default())
+    }
+
+    fn set_fit(&mut self, fit: Fits) -> Result<(), String> {
+        self.fit = fit;
+        if self.wallpapers.is_empty() {
+            return Ok(());
+        }
+        self.apply()
+    }
+
+    fn set_background_color(&mut self, color: u32) -> Result<(), String> {
+        self.background = color & 0xffffff;
+        self.apply()
+    }
+
+    fn get_background_color(&self) -> Result<u32, String> {
+        Ok(self.background)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::fake::fake_monitor;
+    use crate::core::testutil::TempDir;
+    use image::Rgba;
+    use std::cell::RefCell;
+    use std::env;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    // An X server reduced to the root window's pixmap properties, every request
+    // is written down.
+    #[derive(Default)]
+    struct Root {
+        properties: RefCell<HashMap<u32, u32>>,
+        requests: RefCell<Vec<String>>,
+    }
+
+    impl RootPixmap for Root {
+        fn atom(&self, name: &[u8]) -> Result<u32, String> {
+            Ok(match name {
+                b"_XROOTPMAP_ID" => 1,
+                b"ESETROOT_PMAP_ID" => 2,
+                _ => 3,
+            })
+        }
+
+        fn pixmap_property(&self, atom: u32) -> Result<Option<u32>, String> {
+            Ok(self.properties.borrow().get(&atom).copied())
+        }
+
+        fn set_pixmap_property(&self, atom: u32, pixmap: u32) -> Result<(), String> {
+            self.requests
+                .borrow_mut()
+                .push(format!("property {} = {}", atom, pixmap));
+            self.properties.borrow_mut().insert(atom, pixmap);
+            Ok(())
+        }
+
+        fn kill_client(&self, resource: u32) -> Result<(), String> {
+            self.requests
+                .borrow_mut()
+                .push(format!("kill {}", resource));
+            Ok(())
+        }
+
+        fn set_background(&self, pixmap: u32) -> Result<(), String> {
+            self.requests
+                .borrow_mut()
+                .push(format!("background {}", pixmap));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn first_pixmap_is_published_everywhere() {
+        let root = Root::default();
+        publish(&root, 10).unwrap();
+        assert_eq!(
+            *root.requests.borrow(),
+            vec!["property 1 = 10", "property 2 = 10", "background 10"]
+        );
+    }
+
+    #[test]
+    fn previous_pixmap_is_freed() {
+        let root = Root::default();
+        publish(&root, 10).unwrap();
+        publish(&root, 20).unwrap();
+        assert_eq!(
+            root.requests.borrow()[3..],
+            [
+                "kill 10",
+                "property 1 = 20",
+                "property 2 = 20",
+                "background 20"
+            ]
+        );
+
+        // the same pixmap again is never killed
+        publish(&root, 20).unwrap();
+        assert!(!root.requests.borrow()[7..]
+            .iter()
+            .any(|r| r.starts_with("kill")));
+    }
+
+    #[test]
+    fn pixmap_byte_order() {
+        let img = RgbaImage::from_pixel(1, 1, Rgba([1, 2, 3, 255]));
+        assert_eq!(pixmap_data(&img, ImageOrder::LSB_FIRST), vec![3, 2, 1, 0]);
+        assert_eq!(pixmap_data(&img, ImageOrder::MSB_FIRST), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn strips_fit_in_a_request() {
+        // 4 MB requests, 1920 wide rows of 7680 bytes
+        let rows = strip_rows(1920, 4 * 1024 * 1024);
+        assert!(rows * 1920 * 4 + 64 <= 4 * 1024 * 1024);
+        assert!((rows + 1) * 1920 * 4 + 64 > 4 * 1024 * 1024);
+        // a row wider than a request still goes one at a time
+        assert_eq!(strip_rows(1920, 1000), 1);
+    }
+
+    fn picture(dir: &TempDir, name: &str, color: [u8; 4]) -> String {
+        let path = dir.path().join(name);
+        RgbaImage::from_pixel(8, 8, Rgba(color))
+            .save(&path)
+            .unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn every_monitor_gets_its_picture() {
+        let monitors = vec![
+            fake_monitor("DP-1", "Left", 0, 0, 40, 30),
+            fake_monitor("HDMI-1", "Right", 40, 10, 20, 20),
+        ];
+        let dir = TempDir::new("x11");
+        let mut backend = X11Backend::new();
+        backend.background = 0x0000ff;
+        backend.wallpapers.insert(
+            "DP-1".to_string(),
+            picture(&dir, "red.png", [255, 0, 0, 255]),
+        );
+        backend.wallpapers.insert(
+            "HDMI-1".to_string(),
+            picture(&dir, "green.png", [0, 255, 0, 255]),
+        );
+
+        let root = backend.render_root(&monitors, 60, 30).unwrap();
+        assert_eq!(root.get_pixel(5, 5).0, [255, 0, 0, 255]);
+        assert_eq!(root.get_pixel(50, 20).0, [0, 255, 0, 255]);
+        // not covered by a monitor
+        assert_eq!(root.get_pixel(50, 5).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn span_covers_the_whole_root() {
+        let monitors = vec![
+            fake_monitor("DP-1", "Left", 0, 0, 40, 30),
+            fake_monitor("HDMI-1", "Right", 40, 10, 20, 20),
+        ];
+        let dir = TempDir::new("x11");
+        let mut backend = X11Backend::new();
+        backend.fit = Fits::Span;
+        backend.wallpapers.insert(
+            "DP-1".to_string(),
+            picture(&dir, "red.png", [255, 0, 0, 255]),
+        );
+        let root = backend.render_root(&monitors, 60, 30).unwrap();
+        assert_eq!(root.get_pixel(50, 5).0, [255, 0, 0, 255]);
+    }
+
+    // A headless X server, killed when dropped.
+    struct Xvfb(Child);
+
+    impl Xvfb {
+        // None when Xvfb isn't installed. `-displayfd` prints the display number
+        // once the server accepts connections.
+        fn start() -> Option<(Self, String)> {
+            let mut child = Command::new("Xvfb")
+                .args([
+                    "-displayfd",
+                    "1",
+                    "-nolisten",
+                    "tcp",
+                    "-screen",
+                    "0",
+                    "64x48x24",
+                ])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut line = String::new();
+            BufReader::new(child.stdout.take()?)
+                .read_line(&mut line)
+                .ok()?;
+            Some((Self(child), format!(":{}", line.trim())))
+        }
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    // The first pixel of the published root pixmap, read back from the server.
+    fn root_pixel(display: &str) -> [u8; 3] {
+        let (conn, screen_num) = x11rb::connect(Some(display)).unwrap();
+        let screen = &conn.setup().roots[screen_num];
+        let root = RootWindow {
+            conn: &conn,
+            root: screen.root,
+        };
+        let atom = root.atom(b"_XROOTPMAP_ID").unwrap();
+        let pixmap = root.pixmap_property(atom).unwrap().unwrap();
+        let reply = conn
+            .get_image(ImageFormat::Z_PIXMAP, pixmap, 0, 0, 1, 1, !0)
+            .unwrap()
+            .reply()
+            .unwrap();
+        let d = &reply.data;
+        if conn.setup().image_byte_order == ImageOrder::LSB_FIRST {
+            [d[2], d[1], d[0]]
+        } else {
+            [d[1], d[2], d[3]]
+        }
+    }
+
+    #[test]
+    fn wallpaper_on_a_real_server() {
+        let (_server, display) = match Xvfb::start() {
+            Some(s) => s,
+            None => {
+                println!("Xvfb not found, skipping");
+                return;
+            }
+        };
+        // the backend connects to $DISPLAY, no other test does
+        env::set_var("DISPLAY", &display);
+
+        let dir = TempDir::new("x11");
+        let mut backend = X11Backend::new();
+        let monitors = backend.get_monitor_device_path().unwrap();
+        assert!(!monitors.is_empty());
+        let id = monitors[0].device_id.clone();
+
+        backend
+            .set_wallpaper(&id, &picture(&dir, "red.png", [255, 0, 0, 255]))
+            .unwrap();
+        assert_eq!(root_pixel(&display), [255, 0, 0]);
+
+        // the next wallpaper replaces the published pixmap
+        backend
+            .set_wallpaper(&id, &picture(&dir, "green.png", [0, 255, 0, 255]))
+            .unwrap();
+        assert_eq!(root_pixel(&display), [0, 255, 0]);
+    }
+}
@@ -11,9 +11,10 @@ use core::display::DisplayState;
 
 fn main() {
     let mut builder = tauri::Builder::default();
+    let display = DisplayState::new();
 
     builder = builder
-        .system_tray(SystemTray::new().with_menu(tray::build_menu()))
+        .system_tray(SystemTray::new().with_menu(tray::build_menu(&display.profiles())))
         .on_system_tray_event(move |app, event| match event {
             SystemTrayEvent::DoubleClick {
                 tray_id: _,
@@ -27,6 +28,7 @@ fn main() {
             }
             SystemTrayEvent::MenuItemClick { tray_id: _, id, .. } => match id.as_str() {
                 "quit" => {
+                    app.state::<DisplayState>().flush();
                     std::process::exit(0);
                 }
 
@@ -35,6 +37,13 @@ fn main() {
                     window.show().unwrap();
                     window.set_focus().unwrap();
                 }
+                id if id.starts_with(tray::PROFILE_PREFIX) => {
+                    let name = &id[tray::PROFILE_PREFIX.len()..];
+                    let display = app.state::<DisplayState>();
+                    if let Err(e) = display.apply_profile(app, name) {
+                        println!("Apply profile Error: {}", e);
+                    }
+                }
                 _ => {}
             },
             _ => {}
@@ -48,8 +57,47 @@ fn main() {
         });
 
     builder
-        .manage(DisplayState::new())
-        .invoke_handler(tauri::generate_handler![core::display::display_info])
+        .manage(display)
+        .setup(|app| {
+            let display = app.state::<DisplayState>();
+            display.restore_wallpapers();
+            display.watch(app.handle());
+            display.schedule(app.handle());
+            display.sync(app.handle());
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            core::display::display_info,
+            core::display::set_monitor_nickname,
+            core::display::set_monitor_bezel,
+            core::display::set_span_wallpaper,
+            core::display::apply_wallpaper,
+            core::display::set_monitor_mode,
+            core::display::set_monitor_fit,
+            core::display::set_monitor_album_path,
+            core::display::set_monitor_image,
+            core::display::set_monitor_slide_interval,
+            core::display::set_monitor_selector,
+            core::display::set_monitor_time_albums,
+            core::display::location_info,
+            core::display::set_location,
+            core::display::group_info,
+            core::display::save_monitor_group,
+            core::display::remove_monitor_group,
+            core::display::profile_info,
+            core::display::save_profile,
+            core::display::apply_profile,
+            core::display::remove_profile,
+            core::display::set_profile_rule,
+            core::display::export_settings,
+            core::display::inspect_settings_bundle,
+            core::display::import_settings,
+            core::display::picture_marks,
+            core::display::set_picture_marks,
+            core::display::sync_info,
+            core::display::set_sync_folder,
+            core::display::sync_now
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -1,8 +1,32 @@
-use tauri::{CustomMenuItem, SystemTrayMenu, SystemTrayMenuItem};
+use tauri::{AppHandle, CustomMenuItem, SystemTrayMenu, SystemTrayMenuItem, SystemTraySubmenu};
 
-pub fn build_menu() -> SystemTrayMenu {
-    SystemTrayMenu::new()
-        .add_item(CustomMenuItem::new("show".to_string(), "Show"))
-        .add_native_item(SystemTrayMenuItem::Separator)
+use crate::core::profile::ProfileSummary;
+
+// Menu ids of the profile entries, followed by the profile name.
+pub const PROFILE_PREFIX: &str = "profile:";
+
+pub fn build_menu(profiles: &[ProfileSummary]) -> SystemTrayMenu {
+    let mut menu = SystemTrayMenu::new().add_item(CustomMenuItem::new("show".to_string(), "Show"));
+    if !profiles.is_empty() {
+        let mut submenu = SystemTrayMenu::new();
+        for p in profiles {
+            let mut item = CustomMenuItem::new(format!("{}{}", PROFILE_PREFIX, p.name), &p.name);
+            if p.active {
+                item = item.selected();
+            }
+            submenu = submenu.add_item(item);
+        }
+        menu = menu
+            .add_native_item(SystemTrayMenuItem::Separator)
+            .add_submenu(SystemTraySubmenu::new("Profiles", submenu));
+    }
+    menu.add_native_item(SystemTrayMenuItem::Separator)
         .add_item(CustomMenuItem::new("quit".to_string(), "Quit"))
 }
+
+// Rebuild the menu after profiles were saved, removed or switched.
+pub fn update_menu(app: &AppHandle, profiles: &[ProfileSummary]) {
+    if let Err(e) = app.tray_handle().set_menu(build_menu(profiles)) {
+        println!("Tray menu Error: {}", e);
+    }
+}
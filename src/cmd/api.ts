@@ -0,0 +1,120 @@
+import { invoke } from "@tauri-apps/api/tauri";
+
+import type { Bezel, BundleInfo, Display, GroupMode, Location, MonitorGroup, PictureMarks, ProfileRule, ProfileSummary, SyncInfo, SyncReport, TimeAlbum } from ".";
+
+export type Mode = "Picture" | "SlidShow"
+export type Fit = "Fill" | "Fit" | "Stretch" | "Tile" | "Center" | "Span"
+
+// Every setter saves the change and returns the updated monitor.
+
+export const displayInfo = () => invoke<Display[]>("display_info")
+
+export const setMonitorNickname = (deviceId: string, nickname: string | null) =>
+    invoke<Display>("set_monitor_nickname", { deviceId, nickname })
+
+export const setMonitorBezel = (deviceId: string, bezel: Bezel) =>
+    invoke<Display>("set_monitor_bezel", { deviceId, bezel })
+
+export const setSpanWallpaper = (image: string) =>
+    invoke<Display[]>("set_span_wallpaper", { image })
+
+export const applyWallpaper = (deviceId: string) =>
+    invoke<Display>("apply_wallpaper", { deviceId })
+
+export const setMonitorMode = (deviceId: string, mode: Mode) =>
+    invoke<Display>("set_monitor_mode", { deviceId, mode })
+
+export const setMonitorFit = (deviceId: string, fit: Fit) =>
+    invoke<Display>("set_monitor_fit", { deviceId, fit })
+
+export const setMonitorAlbumPath = (deviceId: string, albumPath: string) =>
+    invoke<Display>("set_monitor_album_path", { deviceId, albumPath })
+
+export const setMonitorImage = (deviceId: string, image: string) =>
+    invoke<Display>("set_monitor_image", { deviceId, image })
+
+// a duration like "90s" or "2h30m", a cron line or "every weekday at 09:00",
+// the jitter is a duration as well
+export const setMonitorSlideInterval = (deviceId: string, slideInterval: string, jitter?: string) =>
+    invoke<Display>("set_monitor_slide_interval", { deviceId, slideInterval, jitter })
+
+export const setMonitorSelector = (
+    deviceId: string,
+    settings: { shuffle?: boolean, ratio?: boolean, ratioRange?: number },
+) => invoke<Display>("set_monitor_selector", { deviceId, ...settings })
+
+export const setMonitorTimeAlbums = (deviceId: string, timeAlbums: TimeAlbum[]) =>
+    invoke<Display>("set_monitor_time_albums", { deviceId, timeAlbums })
+
+export const locationInfo = () => invoke<Location | null>("location_info")
+
+// null clears the location
+export const setLocation = (location: Location | null) =>
+    invoke<Location | null>("set_location", {
+        latitude: location?.latitude ?? null,
+        longitude: location?.longitude ?? null,
+    })
+
+export const groupInfo = () => invoke<MonitorGroup[]>("group_info")
+
+// creates the group or replaces the one with the same name, returns the members
+// that got a new picture
+export const saveMonitorGroup = (group: {
+    name: string,
+    members: string[],
+    mode: GroupMode,
+    albumPath: string,
+    slideInterval: string,
+    jitter?: string,
+}) => invoke<Display[]>("save_monitor_group", group)
+
+export const removeMonitorGroup = (name: string) =>
+    invoke<void>("remove_monitor_group", { name })
+
+export const profileInfo = () => invoke<ProfileSummary[]>("profile_info")
+
+// saves what every monitor shows now under the name, replacing a profile with
+// the same name
+export const saveProfile = (name: string) =>
+    invoke<ProfileSummary[]>("save_profile", { name })
+
+// switches every monitor at once, nothing changes when one of them fails
+export const applyProfile = (name: string) =>
+    invoke<Display[]>("apply_profile", { name })
+
+export const removeProfile = (name: string) =>
+    invoke<ProfileSummary[]>("remove_profile", { name })
+
+// the profile is picked on its own whenever the connected monitors fit the rule,
+// null only switches by hand
+export const setProfileRule = (name: string, rule: ProfileRule | null) =>
+    invoke<ProfileSummary[]>("set_profile_rule", { name, rule })
+
+// the whole configuration in one file, album paths relative to their folders
+export const exportSettings = (path: string) =>
+    invoke<void>("export_settings", { path })
+
+export const inspectSettingsBundle = (path: string) =>
+    invoke<BundleInfo>("inspect_settings_bundle", { path })
+
+// monitors maps bundle device ids to connected ones, albumRoots replaces the
+// bundle's album folders in order ("" keeps one)
+export const importSettings = (path: string, monitors: Record<string, string>, albumRoots: string[]) =>
+    invoke<Display[]>("import_settings", { path, monitors, albumRoots })
+
+export const syncInfo = () => invoke<SyncInfo>("sync_info")
+
+// a Syncthing or network folder shared by the machines, null stops syncing
+export const setSyncFolder = (folder: string | null) =>
+    invoke<SyncInfo>("set_sync_folder", { folder })
+
+// syncs right away instead of waiting for the next round, also sent as the
+// "settings_synced" event
+export const syncNow = () => invoke<SyncReport>("sync_now")
+
+export const pictureMarks = (image: string) =>
+    invoke<PictureMarks>("picture_marks", { image })
+
+// replaces every mark of the picture
+export const setPictureMarks = (image: string, marks: PictureMarks) =>
+    invoke<PictureMarks>("set_picture_marks", { image, marks })
@@ -10,8 +10,53 @@ export type DisplayMeta = {
     right: number,
     top: number,
     width: number,
+    make: string,
+    model: string,
+    serial: string,
+    scale: number,
+    transform: string,
+    rotation: number,
+    refreshRate: number,
+    widthMm: number,
+    heightMm: number,
+    fingerprint: MonitorFingerprint | null,
 }
 
+export type MonitorFingerprint = {
+    manufacturer: string,
+    productCode: number,
+    serial: number,
+    serialText: string,
+    model: string,
+    widthMm: number,
+    heightMm: number,
+}
+
+export type SolarEvent = "Dawn" | "Sunrise" | "Sunset" | "Dusk"
+
+// shown from the event until the next one in the list
+export type TimeAlbum = {
+    event: SolarEvent,
+    albumPath: string,
+}
+
+export type Location = {
+    latitude: number,
+    longitude: number,
+}
+
+export type Bezel = {
+    left: number,
+    top: number,
+    right: number,
+    bottom: number,
+}
+
+// seconds
+export type SlideSchedule =
+    | { every: { seconds: number, jitter: number } }
+    | { cron: { expression: string, jitter: number } }
+
 export type DisplayBackgroundSelector = {
     path: string,
     ratio: boolean,
@@ -25,15 +70,88 @@ export type DisplayBackgroundSelector = {
 export type Display = {
     albumPath: string,
     deviceId: string,
+    nickname: string | null,
+    bezel: Bezel,
+    timeAlbums: TimeAlbum[],
     fit: string,
     image: string,
-    imageHistory: string[],
+    imageHistory: { path: string }[],
     meta: DisplayMeta,
     mode: string,
+    slideInterval: SlideSchedule,
+    slideTime: number,
     selector: DisplayBackgroundSelector
 }
 
 
+export type GroupMode = "Synchronized" | "Distributed"
+
+export type MonitorGroup = {
+    name: string,
+    members: string[],
+    mode: GroupMode,
+    albumPath: string,
+    slideInterval: SlideSchedule,
+    slideTime: number,
+    cursor: number,
+}
+
+// exact: just the profile's monitors, subset: at least them, count: any that many
+export type ProfileRule = "exact" | "subset" | { count: number }
+
+export type ProfileSummary = {
+    name: string,
+    active: boolean,
+    monitors: number,
+    rule: ProfileRule | null,
+}
+
+// what a settings bundle holds, to map its monitors and album folders before
+// importing it
+export type BundleInfo = {
+    monitors: {
+        deviceId: string,
+        nickname: string | null,
+        fingerprint: MonitorFingerprint | null,
+        // the connected monitor it goes to unless mapped otherwise
+        suggested: string | null,
+    }[],
+    albumRoots: { path: string, exists: boolean }[],
+    profiles: string[],
+}
+
+// settings synced through a shared folder, keys are "profile/<name>/..."
+export type SyncInfo = {
+    machine: string,
+    folder: string | null,
+    lastSync: number,
+}
+
+// a setting changed here and on another machine since they last synced, the
+// newer one is kept
+export type SyncConflict = {
+    key: string,
+    local: unknown,
+    remote: unknown,
+    remoteMachine: string,
+    kept: unknown,
+}
+
+export type SyncReport = {
+    changed: string[],
+    conflicts: SyncConflict[],
+}
+
+// kept by the picture's folder and file name, so they sync to machines that
+// have the album elsewhere
+export type PictureMarks = {
+    favorite: boolean,
+    // 1 to 5
+    rating: number | null,
+    // never picked by a slideshow or group
+    blacklisted: boolean,
+}
+
 export type DisplayItems = {
     id: number,
     label: string,
@@ -44,6 +162,8 @@ export type AppState = {
     displayList: Display[],
     displayItems: DisplayItems[],
     selectdDisplayIndex: number,
+    // why the monitors couldn't be listed, e.g. no wallpaper backend for the desktop
+    error?: string,
 }
 
 
@@ -3,6 +3,7 @@ import type { DocumentHead } from "@builder.io/qwik-city";
 
 
 import { invoke } from "@tauri-apps/api/tauri";
+import { listen } from "@tauri-apps/api/event";
 import type { AppState, Display } from "~/cmd";
 import { AppContextId } from "~/cmd/context";
 import { DisplaySelector } from "~/components/main/display/display-selector";
@@ -20,21 +21,42 @@ export default component$(() => {
   useContextProvider(AppContextId, state);
 
   const getItemList = $(async () => {
-    const res: Display[] = await invoke("display_info")
+    let res: Display[]
+    try {
+      res = await invoke("display_info")
+    } catch (e) {
+      state.error = String(e)
+      return
+    }
+    state.error = undefined
     state.displayList = [...res]
     state.displayItems = [...res.map((value, index) => {
-      return { id: index, label: value.meta.name, value: value.deviceId }
+      return { id: index, label: value.nickname ?? value.meta.name, value: value.deviceId }
     })]
   })
 
 
-  useVisibleTask$(async () => {
+  useVisibleTask$(async ({ cleanup }) => {
     getItemList()
+    // monitors plugged in or out
+    const unlisten = await listen("monitors_changed", () => getItemList())
+    // slideshow moved on
+    const unlistenWallpaper = await listen<Display>("wallpaper_changed", (event) => {
+      const index = state.displayList.findIndex((d) => d.deviceId === event.payload.deviceId)
+      if (index >= 0) {
+        state.displayList[index] = event.payload
+      }
+    })
+    cleanup(() => {
+      unlisten()
+      unlistenWallpaper()
+    })
   })
 
 
   return (
     <div class="">
+      {state.error && <p class="error">{state.error}</p>}
       <DisplaySelector></DisplaySelector>
     </div>
   );